@@ -47,3 +47,15 @@ where
         .json::<RespBody>()
         .map_err(Error::DeserialiseError)?)
 }
+
+pub async fn delete<RespBody>(url: url::Url) -> Result<RespBody, Error>
+where
+    for<'a> RespBody: serde::Deserialize<'a>,
+{
+    Ok(crate::net::Request::build(crate::net::Method::DELETE, url)
+        .finish()
+        .fetch()
+        .await?
+        .json::<RespBody>()
+        .map_err(Error::DeserialiseError)?)
+}
@@ -7,6 +7,8 @@ mod def;
 mod net;
 mod people;
 mod photos;
+mod review;
+mod trash;
 
 use cow_path::CowPath;
 
@@ -46,6 +48,8 @@ type SharedState = std::rc::Rc<State>;
 pub enum Path {
     Photos(photos::Path),
     People(people::Path),
+    Review,
+    Trash,
     NotFound(std::path::PathBuf),
 }
 
@@ -64,6 +68,8 @@ impl Path {
         match (self, prefix) {
             (Self::Photos(a), Self::Photos(b)) => a.starts_with(b),
             (Self::People(a), Self::People(b)) => a.starts_with(b),
+            (Self::Review, Self::Review) => true,
+            (Self::Trash, Self::Trash) => true,
             _ => false,
         }
     }
@@ -102,6 +108,8 @@ impl<'a> std::convert::TryFrom<std::path::Components<'a>> for Path {
             Some(Component::Normal(c)) if c == "people" => {
                 Ok(Path::from(people::Path::try_from(components)?))
             }
+            Some(Component::Normal(c)) if c == "review" => Ok(Path::Review),
+            Some(Component::Normal(c)) if c == "trash" => Ok(Path::Trash),
             _ => Err(FromPathError::InvalidPath),
         }
     }
@@ -112,6 +120,8 @@ impl From<Path> for CowPath {
         match path {
             Path::Photos(sub_path) => CowPath::from("photos").join(CowPath::from(sub_path)),
             Path::People(sub_path) => CowPath::from("people").join(CowPath::from(sub_path)),
+            Path::Review => CowPath::from("review"),
+            Path::Trash => CowPath::from("trash"),
             Path::NotFound(path) => CowPath::from(path),
         }
     }
@@ -136,7 +146,9 @@ fn nav_bar(state: &State) -> dominator::Dom {
         .attribute("id", "nav-bar")
         .children(&mut [
             make_link("Photos", Path::Photos(photos::Path::Root)),
-            make_link("People", Path::People(people::Path::Root))
+            make_link("People", Path::People(people::Path::Root)),
+            make_link("Review", Path::Review),
+            make_link("Trash", Path::Trash)
         ])
     })
 }
@@ -156,6 +168,8 @@ fn root(state: SharedState) -> dominator::Dom {
                 match path {
                     Path::Photos(sub_path) => photos::root(state.clone(), &sub_path),
                     Path::People(sub_path) => people::root(state.clone(), &sub_path),
+                    Path::Review => review::root(state.clone()),
+                    Path::Trash => trash::root(state.clone()),
                     Path::NotFound(_) => path_not_found(),
                 },
             ]
@@ -219,6 +233,16 @@ pub fn crackers() -> Path {
     Path::try_from_path(std::path::Path::new(&window.location().pathname().unwrap())).unwrap()
 }
 
+/// The IANA name of the time zone that the browser is in, where it says.
+pub fn time_zone() -> Option<String> {
+    let options = js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new())
+        .resolved_options();
+
+    js_sys::Reflect::get(&options, &wasm_bindgen::JsValue::from_str("timeZone"))
+        .ok()
+        .and_then(|time_zone| time_zone.as_string())
+}
+
 mod history {
     pub fn bind(state: crate::SharedState) {
         use futures_signals::signal::SignalExt;
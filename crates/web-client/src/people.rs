@@ -1,5 +1,5 @@
 use crate::CowPath;
-use dominator::{html, Dom};
+use dominator::{clone, html, Dom};
 
 #[derive(Clone)]
 pub enum Path {
@@ -117,21 +117,96 @@ fn plate(
     })
 }
 
-fn people(state: crate::SharedState) -> Dom {
-    type MutablePeople = futures_signals::signal_vec::MutableVec<(i32, photos_web_core::Person)>;
+type MutablePeople = futures_signals::signal_vec::MutableVec<(i32, photos_web_core::Person)>;
+
+/// A suggested merge, along with the people it would merge.
+type Suggestion = (
+    photos_web_core::MergeSuggestion,
+    photos_web_core::Person,
+    photos_web_core::Person,
+);
+type MutableSuggestions = futures_signals::signal_vec::MutableVec<Suggestion>;
+
+fn merge_suggestion(
+    (suggestion, dst, src): &Suggestion,
+    merge_tx: futures::channel::mpsc::Sender<(i32, i32)>,
+    dismiss_tx: futures::channel::mpsc::Sender<(i32, i32)>,
+) -> Dom {
+    let (dst_id, src_id) = (suggestion.dst, suggestion.src);
+
+    let button = |text: &str, tx: futures::channel::mpsc::Sender<(i32, i32)>, ids: (i32, i32)| {
+        html!("button", {
+            .text(text)
+            .event(move |_: dominator::events::Click| {
+                use futures::SinkExt;
+                wasm_bindgen_futures::spawn_local({
+                    let mut tx = tx.clone();
+                    async move {
+                        let _ = tx.send(ids).await;
+                    }
+                })
+            })
+        })
+    };
+
+    let person = |id: i32, person: &photos_web_core::Person| {
+        html!("div", {
+            .class("person")
+            .children(&mut [
+                html!("img", {
+                    .class("avatar")
+                    .attribute("src", &format!("/api/people/{}/avatar?size=64", id))
+                }),
+                html!("span", {
+                    .text(&format!("{}", person.display_name()))
+                }),
+            ])
+        })
+    };
+
+    html!("li", {
+        .children(&mut [
+            person(dst_id, dst),
+            person(src_id, src),
+            html!("span", {
+                .class("distance")
+                .text(&format!("{:.2}", suggestion.distance))
+            }),
+            button("Merge", merge_tx, (src_id, dst_id)),
+            button("Dismiss", dismiss_tx, (dst_id, src_id)),
+        ])
+    })
+}
 
+fn people(state: crate::SharedState) -> Dom {
     let render = {
         let state = state.clone();
-        move |(people, merge_tx): &(
+        move |(people, suggestions, merge_tx, dismiss_tx): &(
             std::sync::Arc<MutablePeople>,
+            std::sync::Arc<MutableSuggestions>,
+            futures::channel::mpsc::Sender<(i32, i32)>,
             futures::channel::mpsc::Sender<(i32, i32)>,
         )| {
             use futures_signals::signal_vec::SignalVecExt;
             let state = state.clone();
-            let merge_tx = merge_tx.clone();
-            people
-                .signal_vec_cloned()
-                .map(move |(id, person)| plate(state.clone(), id, &person, merge_tx.clone()))
+            vec![
+                html!("ul", {
+                    .attribute("id", "merge-suggestions")
+                    .children_signal_vec(suggestions.signal_vec_cloned().map(
+                        clone!(merge_tx, dismiss_tx => move |suggestion| {
+                            merge_suggestion(&suggestion, merge_tx.clone(), dismiss_tx.clone())
+                        })
+                    ))
+                }),
+                html!("ul", {
+                    .attribute("id", "people")
+                    .children_signal_vec(people.signal_vec_cloned().map(
+                        clone!(merge_tx => move |(id, person)| {
+                            plate(state.clone(), id, &person, merge_tx.clone())
+                        })
+                    ))
+                }),
+            ]
         }
     };
 
@@ -140,31 +215,61 @@ fn people(state: crate::SharedState) -> Dom {
     ) -> Result<
         (
             std::sync::Arc<MutablePeople>,
+            std::sync::Arc<MutableSuggestions>,
+            futures::channel::mpsc::Sender<(i32, i32)>,
             futures::channel::mpsc::Sender<(i32, i32)>,
         ),
         crate::api::Error,
     > {
         let people: photos_web_core::People = crate::api::get(state.url("/api/people")).await?;
+        let suggestions: photos_web_core::MergeSuggestions =
+            crate::api::get(state.url("/api/people/merge-suggestions")).await?;
+
+        let people = people.into_inner();
+
+        let suggestions = {
+            let names: std::collections::HashMap<i32, &photos_web_core::Person> =
+                people.iter().map(|(id, person)| (*id, person)).collect();
 
-        let people = std::sync::Arc::new(MutablePeople::new_with_values(people.into_inner()));
+            std::sync::Arc::new(MutableSuggestions::new_with_values(
+                suggestions
+                    .into_inner()
+                    .into_iter()
+                    .filter_map(|suggestion| {
+                        let dst = (*names.get(&suggestion.dst)?).clone();
+                        let src = (*names.get(&suggestion.src)?).clone();
+                        Some((suggestion, dst, src))
+                    })
+                    .collect(),
+            ))
+        };
+
+        let people = std::sync::Arc::new(MutablePeople::new_with_values(people));
 
         let (merge_tx, mut merge_rx) = futures::channel::mpsc::channel(2);
         wasm_bindgen_futures::spawn_local({
+            let state = state.clone();
             let people = people.clone();
+            let suggestions = suggestions.clone();
             async move {
                 use futures::StreamExt;
                 while let Some((src, dst)) = merge_rx.next().await {
-                    match crate::api::post(
+                    match crate::api::post::<i32, _>(
                         state.url(&format!("/api/people/{}/merge/{}", dst, src)),
                         (),
                     )
                     .await
                     {
-                        Ok(()) => {
+                        Ok(_) => {
                             let mut people = people.lock_mut();
                             if let Some(index) = people.iter().position(|(id, _)| *id == src) {
                                 people.remove(index);
                             }
+
+                            // Whatever was suggested about the merged person no longer stands.
+                            suggestions.lock_mut().retain(|(suggestion, _, _)| {
+                                suggestion.dst != src && suggestion.src != src
+                            });
                         }
                         Err(err) => {
                             // Report error to user
@@ -174,16 +279,154 @@ fn people(state: crate::SharedState) -> Dom {
             }
         });
 
-        Ok((people, merge_tx))
+        let (dismiss_tx, mut dismiss_rx) = futures::channel::mpsc::channel(2);
+        wasm_bindgen_futures::spawn_local({
+            let suggestions = suggestions.clone();
+            async move {
+                use futures::StreamExt;
+                while let Some((a, b)) = dismiss_rx.next().await {
+                    match crate::api::post(
+                        state.url(&format!("/api/people/{}/distinct/{}", a, b)),
+                        (),
+                    )
+                    .await
+                    {
+                        Ok(()) => {
+                            suggestions.lock_mut().retain(|(suggestion, _, _)| {
+                                (suggestion.dst, suggestion.src) != (a, b)
+                            });
+                        }
+                        Err(_) => {
+                            // TODO report the error to the user
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((people, suggestions, merge_tx, dismiss_tx))
     };
 
-    crate::def::signal(
-        dominator::DomBuilder::new_html("ul").attribute("id", "people"),
+    crate::def::vec(
+        dominator::DomBuilder::new_html("div").attribute("id", "people-page"),
         move || update(state.clone()),
         render,
     )
 }
 
+type MutableMerges = futures_signals::signal_vec::MutableVec<(i32, photos_web_core::Merge)>;
+
+fn merge_entry(
+    merge_id: i32,
+    merge: &photos_web_core::Merge,
+    undo_tx: futures::channel::mpsc::Sender<i32>,
+) -> Dom {
+    html!("li", {
+        .children(&mut merge.avatar.iter().map(|appearance_id| {
+            html!("img", {
+                .class("avatar")
+                .attribute("src", &format!("/api/appearances/{}/image", appearance_id))
+            })
+        }).collect::<Vec<_>>())
+        .children(&mut [
+            html!("span", {
+                .class("name")
+                .text(&format!("{}", merge.person.display_name()))
+            }),
+            html!("span", {
+                .class("datetime")
+                .text(&format!("Merged {}", merge.merged_datetime.format("%Y-%m-%d %H:%M")))
+            }),
+        ])
+        .children(&mut match merge.undone_datetime {
+            Some(undone_datetime) => vec![html!("span", {
+                .class("datetime")
+                .text(&format!("Undone {}", undone_datetime.format("%Y-%m-%d %H:%M")))
+            })],
+            None => vec![html!("button", {
+                .text("Undo")
+                .event(move |_: dominator::events::Click| {
+                    use futures::SinkExt;
+                    wasm_bindgen_futures::spawn_local({
+                        let mut undo_tx = undo_tx.clone();
+                        async move {
+                            let _ = undo_tx.send(merge_id).await;
+                        }
+                    })
+                })
+            })],
+        })
+    })
+}
+
+/// The people merged into a person, each of which can be split back out.
+fn merges(state: crate::SharedState, id: i32) -> Dom {
+    let render = |(merges, undo_tx): &(
+        std::sync::Arc<MutableMerges>,
+        futures::channel::mpsc::Sender<i32>,
+    )| {
+        use futures_signals::signal_vec::SignalVecExt;
+        vec![html!("ul", {
+            .class("merges")
+            .children_signal_vec(merges.signal_vec_cloned().map(
+                clone!(undo_tx => move |(merge_id, merge)| {
+                    merge_entry(merge_id, &merge, undo_tx.clone())
+                })
+            ))
+        })]
+    };
+
+    async fn update(
+        state: crate::SharedState,
+        id: i32,
+    ) -> Result<
+        (std::sync::Arc<MutableMerges>, futures::channel::mpsc::Sender<i32>),
+        crate::api::Error,
+    > {
+        let merges: photos_web_core::Merges =
+            crate::api::get(state.url(&format!("/api/people/{}/merges", id))).await?;
+
+        let merges = std::sync::Arc::new(MutableMerges::new_with_values(merges.into_inner()));
+
+        let (undo_tx, mut undo_rx) = futures::channel::mpsc::channel(2);
+        wasm_bindgen_futures::spawn_local({
+            let merges = merges.clone();
+            async move {
+                use futures::StreamExt;
+                while let Some(merge_id) = undo_rx.next().await {
+                    match crate::api::post::<i32, _>(
+                        state.url(&format!("/api/merges/{}/undo", merge_id)),
+                        (),
+                    )
+                    .await
+                    {
+                        Ok(_) => {
+                            // Fetch the list again to pick up when the merge was undone.
+                            let fetched: Result<photos_web_core::Merges, _> =
+                                crate::api::get(state.url(&format!("/api/people/{}/merges", id)))
+                                    .await;
+                            if let Ok(fetched) = fetched {
+                                merges.lock_mut().replace_cloned(fetched.into_inner());
+                            }
+                        }
+                        Err(_) => {
+                            // TODO report the error to the user
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((merges, undo_tx))
+    }
+
+    crate::def::vec(
+        dominator::DomBuilder::new_html("div").class("merges"),
+        move || update(state.clone(), id),
+        render,
+    )
+}
+
 fn person(state: crate::SharedState, id: i32) -> Dom {
     use futures_signals::signal::Mutable;
 
@@ -279,10 +522,12 @@ fn person(state: crate::SharedState, id: i32) -> Dom {
 
             vec![
                 header,
+                merges(state.clone(), *id),
                 crate::photos::collection(
                     state.clone(),
                     photos_web_core::PhotoQueryParams {
                         people: Some(vec![*id]),
+                        ..Default::default()
                     },
                 ),
             ]
@@ -111,7 +111,11 @@ pub fn collection_entry(
 }
 
 pub fn collection(state: super::SharedState, params: Params) -> Dom {
-    let params = SharedParams::new(params);
+    // Photos are grouped into the days of wherever they're being looked at.
+    let params = SharedParams::new(Params {
+        time_zone: crate::time_zone(),
+        ..params
+    });
 
     let update = {
         let state = state.clone();
@@ -206,22 +210,105 @@ fn appearance_detail(
     appearance_id: i32,
     appearance: photos_web_core::Appearance,
 ) -> Dom {
-    let render = move |person: &photos_web_core::Person| -> Vec<Dom> {
-        vec![html!("div", {
-            .text(&format!("{}", person.display_name()))
-        })]
+    type Suggestions = Vec<(i32, photos_web_core::Person, f64)>;
+
+    let person_id = appearance.person;
+
+    let render = {
+        let state = state.clone();
+        move |(person, suggestions): &(Option<photos_web_core::Person>, Suggestions)| -> Vec<Dom> {
+            let mut children = vec![html!("div", {
+                .text(&match person {
+                    Some(person) => format!("{}", person.display_name()),
+                    None => String::from("Unknown"),
+                })
+            })];
+
+            if let Some(person_id) = person_id {
+                children.push(html!("button", {
+                    .text("Use as avatar")
+                    .event(clone!(state => move |_: dominator::events::Click| {
+                        wasm_bindgen_futures::spawn_local(clone!(state => async move {
+                            let _: Result<(), _> = crate::api::put(
+                                state.url(&format!("/api/people/{}/avatar", person_id)),
+                                appearance_id,
+                            )
+                            .await;
+                        }));
+                    }))
+                }));
+            }
+
+            if !suggestions.is_empty() {
+                children.push(html!("div", {
+                    .class("suggestions-prompt")
+                    .text("Is this...")
+                }));
+            }
+
+            for (suggested_id, suggested, distance) in suggestions.iter() {
+                let suggested_id = *suggested_id;
+                children.push(html!("div", {
+                    .class("suggestion")
+                    .text(&format!("{} ({:.2})?", suggested.display_name(), distance))
+                    .event(clone!(state => move |_: dominator::events::Click| {
+                        if person_id != Some(suggested_id) {
+                            wasm_bindgen_futures::spawn_local(clone!(state => async move {
+                                let _: Result<(), _> = crate::api::put(
+                                    state.url(&format!("/api/appearances/{}", appearance_id)),
+                                    photos_web_core::AppearanceUpdate {
+                                        person: Some(suggested_id),
+                                        reference: false,
+                                    },
+                                )
+                                .await;
+                            }));
+                        }
+                    }))
+                }));
+            }
+
+            children
+        }
     };
 
     async fn update(
         state: crate::SharedState,
-        person_id: i32,
-    ) -> Result<photos_web_core::Person, crate::api::Error> {
-        crate::api::get(state.url(&format!("/api/people/{}", person_id))).await
+        appearance_id: i32,
+        person_id: Option<i32>,
+    ) -> Result<(Option<photos_web_core::Person>, Suggestions), crate::api::Error> {
+        let person = match person_id {
+            Some(person_id) => Some(
+                crate::api::get(state.url(&format!("/api/people/{}", person_id))).await?,
+            ),
+            None => None,
+        };
+
+        let suggestions: photos_web_core::Suggestions = crate::api::get(
+            state.url(&format!("/api/appearances/{}/suggestions", appearance_id)),
+        )
+        .await?;
+
+        let people: photos_web_core::People = crate::api::get(state.url("/api/people")).await?;
+        let people: std::collections::HashMap<i32, photos_web_core::Person> =
+            people.into_inner().into_iter().collect();
+
+        let suggestions = suggestions
+            .into_inner()
+            .into_iter()
+            .filter_map(|suggestion| {
+                people
+                    .get(&suggestion.person)
+                    .map(|person| (suggestion.person, person.clone(), suggestion.distance))
+            })
+            .collect();
+
+        Ok((person, suggestions))
     }
 
     crate::def::vec(
         dominator::DomBuilder::new_html("div"),
-        move || update(state.clone(), appearance.person),
+        move || update(state.clone(), appearance_id, person_id),
         render,
     )
 }
@@ -247,7 +334,10 @@ fn appearance_gallery(
                             None => false
                         }
                     ))
-                    .attribute("src", &format!("/api/people/{}/avatar?size=64", appearance.person))
+                    .attribute("src", &match appearance.person {
+                        Some(person_id) => format!("/api/people/{}/avatar?size=64", person_id),
+                        None => format!("/api/appearances/{}/image", id),
+                    })
                     .event({
                         let selected_appearance = selected_appearance.clone();
                         move |_: dominator::events::Click| {
@@ -279,9 +369,25 @@ fn appearance_gallery(
     )
 }
 
+fn trash_button(state: super::SharedState, id: i32) -> Dom {
+    html!("button", {
+        .class("trash")
+        .text("Move to trash")
+        .event(clone!(state => move |_: dominator::events::Click| {
+            wasm_bindgen_futures::spawn_local(clone!(state => async move {
+                let result: Result<(), _> =
+                    crate::api::delete(state.url(&format!("/api/photos/{}", id))).await;
+                if result.is_ok() {
+                    state.path.set(crate::Path::from(Path::Root));
+                }
+            }));
+        }))
+    })
+}
+
 pub fn photo(state: super::SharedState, id: i32) -> Dom {
     fn render(state: super::SharedState, id: i32, photo: &photos_web_core::Photo) -> Vec<Dom> {
-        vec![frame(photo), info(state, id)]
+        vec![frame(photo), trash_button(state.clone(), id), info(state, id)]
     }
 
     async fn update(
@@ -0,0 +1,147 @@
+use dominator::{clone, html, Dom};
+
+/// An appearance awaiting review, with the person it was matched to and how closely.
+type Entry = (i32, i32, Option<f64>, photos_web_core::Person);
+type MutableEntries = futures_signals::signal_vec::MutableVec<Entry>;
+
+#[derive(Clone, Copy)]
+enum Verdict {
+    Confirm,
+    Reject,
+}
+
+impl Verdict {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            Self::Confirm => "confirm",
+            Self::Reject => "reject",
+        }
+    }
+}
+
+fn entry(
+    (id, person_id, distance, person): &Entry,
+    verdict_tx: futures::channel::mpsc::Sender<(i32, Verdict)>,
+) -> Dom {
+    let id = *id;
+
+    let button = move |text: &str, verdict: Verdict| {
+        html!("button", {
+            .text(text)
+            .event(clone!(verdict_tx => move |_: dominator::events::Click| {
+                use futures::SinkExt;
+                wasm_bindgen_futures::spawn_local({
+                    let mut verdict_tx = verdict_tx.clone();
+                    async move {
+                        let _ = verdict_tx.send((id, verdict)).await;
+                    }
+                })
+            }))
+        })
+    };
+
+    html!("li", {
+        .children(&mut [
+            html!("img", {
+                .class("avatar")
+                .attribute("src", &format!("/api/appearances/{}/image", id))
+            }),
+            html!("img", {
+                .class("avatar")
+                .attribute("src", &format!("/api/people/{}/avatar?size=64", person_id))
+            }),
+            html!("span", {
+                .text(&match distance {
+                    Some(distance) => format!("{} ({:.2})", person.display_name(), distance),
+                    None => format!("{}", person.display_name()),
+                })
+            }),
+            button("Confirm", Verdict::Confirm),
+            button("Reject", Verdict::Reject),
+        ])
+    })
+}
+
+fn review(state: crate::SharedState) -> Dom {
+    let render = move |(entries, verdict_tx): &(
+        std::sync::Arc<MutableEntries>,
+        futures::channel::mpsc::Sender<(i32, Verdict)>,
+    )| {
+        use futures_signals::signal_vec::SignalVecExt;
+        let verdict_tx = verdict_tx.clone();
+        entries
+            .signal_vec_cloned()
+            .map(move |review_entry| entry(&review_entry, verdict_tx.clone()))
+    };
+
+    async fn update(
+        state: crate::SharedState,
+    ) -> Result<
+        (
+            std::sync::Arc<MutableEntries>,
+            futures::channel::mpsc::Sender<(i32, Verdict)>,
+        ),
+        crate::api::Error,
+    > {
+        let appearances: photos_web_core::Appearances =
+            crate::api::get(state.url("/api/review")).await?;
+
+        let people: photos_web_core::People = crate::api::get(state.url("/api/people")).await?;
+        let people: std::collections::HashMap<i32, photos_web_core::Person> =
+            people.into_inner().into_iter().collect();
+
+        let entries = std::sync::Arc::new(MutableEntries::new_with_values(
+            appearances
+                .into_inner()
+                .into_iter()
+                .filter_map(|(id, appearance)| {
+                    appearance.person.and_then(|person_id| {
+                        people.get(&person_id).map(|person| {
+                            (id, person_id, appearance.match_distance, person.clone())
+                        })
+                    })
+                })
+                .collect(),
+        ));
+
+        let (verdict_tx, mut verdict_rx) = futures::channel::mpsc::channel(2);
+        wasm_bindgen_futures::spawn_local({
+            let entries = entries.clone();
+            async move {
+                use futures::StreamExt;
+                while let Some((id, verdict)) = verdict_rx.next().await {
+                    match crate::api::post(
+                        state.url(&format!("/api/appearances/{}/{}", id, verdict.endpoint())),
+                        (),
+                    )
+                    .await
+                    {
+                        Ok(()) => {
+                            let mut entries = entries.lock_mut();
+                            if let Some(index) =
+                                entries.iter().position(|(entry_id, _, _, _)| *entry_id == id)
+                            {
+                                entries.remove(index);
+                            }
+                        }
+                        Err(_) => {
+                            // TODO report the error to the user
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((entries, verdict_tx))
+    }
+
+    crate::def::signal(
+        dominator::DomBuilder::new_html("ul").attribute("id", "review"),
+        move || update(state.clone()),
+        render,
+    )
+}
+
+pub fn root(state: crate::SharedState) -> Dom {
+    review(state)
+}
@@ -0,0 +1,126 @@
+use dominator::{clone, html, Dom};
+
+type Entry = (i32, photos_web_core::TrashedPhoto);
+type MutableEntries = futures_signals::signal_vec::MutableVec<Entry>;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Restore,
+    Purge,
+}
+
+fn entry(
+    (id, trashed): &Entry,
+    action_tx: futures::channel::mpsc::Sender<(i32, Action)>,
+) -> Dom {
+    let id = *id;
+
+    let button = move |text: &str, action: Action| {
+        html!("button", {
+            .text(text)
+            .event(clone!(action_tx => move |_: dominator::events::Click| {
+                use futures::SinkExt;
+                wasm_bindgen_futures::spawn_local({
+                    let mut action_tx = action_tx.clone();
+                    async move {
+                        let _ = action_tx.send((id, action)).await;
+                    }
+                })
+            }))
+        })
+    };
+
+    html!("li", {
+        .children(&mut [
+            html!("img", {
+                .attribute("src", &format!("/static/trash/thumbs/{}", trashed.photo.file_name))
+                .attribute("width", &trashed.photo.thumb_width.to_string())
+                .attribute("height", &trashed.photo.thumb_height.to_string())
+            }),
+            html!("span", {
+                .class("datetime")
+                .text(&format!("Trashed {}", trashed.trashed_datetime.format("%Y-%m-%d %H:%M")))
+            }),
+            button("Restore", Action::Restore),
+            button("Delete forever", Action::Purge),
+        ])
+    })
+}
+
+fn trash(state: crate::SharedState) -> Dom {
+    let render = move |(entries, action_tx): &(
+        std::sync::Arc<MutableEntries>,
+        futures::channel::mpsc::Sender<(i32, Action)>,
+    )| {
+        use futures_signals::signal_vec::SignalVecExt;
+        let action_tx = action_tx.clone();
+        entries
+            .signal_vec_cloned()
+            .map(move |trash_entry| entry(&trash_entry, action_tx.clone()))
+    };
+
+    async fn update(
+        state: crate::SharedState,
+    ) -> Result<
+        (
+            std::sync::Arc<MutableEntries>,
+            futures::channel::mpsc::Sender<(i32, Action)>,
+        ),
+        crate::api::Error,
+    > {
+        let photos: photos_web_core::TrashedPhotos =
+            crate::api::get(state.url("/api/trash")).await?;
+
+        let entries = std::sync::Arc::new(MutableEntries::new_with_values(photos.into_inner()));
+
+        let (action_tx, mut action_rx) = futures::channel::mpsc::channel(2);
+        wasm_bindgen_futures::spawn_local({
+            let entries = entries.clone();
+            async move {
+                use futures::StreamExt;
+                while let Some((id, action)) = action_rx.next().await {
+                    let result = match action {
+                        Action::Restore => {
+                            crate::api::post::<(), _>(
+                                state.url(&format!("/api/trash/{}/restore", id)),
+                                (),
+                            )
+                            .await
+                        }
+                        Action::Purge => crate::api::delete::<photos_web_core::DeletedPhoto>(
+                            state.url(&format!("/api/trash/{}", id)),
+                        )
+                        .await
+                        .map(|_| ()),
+                    };
+
+                    match result {
+                        Ok(()) => {
+                            let mut entries = entries.lock_mut();
+                            if let Some(index) =
+                                entries.iter().position(|(entry_id, _)| *entry_id == id)
+                            {
+                                entries.remove(index);
+                            }
+                        }
+                        Err(_) => {
+                            // TODO report the error to the user
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((entries, action_tx))
+    }
+
+    crate::def::signal(
+        dominator::DomBuilder::new_html("ul").attribute("id", "trash"),
+        move || update(state.clone()),
+        render,
+    )
+}
+
+pub fn root(state: crate::SharedState) -> Dom {
+    trash(state)
+}
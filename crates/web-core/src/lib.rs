@@ -18,9 +18,22 @@ impl From<&dyn std::error::Error> for ErrorDesc {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct AvatarQueryParams {
     pub size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    /// Whether to mask out everything beyond the circle that fits within the avatar.
+    #[serde(default)]
+    pub circle: bool,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -84,6 +97,10 @@ impl People {
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct PhotoQueryParams {
     pub people: Option<Vec<i32>>,
+    /// The IANA name of the time zone that photos are grouped into days in, the server's default
+    /// time zone being used if there isn't one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -96,6 +113,9 @@ pub struct Photo {
     #[serde(with = "crate::serde_util::datetime_ts_seconds_opt")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub original_datetime: Option<chrono::NaiveDateTime>,
+    /// Seconds ahead of UTC that the original datetime is, where the camera recorded it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_offset: Option<i32>,
     #[serde(with = "chrono::naive::serde::ts_seconds")]
     pub upload_datetime: chrono::NaiveDateTime,
 }
@@ -124,15 +144,95 @@ impl Photos {
     }
 }
 
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PhotoUpdate {
+    /// When the photo was taken, or `None` for it to be placed by when it was uploaded instead.
+    #[serde(default, with = "crate::serde_util::datetime_ts_seconds_opt")]
+    pub original_datetime: Option<chrono::NaiveDateTime>,
+    /// Seconds ahead of UTC that the original datetime is, or `None` for it to be taken as being
+    /// in the server's default time zone.
+    #[serde(default)]
+    pub original_offset: Option<i32>,
+}
+
+/// Which way to turn a photo, clockwise, or to flip it from left to right.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Rotation {
+    #[serde(rename = "90")]
+    Clockwise90,
+    #[serde(rename = "180")]
+    Clockwise180,
+    #[serde(rename = "270")]
+    Clockwise270,
+    #[serde(rename = "flip")]
+    Flip,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PhotoRotation {
+    pub rotation: Rotation,
+}
+
+/// Moves when each of the photos was taken, as when the camera's clock was wrong. Photos that
+/// aren't known to have been taken at any particular time are left as they are.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TimeShift {
+    pub photos: Vec<i32>,
+    /// How far to move each photo's time, which is back if negative.
+    pub seconds: i64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TrashedPhoto {
+    #[serde(flatten)]
+    pub photo: Photo,
+    pub trashed_datetime: chrono::NaiveDateTime,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct TrashedPhotos(#[serde(with = "tuple_vec_map")] Vec<(i32, TrashedPhoto)>);
+
+impl From<Vec<(i32, TrashedPhoto)>> for TrashedPhotos {
+    fn from(photos: Vec<(i32, TrashedPhoto)>) -> Self {
+        Self(photos)
+    }
+}
+
+impl TrashedPhotos {
+    pub fn into_inner(self) -> Vec<(i32, TrashedPhoto)> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(i32, TrashedPhoto)> {
+        self.0.iter()
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Appearance {
-    pub person: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub person: Option<i32>,
     pub photo: i32,
     pub reference: bool,
     pub top: i32,
     pub left: i32,
     pub bottom: i32,
     pub right: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_distance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_appearance: Option<i32>,
+    pub confirmed: bool,
+    /// The 68 points located on the face, as `(x, y)` pairs, when asked for and known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub landmarks: Option<Vec<(i32, i32)>>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct AppearanceQueryParams {
+    #[serde(default)]
+    pub landmarks: bool,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -162,3 +262,372 @@ impl Appearances {
         self.0.iter()
     }
 }
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AcceptedPhoto {
+    pub photo: i32,
+    pub job: i32,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct PurgeQueryParams {
+    /// Whether to also delete the people who appeared in the photo and nowhere else.
+    #[serde(default)]
+    pub delete_empty_people: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct DeletedPhoto {
+    pub appearances: Vec<i32>,
+    pub people: Vec<i32>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct JobQueryParams {
+    pub status: Option<JobStatus>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Job {
+    pub photo: i32,
+    pub status: JobStatus,
+    pub attempts: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(with = "chrono::naive::serde::ts_seconds")]
+    pub created_datetime: chrono::NaiveDateTime,
+    #[serde(with = "chrono::naive::serde::ts_seconds")]
+    pub updated_datetime: chrono::NaiveDateTime,
+    /// Whether the job runs detection over its photo again, rather than for the first time.
+    #[serde(default)]
+    pub reprocess: bool,
+    /// The faces that a reprocessing job added, removed and changed once it's completed. Only
+    /// given when the job is fetched on its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<ReprocessedPhoto>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct Jobs(#[serde(with = "tuple_vec_map")] Vec<(i32, Job)>);
+
+impl<T, I> From<I> for Jobs
+where
+    T: Into<Job>,
+    I: IntoIterator<Item = (i32, T)>,
+{
+    fn from(i: I) -> Self {
+        Self(i.into_iter().map(|(id, job)| (id, job.into())).collect())
+    }
+}
+
+impl Jobs {
+    pub fn into_inner(self) -> Vec<(i32, Job)> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(i32, Job)> {
+        self.0.iter()
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct FaceClusterQueryParams {
+    pub min_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct FaceCluster {
+    pub appearances: Vec<i32>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct FaceClusters(Vec<FaceCluster>);
+
+impl From<Vec<FaceCluster>> for FaceClusters {
+    fn from(clusters: Vec<FaceCluster>) -> Self {
+        Self(clusters)
+    }
+}
+
+impl FaceClusters {
+    pub fn into_inner(self) -> Vec<FaceCluster> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FaceCluster> {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PromotedFaceCluster {
+    pub person: Person,
+    pub appearances: Vec<i32>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SuggestionQueryParams {
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Suggestion {
+    pub person: i32,
+    pub distance: f64,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct Suggestions(Vec<Suggestion>);
+
+impl From<Vec<Suggestion>> for Suggestions {
+    fn from(suggestions: Vec<Suggestion>) -> Self {
+        Self(suggestions)
+    }
+}
+
+impl Suggestions {
+    pub fn into_inner(self) -> Vec<Suggestion> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Suggestion> {
+        self.0.iter()
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct FaceSearchQueryParams {
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct FoundFace {
+    pub top: i32,
+    pub left: i32,
+    pub bottom: i32,
+    pub right: i32,
+    pub candidates: Vec<Suggestion>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct FoundFaces(Vec<FoundFace>);
+
+impl From<Vec<FoundFace>> for FoundFaces {
+    fn from(faces: Vec<FoundFace>) -> Self {
+        Self(faces)
+    }
+}
+
+impl FoundFaces {
+    pub fn into_inner(self) -> Vec<FoundFace> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FoundFace> {
+        self.0.iter()
+    }
+}
+
+/// How the distance between two people is measured from their faces.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Linkage {
+    /// The distance between their mean faces.
+    #[serde(rename = "centroid")]
+    Centroid,
+    /// The distance between their closest two faces.
+    #[serde(rename = "min-linkage")]
+    Min,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct MergeSuggestionQueryParams {
+    pub linkage: Option<Linkage>,
+    pub max_distance: Option<f64>,
+    pub count: Option<usize>,
+}
+
+/// Two people that look like they might be the same person. `dst` is whichever of them has more
+/// faces to go on, and so the one to merge the other into.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct MergeSuggestion {
+    pub dst: i32,
+    pub src: i32,
+    pub distance: f64,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct MergeSuggestions(Vec<MergeSuggestion>);
+
+impl From<Vec<MergeSuggestion>> for MergeSuggestions {
+    fn from(suggestions: Vec<MergeSuggestion>) -> Self {
+        Self(suggestions)
+    }
+}
+
+impl MergeSuggestions {
+    pub fn into_inner(self) -> Vec<MergeSuggestion> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MergeSuggestion> {
+        self.0.iter()
+    }
+}
+
+/// A person merged into another, as they were beforehand.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Merge {
+    pub dst: i32,
+    pub src: i32,
+    pub person: Person,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<i32>,
+    pub appearances: Vec<i32>,
+    pub merged_datetime: chrono::NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undone_datetime: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct Merges(#[serde(with = "tuple_vec_map")] Vec<(i32, Merge)>);
+
+impl From<Vec<(i32, Merge)>> for Merges {
+    fn from(merges: Vec<(i32, Merge)>) -> Self {
+        Self(merges)
+    }
+}
+
+impl Merges {
+    pub fn into_inner(self) -> Vec<(i32, Merge)> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(i32, Merge)> {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AppearanceUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub person: Option<i32>,
+    pub reference: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SplitPerson {
+    pub person: Person,
+    pub appearances: Vec<i32>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct NewAppearance {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub person: Option<i32>,
+    pub top: i32,
+    pub left: i32,
+    pub bottom: i32,
+    pub right: i32,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReviewQueryParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchStrategy {
+    Nearest,
+    Vote,
+    Centroid,
+}
+
+/// Overrides for how to re-match faces, falling back to the server's own settings. Nothing is
+/// recorded unless `apply` is set.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Rematch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<MatchStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neighbour_count: Option<usize>,
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RematchOutcome {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_person: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub person: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct RematchOutcomes(#[serde(with = "tuple_vec_map")] Vec<(i32, RematchOutcome)>);
+
+impl From<Vec<(i32, RematchOutcome)>> for RematchOutcomes {
+    fn from(outcomes: Vec<(i32, RematchOutcome)>) -> Self {
+        Self(outcomes)
+    }
+}
+
+impl RematchOutcomes {
+    pub fn into_inner(self) -> Vec<(i32, RematchOutcome)> {
+        self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(i32, RematchOutcome)> {
+        self.0.iter()
+    }
+}
+
+/// Picks the photos to reprocess, each of which is given a job. Every photo is picked if nothing
+/// is given. Faces are looked for as the server was started to, except where the detection
+/// settings are given.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Reprocess {
+    #[serde(default)]
+    pub photos: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<chrono::NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub without_appearances: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_face_pixels: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_face_fraction: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upsample: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_image_size: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct ReprocessedPhoto {
+    pub added: Vec<i32>,
+    pub removed: Vec<i32>,
+    pub changed: Vec<i32>,
+}
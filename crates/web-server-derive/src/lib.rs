@@ -2,16 +2,42 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 
+/// Implements `ResponseError`, answering with a 500 unless a variant is given another status with
+/// `#[status_code(NOT_FOUND)]`, naming one of the `StatusCode` constants.
 #[proc_macro_derive(ApiError, attributes(status_code))]
 pub fn derive_response_error(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
 
     let name = input.ident;
 
+    let mut arms = Vec::new();
+    if let syn::Data::Enum(data) = &input.data {
+        for variant in data.variants.iter() {
+            for attr in variant.attrs.iter() {
+                if !attr.path.is_ident("status_code") {
+                    continue;
+                }
+
+                let status_code = match attr.parse_args::<syn::Ident>() {
+                    Ok(status_code) => status_code,
+                    Err(err) => return TokenStream::from(err.to_compile_error()),
+                };
+                let variant_name = &variant.ident;
+                arms.push(quote::quote! {
+                    Self::#variant_name { .. } => ::actix_web::http::StatusCode::#status_code,
+                });
+            }
+        }
+    }
+
     TokenStream::from(quote::quote! {
         impl ::actix_web::ResponseError for #name {
             fn status_code(&self) -> ::actix_web::http::StatusCode {
-                ::actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+                #[allow(unreachable_patterns)]
+                match self {
+                    #(#arms)*
+                    _ => ::actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                }
             }
 
             fn error_response(&self) -> ::actix_web::web::HttpResponse<::actix_web::body::Body> {
@@ -0,0 +1,11 @@
+CREATE TABLE jobs (
+  id               INTEGER PRIMARY KEY NOT NULL,
+  photo            INTEGER NOT NULL REFERENCES photos(id),
+  status           TEXT NOT NULL,
+  attempts         INTEGER NOT NULL,
+  error            TEXT,
+  created_datetime DATETIME NOT NULL,
+  updated_datetime DATETIME NOT NULL
+);
+
+CREATE INDEX jobs_by_status ON jobs(status);
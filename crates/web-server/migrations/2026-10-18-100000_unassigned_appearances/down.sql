@@ -0,0 +1,27 @@
+CREATE TABLE avatars_backup AS SELECT * FROM avatars;
+DROP TABLE avatars;
+
+CREATE TABLE appearances_new (
+  id            INTEGER PRIMARY KEY NOT NULL,
+  person        INTEGER NOT NULL REFERENCES people(id),
+  photo         INTEGER NOT NULL REFERENCES photos(id),
+  reference     BOOLEAN NOT NULL,
+  top           INTEGER NOT NULL,
+  left          INTEGER NOT NULL,
+  bottom        INTEGER NOT NULL,
+  right         INTEGER NOT NULL,
+  face_encoding BLOB NOT NULL
+);
+
+INSERT INTO appearances_new SELECT * FROM appearances WHERE person IS NOT NULL;
+DROP TABLE appearances;
+ALTER TABLE appearances_new RENAME TO appearances;
+
+CREATE TABLE avatars (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  person     INTEGER NOT NULL UNIQUE REFERENCES people(id),
+  appearance INTEGER NOT NULL REFERENCES appearances(id)
+);
+
+INSERT INTO avatars SELECT * FROM avatars_backup;
+DROP TABLE avatars_backup;
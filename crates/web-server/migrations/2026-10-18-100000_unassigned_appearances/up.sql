@@ -0,0 +1,29 @@
+-- SQLite can't relax a NOT NULL constraint in place, so the table has to be rebuilt. Avatars
+-- reference appearances, so they're set aside while that happens.
+CREATE TABLE avatars_backup AS SELECT * FROM avatars;
+DROP TABLE avatars;
+
+CREATE TABLE appearances_new (
+  id            INTEGER PRIMARY KEY NOT NULL,
+  person        INTEGER REFERENCES people(id),
+  photo         INTEGER NOT NULL REFERENCES photos(id),
+  reference     BOOLEAN NOT NULL,
+  top           INTEGER NOT NULL,
+  left          INTEGER NOT NULL,
+  bottom        INTEGER NOT NULL,
+  right         INTEGER NOT NULL,
+  face_encoding BLOB NOT NULL
+);
+
+INSERT INTO appearances_new SELECT * FROM appearances;
+DROP TABLE appearances;
+ALTER TABLE appearances_new RENAME TO appearances;
+
+CREATE TABLE avatars (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  person     INTEGER NOT NULL UNIQUE REFERENCES people(id),
+  appearance INTEGER NOT NULL REFERENCES appearances(id)
+);
+
+INSERT INTO avatars SELECT * FROM avatars_backup;
+DROP TABLE avatars_backup;
@@ -0,0 +1,2 @@
+DROP TABLE person_exclusions;
+DROP TABLE appearance_exclusions;
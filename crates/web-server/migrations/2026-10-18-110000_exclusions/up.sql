@@ -0,0 +1,14 @@
+CREATE TABLE appearance_exclusions (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  appearance INTEGER NOT NULL REFERENCES appearances(id),
+  person     INTEGER NOT NULL REFERENCES people(id),
+  UNIQUE(appearance, person)
+);
+
+-- Pairs are stored with the lower person id first, so each pair only appears once.
+CREATE TABLE person_exclusions (
+  id       INTEGER PRIMARY KEY NOT NULL,
+  person_a INTEGER NOT NULL REFERENCES people(id),
+  person_b INTEGER NOT NULL REFERENCES people(id),
+  UNIQUE(person_a, person_b)
+);
@@ -0,0 +1,7 @@
+ALTER TABLE appearances ADD COLUMN match_distance DOUBLE;
+ALTER TABLE appearances ADD COLUMN match_appearance INTEGER REFERENCES appearances(id);
+ALTER TABLE appearances ADD COLUMN confirmed BOOLEAN NOT NULL DEFAULT 0;
+
+-- References were either picked by hand or were the first sighting of a person, so there's no
+-- match to review for them.
+UPDATE appearances SET confirmed = 1 WHERE reference = 1;
@@ -0,0 +1,3 @@
+-- Identifies the model that produced each face encoding. Encodings recorded before this was
+-- tracked are left without one, and so get re-encoded with whatever model is in use.
+ALTER TABLE appearances ADD COLUMN encoder TEXT;
@@ -0,0 +1,3 @@
+DROP TABLE merge_exclusions;
+DROP TABLE merge_appearances;
+DROP TABLE merges;
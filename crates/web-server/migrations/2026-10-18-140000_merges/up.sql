@@ -0,0 +1,35 @@
+-- Each person merged into another, along with what they were before, so the merge can be
+-- undone. People and appearances come and go, so nothing here refers to them by foreign key.
+CREATE TABLE merges (
+  id              INTEGER PRIMARY KEY NOT NULL,
+  dst             INTEGER NOT NULL,
+  src             INTEGER NOT NULL,
+  first_name      TEXT NOT NULL,
+  middle_names    TEXT,
+  surname         TEXT NOT NULL,
+  display_name    TEXT,
+  dob             DATE,
+  avatar          INTEGER,
+  merged_datetime DATETIME NOT NULL,
+  undone_datetime DATETIME
+);
+
+CREATE INDEX merges_by_dst ON merges(dst);
+
+-- The appearances that were moved over by a merge.
+CREATE TABLE merge_appearances (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  merge      INTEGER NOT NULL REFERENCES merges(id),
+  appearance INTEGER NOT NULL
+);
+
+-- What the merged person was known not to be, as either an appearance or another person. Those
+-- the person merged into was already known not to be are marked as shared, so that undoing the
+-- merge leaves them be.
+CREATE TABLE merge_exclusions (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  merge      INTEGER NOT NULL REFERENCES merges(id),
+  appearance INTEGER,
+  person     INTEGER,
+  shared     BOOLEAN NOT NULL
+);
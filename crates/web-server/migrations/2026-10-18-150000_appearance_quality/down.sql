@@ -0,0 +1,58 @@
+CREATE TABLE avatars_backup AS SELECT * FROM avatars;
+DROP TABLE avatars;
+CREATE TABLE appearance_exclusions_backup AS SELECT * FROM appearance_exclusions;
+DROP TABLE appearance_exclusions;
+-- Matches refer to other appearances, which the new table's references don't find until it's
+-- been renamed, so they're set again afterwards.
+CREATE TABLE appearance_matches_backup AS
+  SELECT id, match_appearance FROM appearances WHERE match_appearance IS NOT NULL;
+
+CREATE TABLE appearances_new (
+  id               INTEGER PRIMARY KEY NOT NULL,
+  person           INTEGER REFERENCES people(id),
+  photo            INTEGER NOT NULL REFERENCES photos(id),
+  reference        BOOLEAN NOT NULL,
+  top              INTEGER NOT NULL,
+  left             INTEGER NOT NULL,
+  bottom           INTEGER NOT NULL,
+  right            INTEGER NOT NULL,
+  face_encoding    BLOB NOT NULL,
+  match_distance   DOUBLE,
+  match_appearance INTEGER REFERENCES appearances(id),
+  confirmed        BOOLEAN NOT NULL DEFAULT 0,
+  encoder          TEXT
+);
+
+INSERT INTO appearances_new
+  SELECT id, person, photo, reference, top, left, bottom, right, face_encoding, match_distance,
+         NULL, confirmed, encoder
+  FROM appearances;
+DROP TABLE appearances;
+ALTER TABLE appearances_new RENAME TO appearances;
+
+UPDATE appearances
+  SET match_appearance = (
+    SELECT match_appearance FROM appearance_matches_backup
+    WHERE appearance_matches_backup.id = appearances.id
+  )
+  WHERE id IN (SELECT id FROM appearance_matches_backup);
+DROP TABLE appearance_matches_backup;
+
+CREATE TABLE avatars (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  person     INTEGER NOT NULL UNIQUE REFERENCES people(id),
+  appearance INTEGER NOT NULL REFERENCES appearances(id)
+);
+
+INSERT INTO avatars SELECT * FROM avatars_backup;
+DROP TABLE avatars_backup;
+
+CREATE TABLE appearance_exclusions (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  appearance INTEGER NOT NULL REFERENCES appearances(id),
+  person     INTEGER NOT NULL REFERENCES people(id),
+  UNIQUE(appearance, person)
+);
+
+INSERT INTO appearance_exclusions SELECT * FROM appearance_exclusions_backup;
+DROP TABLE appearance_exclusions_backup;
@@ -0,0 +1,4 @@
+-- How well each face is shown, as measured from the image it was found in, for picking avatars.
+-- Faces recorded before these were measured are left without them until they're reprocessed.
+ALTER TABLE appearances ADD COLUMN sharpness DOUBLE;
+ALTER TABLE appearances ADD COLUMN frontalness DOUBLE;
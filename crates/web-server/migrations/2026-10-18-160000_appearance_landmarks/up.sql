@@ -0,0 +1,3 @@
+-- The 68 facial landmarks located for each face, as little-endian (x, y) pairs of 32 bit integers.
+-- Faces whose landmarks weren't located, or were recorded before they were kept, have none.
+ALTER TABLE appearances ADD COLUMN landmarks BLOB;
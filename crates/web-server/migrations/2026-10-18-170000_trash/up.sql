@@ -0,0 +1,5 @@
+-- Photos moved to the trash, which are hidden until they're restored or purged for good.
+CREATE TABLE trashed_photos (
+  photo            INTEGER PRIMARY KEY NOT NULL REFERENCES photos(id),
+  trashed_datetime DATETIME NOT NULL
+);
@@ -0,0 +1,4 @@
+-- How far ahead of UTC, in seconds, the clock that gave each photo's original datetime was set.
+-- Photos whose cameras didn't record an offset have none, and are taken to be in the server's
+-- default time zone.
+ALTER TABLE photos ADD COLUMN original_offset INTEGER;
@@ -0,0 +1,45 @@
+-- The photos' dependents are emptied rather than dropped while the table is rebuilt, as their
+-- references to it carry over to the new one once it's renamed.
+CREATE TABLE avatars_backup AS SELECT * FROM avatars;
+DELETE FROM avatars;
+CREATE TABLE appearance_exclusions_backup AS SELECT * FROM appearance_exclusions;
+DELETE FROM appearance_exclusions;
+CREATE TABLE appearances_backup AS SELECT * FROM appearances;
+DELETE FROM appearances;
+CREATE TABLE jobs_backup AS SELECT * FROM jobs;
+DELETE FROM jobs;
+CREATE TABLE trashed_photos_backup AS SELECT * FROM trashed_photos;
+DELETE FROM trashed_photos;
+
+CREATE TABLE photos_new (
+  id                INTEGER PRIMARY KEY NOT NULL,
+  digest            BLOB NOT NULL,
+  file_name         TEXT NOT NULL,
+  image_width       INTEGER NOT NULL,
+  image_height      INTEGER NOT NULL,
+  thumb_width       INTEGER NOT NULL,
+  thumb_height      INTEGER NOT NULL,
+  original_datetime DATETIME,
+  upload_datetime   DATETIME NOT NULL,
+  original_offset   INTEGER
+);
+
+INSERT INTO photos_new
+  SELECT id, digest, file_name, image_width, image_height, thumb_width, thumb_height,
+         original_datetime, upload_datetime, original_offset
+  FROM photos;
+DROP TABLE photos;
+ALTER TABLE photos_new RENAME TO photos;
+
+CREATE UNIQUE INDEX photos_by_digest ON photos(digest);
+
+INSERT INTO appearances SELECT * FROM appearances_backup;
+DROP TABLE appearances_backup;
+INSERT INTO avatars SELECT * FROM avatars_backup;
+DROP TABLE avatars_backup;
+INSERT INTO appearance_exclusions SELECT * FROM appearance_exclusions_backup;
+DROP TABLE appearance_exclusions_backup;
+INSERT INTO jobs SELECT * FROM jobs_backup;
+DROP TABLE jobs_backup;
+INSERT INTO trashed_photos SELECT * FROM trashed_photos_backup;
+DROP TABLE trashed_photos_backup;
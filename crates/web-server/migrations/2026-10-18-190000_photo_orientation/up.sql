@@ -0,0 +1,3 @@
+-- The EXIF orientation that each photo is shown in, as a number from 1 to 8, where it has been
+-- turned or flipped since it was uploaded. It stands in for whatever the photo's file records.
+ALTER TABLE photos ADD COLUMN orientation INTEGER;
@@ -0,0 +1,3 @@
+-- Whether each face was drawn around by hand rather than found by detection, so that running
+-- detection over its photo again leaves it be.
+ALTER TABLE appearances ADD COLUMN manual BOOLEAN NOT NULL DEFAULT 0;
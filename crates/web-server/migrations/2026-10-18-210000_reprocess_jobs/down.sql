@@ -0,0 +1,24 @@
+DROP INDEX job_changes_by_job;
+DROP TABLE job_changes;
+
+DROP INDEX jobs_by_status;
+
+CREATE TABLE jobs_new (
+  id               INTEGER PRIMARY KEY NOT NULL,
+  photo            INTEGER NOT NULL REFERENCES photos(id),
+  status           TEXT NOT NULL,
+  attempts         INTEGER NOT NULL,
+  error            TEXT,
+  created_datetime DATETIME NOT NULL,
+  updated_datetime DATETIME NOT NULL
+);
+
+-- Reprocessing jobs can't be told apart from the rest once these columns are gone, so they go.
+INSERT INTO jobs_new
+  SELECT id, photo, status, attempts, error, created_datetime, updated_datetime
+  FROM jobs
+  WHERE NOT reprocess;
+DROP TABLE jobs;
+ALTER TABLE jobs_new RENAME TO jobs;
+
+CREATE INDEX jobs_by_status ON jobs(status);
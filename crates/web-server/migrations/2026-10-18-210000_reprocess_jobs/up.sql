@@ -0,0 +1,18 @@
+-- Jobs that run detection over a photo again rather than for the first time, along with the
+-- detection settings that they override the server's own with, if any.
+ALTER TABLE jobs ADD COLUMN reprocess BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE jobs ADD COLUMN min_face_pixels INTEGER;
+ALTER TABLE jobs ADD COLUMN min_face_fraction DOUBLE;
+ALTER TABLE jobs ADD COLUMN upsample INTEGER;
+ALTER TABLE jobs ADD COLUMN max_image_size INTEGER;
+
+-- The appearances that each reprocessing job added, removed or changed. Appearances come and go,
+-- so they aren't referred to by foreign key.
+CREATE TABLE job_changes (
+  id         INTEGER PRIMARY KEY NOT NULL,
+  job        INTEGER NOT NULL REFERENCES jobs(id),
+  appearance INTEGER NOT NULL,
+  change     TEXT NOT NULL
+);
+
+CREATE INDEX job_changes_by_job ON job_changes(job);
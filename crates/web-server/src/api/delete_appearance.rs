@@ -0,0 +1,27 @@
+use actix_web::{delete, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[delete("/appearances/{id:\\d+}")]
+pub async fn endpoint(
+    appearance_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    avatar_dir: web::Data<crate::api::AvatarDirPath>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    crate::db::model::Appearance::delete(&db, *appearance_id).await?;
+
+    recognition.known_faces_mut().remove(*appearance_id);
+
+    crate::files::remove_all(
+        Vec::new(),
+        vec![avatar_dir.join(appearance_id.to_string())],
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(()))
+}
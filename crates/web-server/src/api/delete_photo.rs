@@ -0,0 +1,32 @@
+use actix_web::{delete, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+/// Moves a photo to the trash, from which it can be restored until it's purged.
+#[delete("/photos/{id:\\d+}")]
+pub async fn endpoint(
+    photo_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+    trash_dir: web::Data<crate::api::TrashDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let trashed =
+        crate::db::model::Photo::trash(&db, *photo_id, recognition.encoder().to_owned()).await?;
+
+    crate::trash::move_in(&photo_dir, &thumb_dir, &trash_dir, &trashed.file_name).await;
+
+    {
+        let mut known_faces = recognition.known_faces_mut();
+        for (appearance_id, _, _) in trashed.faces.iter() {
+            known_faces.remove(*appearance_id);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(()))
+}
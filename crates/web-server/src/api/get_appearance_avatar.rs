@@ -2,29 +2,30 @@ use actix_web::{get, web};
 
 #[derive(Debug, super::Error, thiserror::Error)]
 pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
     #[error("Database query failed")]
     DatabaseQueryError(#[from] crate::db::QueryError),
     #[error("No matching appearance")]
     NotFound,
-    #[error("Failed to generate image")]
-    ImageError(#[source] crate::db::model::avatar::ImageError),
-    #[error("Failed to encode avatar")]
-    EncodeError(#[source] image::ImageError),
+    #[error("Failed to render avatar")]
+    RenderError(#[from] crate::avatar::RenderError),
 }
 
 #[get("/appearances/{id:\\d+}/image")]
 pub async fn endpoint(
+    req: actix_web::HttpRequest,
     appearance_id: web::Path<i32>,
     photo_dir: web::Data<crate::api::PhotoDirPath>,
+    avatar_dir: web::Data<crate::api::AvatarDirPath>,
     db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
 ) -> Result<actix_web::HttpResponse, Error> {
-    crate::db::model::Avatar::fetch_for_appearance(&db, *appearance_id)
+    let params: photos_web_core::AvatarQueryParams = serde_qs::from_str(req.query_string())?;
+
+    let avatar = crate::db::model::Avatar::fetch_for_appearance(&db, *appearance_id)
         .await?
-        .ok_or(Error::NotFound)
-        .and_then(|avatar| {
-            avatar
-                .image(128, &photo_dir.into_inner())
-                .map_err(Error::ImageError)
-        })
-        .and_then(|image| crate::image_ext::encode_image(&image).map_err(Error::EncodeError))
+        .ok_or(Error::NotFound)?;
+
+    Ok(crate::avatar::respond(avatar, &params, &photo_dir, &avatar_dir, &recognition).await?)
 }
@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse};
+
+const DEFAULT_COUNT: usize = 5;
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("No matching appearance")]
+    NotFound,
+}
+
+#[get("/appearances/{id:\\d+}/suggestions")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    appearance_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let params: photos_web_core::SuggestionQueryParams = serde_qs::from_str(req.query_string())?;
+    let count = params.count.unwrap_or(DEFAULT_COUNT);
+
+    let appearance = crate::db::model::Appearance::fetch(&db, *appearance_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let excluded_people = crate::db::model::exclusion::fetch_excluded_people(
+        &db,
+        *appearance_id,
+        appearance.person,
+    )
+    .await?;
+
+    let suggestions = recognition
+        .known_faces()
+        .rank(*appearance_id, &excluded_people, count);
+
+    Ok(HttpResponse::Ok().json(photos_web_core::Suggestions::from(
+        suggestions
+            .into_iter()
+            .map(|(person, distance)| photos_web_core::Suggestion { person, distance })
+            .collect::<Vec<_>>(),
+    )))
+}
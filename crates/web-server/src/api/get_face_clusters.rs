@@ -0,0 +1,42 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+}
+
+#[get("/face-clusters")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let params: photos_web_core::FaceClusterQueryParams = serde_qs::from_str(req.query_string())?;
+    let min_size = params.min_size.unwrap_or(1);
+    let tolerance = recognition.matcher().tolerance;
+
+    let faces = crate::db::model::Appearance::fetch_unassigned_encodings(
+        &db,
+        recognition.encoder().to_owned(),
+    )
+    .await?;
+
+    let clusters = web::block(move || {
+        Ok::<_, ()>(crate::recognition::clustering::cluster(&faces, tolerance))
+    })
+    .await
+    .map_err(|_| Error::OperationCancelled)?;
+
+    Ok(HttpResponse::Ok().json(photos_web_core::FaceClusters::from(
+        clusters
+            .into_iter()
+            .filter(|appearances| appearances.len() >= min_size)
+            .map(|appearances| photos_web_core::FaceCluster { appearances })
+            .collect::<Vec<_>>(),
+    )))
+}
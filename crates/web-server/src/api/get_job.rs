@@ -0,0 +1,42 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+#[get("/jobs/{id:\\d+}")]
+pub async fn endpoint(
+    job_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use crate::db::model::job::{AppearanceChange, JobStatus};
+
+    let job = match crate::db::model::Job::fetch(&db, *job_id).await? {
+        Some(job) => job,
+        None => return Ok(HttpResponse::Ok().json(None::<photos_web_core::Job>)),
+    };
+
+    let changes = if job.reprocess && job.status == JobStatus::Completed {
+        let mut changes = photos_web_core::ReprocessedPhoto::default();
+        for (appearance_id, change) in crate::db::model::Job::fetch_changes(&db, *job_id)
+            .await?
+            .into_iter()
+        {
+            match change {
+                AppearanceChange::Added => changes.added.push(appearance_id),
+                AppearanceChange::Removed => changes.removed.push(appearance_id),
+                AppearanceChange::Changed => changes.changed.push(appearance_id),
+            }
+        }
+        Some(changes)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(photos_web_core::Job {
+        changes,
+        ..job.into()
+    }))
+}
@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+#[get("/jobs")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let params: photos_web_core::JobQueryParams = serde_qs::from_str(req.query_string())?;
+    Ok(
+        crate::db::model::Job::fetch_all(&db, params.status.map(|status| status.into()))
+            .await
+            .map(|jobs| HttpResponse::Ok().json(photos_web_core::Jobs::from(jobs)))?,
+    )
+}
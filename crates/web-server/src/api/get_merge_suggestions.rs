@@ -0,0 +1,68 @@
+use actix_web::{get, web, HttpResponse};
+
+const DEFAULT_COUNT: usize = 20;
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+}
+
+/// Ranks pairs of people who look like they might be the same person, leaving out any pair known
+/// to be distinct. Pairs are only suggested if they're closer than the match tolerance, unless
+/// told otherwise.
+#[get("/people/merge-suggestions")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use crate::recognition::Linkage;
+
+    let params: photos_web_core::MergeSuggestionQueryParams =
+        serde_qs::from_str(req.query_string())?;
+    let linkage = match params.linkage {
+        Some(photos_web_core::Linkage::Min) => Linkage::Min,
+        Some(photos_web_core::Linkage::Centroid) | None => Linkage::Centroid,
+    };
+    let max_distance = params
+        .max_distance
+        .unwrap_or(recognition.matcher().tolerance);
+    let count = params.count.unwrap_or(DEFAULT_COUNT);
+
+    let distinct: std::collections::HashSet<(i32, i32)> =
+        crate::db::model::exclusion::fetch_all_for_people(&db)
+            .await?
+            .into_iter()
+            .collect();
+
+    let similar = web::block({
+        let recognition = recognition.get_ref().clone();
+        move || Ok::<_, ()>(recognition.known_faces().similar_people(linkage, max_distance))
+    })
+    .await
+    .map_err(|_| Error::OperationCancelled)?;
+
+    Ok(HttpResponse::Ok().json(photos_web_core::MergeSuggestions::from(
+        similar
+            .into_iter()
+            .filter(|similar| {
+                let pair = (
+                    std::cmp::min(similar.dst, similar.src),
+                    std::cmp::max(similar.dst, similar.src),
+                );
+                !distinct.contains(&pair)
+            })
+            .take(count)
+            .map(|similar| photos_web_core::MergeSuggestion {
+                dst: similar.dst,
+                src: similar.src,
+                distance: similar.distance,
+            })
+            .collect::<Vec<_>>(),
+    )))
+}
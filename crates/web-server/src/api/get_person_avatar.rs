@@ -8,10 +8,8 @@ pub enum Error {
     DatabaseQueryError(#[from] crate::db::QueryError),
     #[error("No matching person")]
     NotFound,
-    #[error("Failed to generate image")]
-    ImageError(#[source] crate::db::model::avatar::ImageError),
-    #[error("Failed to encode avatar")]
-    EncodeError(#[source] image::ImageError),
+    #[error("Failed to render avatar")]
+    RenderError(#[from] crate::avatar::RenderError),
 }
 
 #[get("/people/{id:\\d+}/avatar")]
@@ -19,17 +17,15 @@ pub async fn endpoint(
     req: actix_web::HttpRequest,
     person_id: web::Path<i32>,
     photo_dir: web::Data<crate::api::PhotoDirPath>,
+    avatar_dir: web::Data<crate::api::AvatarDirPath>,
     db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
 ) -> Result<actix_web::HttpResponse, Error> {
     let params: photos_web_core::AvatarQueryParams = serde_qs::from_str(req.query_string())?;
 
-    crate::db::model::Avatar::fetch_for_person(&db, *person_id)
+    let avatar = crate::db::model::Avatar::fetch_for_person(&db, *person_id)
         .await?
-        .ok_or(Error::NotFound)
-        .and_then(|avatar| {
-            avatar
-                .image(params.size.unwrap_or(128), &photo_dir.into_inner())
-                .map_err(Error::ImageError)
-        })
-        .and_then(|image| crate::image_ext::encode_image(&image).map_err(Error::EncodeError))
+        .ok_or(Error::NotFound)?;
+
+    Ok(crate::avatar::respond(avatar, &params, &photo_dir, &avatar_dir, &recognition).await?)
 }
@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+/// Lists the people merged into a person, most recent first, including any merges since undone.
+#[get("/people/{id:\\d+}/merges")]
+pub async fn endpoint(
+    person_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let merges = crate::db::model::Merge::fetch_for_person(&db, *person_id).await?;
+
+    Ok(HttpResponse::Ok().json(photos_web_core::Merges::from(
+        merges
+            .into_iter()
+            .map(|(merge_id, merge)| (merge_id, merge.into()))
+            .collect::<Vec<_>>(),
+    )))
+}
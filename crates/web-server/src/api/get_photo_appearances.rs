@@ -2,20 +2,33 @@ use actix_web::{get, web, HttpResponse};
 
 #[derive(Debug, super::Error, thiserror::Error)]
 pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
     #[error("Database query failed")]
     DatabaseQueryError(#[from] crate::db::QueryError),
 }
 
+/// Lists the appearances in a photo, along with the landmarks located on each face if asked for.
 #[get("/photos/{id:\\d+}/appearances")]
 pub async fn endpoint(
+    req: actix_web::HttpRequest,
     photo_id: web::Path<i32>,
     db: web::Data<crate::db::System>,
 ) -> Result<actix_web::HttpResponse, Error> {
-    Ok(
-        crate::db::model::Appearance::fetch_all_for_photo(&db, *photo_id)
-            .await
-            .map(|appearances| {
-                HttpResponse::Ok().json(photos_web_core::Appearances::from(appearances))
-            })?,
-    )
+    let params: photos_web_core::AppearanceQueryParams = serde_qs::from_str(req.query_string())?;
+
+    let appearances = crate::db::model::Appearance::fetch_all_for_photo(&db, *photo_id)
+        .await?
+        .into_iter()
+        .map(|(appearance_id, appearance)| {
+            let landmarks = appearance.landmarks.clone();
+            let mut appearance: photos_web_core::Appearance = appearance.into();
+            if params.landmarks {
+                appearance.landmarks = landmarks.map(|landmarks| landmarks.into_inner());
+            }
+            (appearance_id, appearance)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(photos_web_core::Appearances::from(appearances)))
 }
@@ -4,6 +4,8 @@ use actix_web::{get, web, HttpResponse};
 pub enum Error {
     #[error("Failed to decode query string")]
     QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Unknown time zone")]
+    UnknownTimeZone(#[from] super::UnknownTimeZone),
     #[error("Database query failed")]
     DatabaseQueryError(#[from] crate::db::QueryError),
 }
@@ -12,11 +14,16 @@ pub enum Error {
 pub async fn endpoint(
     req: actix_web::HttpRequest,
     db: web::Data<crate::db::System>,
+    default_time_zone: web::Data<crate::api::DefaultTimeZone>,
 ) -> Result<actix_web::HttpResponse, Error> {
     let params: photos_web_core::PhotoQueryParams = serde_qs::from_str(req.query_string())?;
-    Ok(
-        crate::db::model::Photo::count_per_day(&db, params.people.unwrap_or_default())
-            .await
-            .map(|day_counts| HttpResponse::Ok().json(day_counts))?,
+    let (default_time_zone, time_zone) = super::time_zones(&default_time_zone, params.time_zone)?;
+    Ok(crate::db::model::Photo::count_per_day(
+        &db,
+        params.people.unwrap_or_default(),
+        default_time_zone,
+        time_zone,
     )
+    .await
+    .map(|day_counts| HttpResponse::Ok().json(day_counts))?)
 }
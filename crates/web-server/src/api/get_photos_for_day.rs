@@ -4,6 +4,8 @@ use actix_web::{get, web, HttpResponse};
 pub enum Error {
     #[error("Failed to decode query string")]
     QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Unknown time zone")]
+    UnknownTimeZone(#[from] super::UnknownTimeZone),
     #[error("Database query failed")]
     DatabaseQueryError(#[from] crate::db::QueryError),
 }
@@ -13,11 +15,17 @@ pub async fn endpoint(
     req: actix_web::HttpRequest,
     date: web::Path<chrono::NaiveDate>,
     db: web::Data<crate::db::System>,
+    default_time_zone: web::Data<crate::api::DefaultTimeZone>,
 ) -> Result<HttpResponse, Error> {
     let params: photos_web_core::PhotoQueryParams = serde_qs::from_str(req.query_string())?;
-    Ok(
-        crate::db::model::Photo::fetch_all_for_day(&db, *date, params.people.unwrap_or_default())
-            .await
-            .map(|photos| HttpResponse::Ok().json(photos_web_core::Photos::from(photos)))?,
+    let (default_time_zone, time_zone) = super::time_zones(&default_time_zone, params.time_zone)?;
+    Ok(crate::db::model::Photo::fetch_all_for_day(
+        &db,
+        *date,
+        params.people.unwrap_or_default(),
+        default_time_zone,
+        time_zone,
     )
+    .await
+    .map(|photos| HttpResponse::Ok().json(photos_web_core::Photos::from(photos)))?)
 }
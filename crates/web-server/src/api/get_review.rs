@@ -0,0 +1,24 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+#[get("/review")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let params: photos_web_core::ReviewQueryParams = serde_qs::from_str(req.query_string())?;
+    Ok(
+        crate::db::model::Appearance::fetch_unconfirmed(&db, params.limit)
+            .await
+            .map(|appearances| {
+                HttpResponse::Ok().json(photos_web_core::Appearances::from(appearances))
+            })?,
+    )
+}
@@ -0,0 +1,27 @@
+use actix_web::{get, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+#[get("/trash")]
+pub async fn endpoint(db: web::Data<crate::db::System>) -> Result<actix_web::HttpResponse, Error> {
+    let photos = crate::db::model::Photo::fetch_trashed(&db).await?;
+
+    Ok(HttpResponse::Ok().json(photos_web_core::TrashedPhotos::from(
+        photos
+            .into_iter()
+            .map(|(photo_id, photo, trashed_datetime)| {
+                (
+                    photo_id,
+                    photos_web_core::TrashedPhoto {
+                        photo: photo.into(),
+                        trashed_datetime,
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    )))
+}
@@ -2,16 +2,19 @@ use actix_web::{post, web, HttpResponse};
 
 #[derive(Debug, super::Error, thiserror::Error)]
 pub enum Error {
-    #[error("Database update failed")]
-    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+    #[error("Failed to merge people")]
+    MergeFailed(#[from] crate::db::model::person::MergeError),
 }
 
 #[post("/people/{dst_id:\\d+}/merge/{src_id:\\d+}")]
 pub async fn endpoint(
     web::Path((dst_id, src_id)): web::Path<(i32, i32)>,
     db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
 ) -> Result<actix_web::HttpResponse, Error> {
-    Ok(crate::db::model::Person::merge(&db, dst_id, src_id)
-        .await
-        .map(|_| HttpResponse::Ok().json(()))?)
+    let merge_id = crate::db::model::Person::merge(&db, dst_id, src_id).await?;
+
+    recognition.known_faces_mut().merge(dst_id, src_id);
+
+    Ok(HttpResponse::Ok().json(merge_id))
 }
@@ -1,14 +1,41 @@
+mod delete_appearance;
+mod delete_photo;
 mod get_appearance_avatar;
+mod get_appearance_suggestions;
+mod get_face_clusters;
+mod get_job;
+mod get_jobs;
+mod get_merge_suggestions;
 mod get_people;
 mod get_person;
 mod get_person_avatar;
+mod get_person_merges;
 mod get_photo;
 mod get_photo_appearances;
 mod get_photo_count_per_day;
 mod get_photos_for_day;
+mod get_review;
+mod get_trash;
 mod merge_person;
+mod post_appearance_confirm;
+mod post_appearance_reject;
+mod post_distinct_people;
+mod post_face_search;
 mod post_photo;
+mod post_photo_appearance;
+mod post_photo_rotate;
+mod post_rematch;
+mod post_reprocess;
+mod post_time_shift;
+mod promote_face_cluster;
+mod purge_trashed_photo;
+mod put_appearance;
 mod put_person;
+mod put_person_avatar;
+mod put_photo;
+mod restore_trashed_photo;
+mod split_person;
+mod undo_merge;
 
 pub use photos_web_server_derive::ApiError as Error;
 
@@ -44,21 +71,15 @@ impl std::ops::Deref for ThumbDirPath {
     }
 }
 
-pub struct FaceLandmarkPredictorModelFilePath(std::path::PathBuf);
+pub struct AvatarDirPath(std::path::PathBuf);
 
-impl FaceLandmarkPredictorModelFilePath {
-    pub fn as_path(&self) -> &std::path::Path {
-        &self.0
-    }
-}
-
-impl From<std::path::PathBuf> for FaceLandmarkPredictorModelFilePath {
+impl From<std::path::PathBuf> for AvatarDirPath {
     fn from(path: std::path::PathBuf) -> Self {
         Self(path)
     }
 }
 
-impl std::ops::Deref for FaceLandmarkPredictorModelFilePath {
+impl std::ops::Deref for AvatarDirPath {
     type Target = std::path::Path;
 
     fn deref(&self) -> &Self::Target {
@@ -66,38 +87,98 @@ impl std::ops::Deref for FaceLandmarkPredictorModelFilePath {
     }
 }
 
-pub struct FaceEncoderModelFilePath(std::path::PathBuf);
+pub struct TrashDirPath(std::path::PathBuf);
+
+impl From<std::path::PathBuf> for TrashDirPath {
+    fn from(path: std::path::PathBuf) -> Self {
+        Self(path)
+    }
+}
+
+impl std::ops::Deref for TrashDirPath {
+    type Target = std::path::Path;
 
-impl FaceEncoderModelFilePath {
-    pub fn as_path(&self) -> &std::path::Path {
+    fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl From<std::path::PathBuf> for FaceEncoderModelFilePath {
-    fn from(path: std::path::PathBuf) -> Self {
-        Self(path)
+/// The time zone that photos are taken to be in when they weren't taken with an offset from UTC,
+/// and that they're grouped into days in for viewers who don't give their own.
+pub struct DefaultTimeZone(chrono_tz::Tz);
+
+impl From<chrono_tz::Tz> for DefaultTimeZone {
+    fn from(time_zone: chrono_tz::Tz) -> Self {
+        Self(time_zone)
     }
 }
 
-impl std::ops::Deref for FaceEncoderModelFilePath {
-    type Target = std::path::Path;
+impl std::ops::Deref for DefaultTimeZone {
+    type Target = chrono_tz::Tz;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown time zone '{0}'")]
+pub struct UnknownTimeZone(String);
+
+/// The time zones that a viewer's photos are grouped into days by: the default that photos
+/// taken without an offset from UTC are taken to be in, and the one named by the viewer, if they
+/// named one, for the days themselves.
+pub fn time_zones(
+    default_time_zone: &DefaultTimeZone,
+    time_zone: Option<String>,
+) -> Result<(chrono_tz::Tz, chrono_tz::Tz), UnknownTimeZone> {
+    let default_time_zone = **default_time_zone;
+    let time_zone = match time_zone {
+        Some(time_zone) => time_zone
+            .parse::<chrono_tz::Tz>()
+            .map_err(|_| UnknownTimeZone(time_zone))?,
+        None => default_time_zone,
+    };
+    Ok((default_time_zone, time_zone))
+}
+
 pub fn configure(cfg: &mut actix_web::web::ServiceConfig) {
-    cfg.service(get_appearance_avatar::endpoint)
+    cfg.service(delete_appearance::endpoint)
+        .service(delete_photo::endpoint)
+        .service(get_appearance_avatar::endpoint)
+        .service(get_appearance_suggestions::endpoint)
+        .service(get_face_clusters::endpoint)
+        .service(get_job::endpoint)
+        .service(get_jobs::endpoint)
+        .service(get_merge_suggestions::endpoint)
         .service(get_person_avatar::endpoint)
+        .service(get_person_merges::endpoint)
         .service(get_people::endpoint)
         .service(get_person::endpoint)
         .service(get_photo::endpoint)
         .service(get_photo_appearances::endpoint)
         .service(get_photo_count_per_day::endpoint)
         .service(get_photos_for_day::endpoint)
+        .service(get_review::endpoint)
+        .service(get_trash::endpoint)
         .service(merge_person::endpoint)
+        .service(post_appearance_confirm::endpoint)
+        .service(post_appearance_reject::endpoint)
+        .service(post_distinct_people::endpoint)
+        .service(post_face_search::endpoint)
         .service(post_photo::endpoint)
-        .service(put_person::endpoint);
+        .service(post_photo_appearance::endpoint)
+        .service(post_photo_rotate::endpoint)
+        .service(post_rematch::endpoint)
+        .service(post_reprocess::endpoint)
+        .service(post_time_shift::endpoint)
+        .service(promote_face_cluster::endpoint)
+        .service(purge_trashed_photo::endpoint)
+        .service(put_appearance::endpoint)
+        .service(put_person::endpoint)
+        .service(put_person_avatar::endpoint)
+        .service(put_photo::endpoint)
+        .service(restore_trashed_photo::endpoint)
+        .service(split_person::endpoint)
+        .service(undo_merge::endpoint);
 }
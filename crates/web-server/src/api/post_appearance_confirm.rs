@@ -0,0 +1,20 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[post("/appearances/{id:\\d+}/confirm")]
+pub async fn endpoint(
+    appearance_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    crate::db::model::Appearance::confirm(&db, *appearance_id).await?;
+
+    recognition.known_faces_mut().confirm(*appearance_id);
+
+    Ok(HttpResponse::Ok().json(()))
+}
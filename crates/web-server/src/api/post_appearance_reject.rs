@@ -0,0 +1,24 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+/// Detaches an appearance from the person it was matched to, remembering that it isn't them.
+#[post("/appearances/{id:\\d+}/reject")]
+pub async fn endpoint(
+    appearance_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let previous_person =
+        crate::db::model::Appearance::update(&db, *appearance_id, None, false, false).await?;
+
+    recognition
+        .known_faces_mut()
+        .reassign(*appearance_id, previous_person, None, false);
+
+    Ok(HttpResponse::Ok().json(()))
+}
@@ -0,0 +1,23 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("A person can't be distinct from themselves")]
+    SamePerson,
+}
+
+#[post("/people/{a_id:\\d+}/distinct/{b_id:\\d+}")]
+pub async fn endpoint(
+    web::Path((a_id, b_id)): web::Path<(i32, i32)>,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    if a_id == b_id {
+        return Err(Error::SamePerson);
+    }
+
+    crate::db::model::exclusion::insert_for_people(&db, a_id, b_id).await?;
+
+    Ok(HttpResponse::Ok().json(()))
+}
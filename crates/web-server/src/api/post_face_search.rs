@@ -0,0 +1,62 @@
+use actix_web::{post, web, HttpResponse};
+
+const DEFAULT_COUNT: usize = 5;
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to decode query string")]
+    QueryStringDecodeError(#[from] serde_qs::Error),
+    #[error("Failed to parse HTTP request body")]
+    PayloadParsingFailed(#[from] actix_web::client::PayloadError),
+    #[error("Failed to decode image")]
+    ImageLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    #[error("Failed to detect faces")]
+    FaceDetectionFailed(#[source] crate::recognition::ServiceError),
+}
+
+/// Detects the faces in an image and ranks the people each might be, without storing anything.
+#[post("/faces/search")]
+pub async fn endpoint(
+    req: actix_web::HttpRequest,
+    mut body: web::Payload,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use futures::StreamExt;
+
+    let params: photos_web_core::FaceSearchQueryParams = serde_qs::from_str(req.query_string())?;
+    let count = params.count.unwrap_or(DEFAULT_COUNT);
+
+    let mut bytes = web::BytesMut::new();
+    while let Some(chunk) = body.next().await {
+        bytes.extend_from_slice(&chunk?);
+    }
+
+    let image = crate::image_ext::ImageExt::new(std::io::Cursor::new(&bytes))
+        .map_err(Error::ImageLoadFailed)?
+        .reorient()
+        .into_rgb8();
+
+    let faces = recognition
+        .detect_faces(image)
+        .await
+        .map_err(Error::FaceDetectionFailed)?;
+
+    let known_faces = recognition.known_faces();
+
+    Ok(HttpResponse::Ok().json(photos_web_core::FoundFaces::from(
+        faces
+            .into_iter()
+            .map(|face| photos_web_core::FoundFace {
+                top: face.face_box.top,
+                left: face.face_box.left,
+                bottom: face.face_box.bottom,
+                right: face.face_box.right,
+                candidates: known_faces
+                    .search(&face.face_encoding, count)
+                    .into_iter()
+                    .map(|(person, distance)| photos_web_core::Suggestion { person, distance })
+                    .collect(),
+            })
+            .collect::<Vec<_>>(),
+    )))
+}
@@ -33,6 +33,9 @@ pub enum Error {
     FetchExistingPhotoFailed(#[source] crate::db::QueryError),
     #[error("Photo already posted")]
     PhotoAlreadyPosted(i32),
+    #[error("Photo {0} is in the trash, and can be restored from there")]
+    #[status_code(CONFLICT)]
+    PhotoInTrash(i32),
     #[error("Failed to create the photo sub-directory")]
     CreatePhotoDirError(#[source] std::io::Error),
     #[error("Failed to create the thumb sub-directory")]
@@ -41,36 +44,17 @@ pub enum Error {
     SavePhotoFailed(#[source] SaveImageError),
     #[error("Failed to store the photo's thumbnail")]
     SaveThumbFailed(#[source] SaveImageError),
-    #[error("Failed to fetch known faces")]
-    FetchKnownFacesFailed(#[source] crate::db::QueryError),
-    #[error("Failed to setup face landmark predictor: {0}")]
-    FaceLandmarkPredictorInitFailed(String),
-    #[error("Failed to setup face encoder: {0}")]
-    FaceEncoderInitFailed(String),
     #[error("Failed to record photo in database")]
     RecordPhotoFailed(#[source] crate::db::QueryError),
-    #[error("Failed to record person in database")]
-    RecordPersonFailed(#[source] crate::db::QueryError),
-    #[error("Failed to record appearance in database")]
-    RecordAppearanceFailed(#[source] crate::db::QueryError),
-    #[error("Failed to record avatar in database")]
-    RecordAvatarFailed(#[source] crate::db::QueryError),
 }
 
-const THUMB_SIZE: u32 = 256;
-
 #[post("/photos")]
 pub async fn endpoint(
     mut body: web::Payload,
     db: web::Data<crate::db::System>,
     photo_dir: web::Data<crate::api::PhotoDirPath>,
     thumb_dir: web::Data<crate::api::ThumbDirPath>,
-    face_landmark_predictor_model_file_path: web::Data<
-        crate::api::FaceLandmarkPredictorModelFilePath,
-    >,
-    face_encoder_model_file_path: web::Data<crate::api::FaceEncoderModelFilePath>,
 ) -> Result<actix_web::HttpResponse, Error> {
-    use dlib_face_recognition::{FaceDetectorTrait, FaceEncoderTrait, LandmarkPredictorTrait};
     use futures::StreamExt;
     use image::GenericImageView;
 
@@ -88,20 +72,22 @@ pub async fn endpoint(
     {
         let image_digest = image_digest.clone();
         db.run_query(move |db_connection| {
-            use crate::db::schema::photos;
-            use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+            use crate::db::schema::{photos, trashed_photos};
+            use diesel::{ExpressionMethods, NullableExpressionMethods, QueryDsl, RunQueryDsl};
 
             photos::table
-                .select(photos::id)
+                .left_join(trashed_photos::table)
+                .select((photos::id, trashed_photos::photo.nullable()))
                 .filter(photos::digest.eq(image_digest))
                 .limit(1)
-                .load::<i32>(&db_connection)
+                .load::<(i32, Option<i32>)>(&db_connection)
         })
         .await
         .map_err(|err| Error::FetchExistingPhotoFailed(err))
         .and_then(|ids| match ids.as_slice() {
             [] => Ok(()),
-            [id] => Err(Error::PhotoAlreadyPosted(*id)),
+            [(id, None)] => Err(Error::PhotoAlreadyPosted(*id)),
+            [(id, Some(_))] => Err(Error::PhotoInTrash(*id)),
             _ => unreachable!(),
         })?;
     }
@@ -114,6 +100,11 @@ pub async fn endpoint(
 
     log::debug!("  ORIGINAL DATETIME {:?}", original_datetime);
 
+    // An offset is no use without the time it's an offset of.
+    let original_offset = original_datetime.and(image.original_offset());
+
+    log::debug!("  ORIGINAL OFFSET {:?}", original_offset);
+
     let image_format = image.format();
 
     log::debug!("  FORMAT {:?}", image_format);
@@ -145,7 +136,7 @@ pub async fn endpoint(
     log::debug!("  THUMB PATH {:?}", thumb_file_path);
 
     let image = image.reorient();
-    let thumb = crate::image_ext::thumbnail(&image, THUMB_SIZE);
+    let thumb = crate::image_ext::thumbnail(&image, crate::image_ext::THUMB_SIZE);
 
     let (image_width, image_height) = image.dimensions();
     let (thumb_width, thumb_height) = thumb.dimensions();
@@ -169,7 +160,7 @@ pub async fn endpoint(
         .await
         .map_err(|err| Error::SaveThumbFailed(err))?;
 
-    let photo_id = crate::db::model::Photo::insert(
+    let (photo_id, job_id) = crate::db::model::Photo::insert(
         &db,
         image_digest,
         month_sub_dir
@@ -181,112 +172,103 @@ pub async fn endpoint(
         thumb_width,
         thumb_height,
         original_datetime,
+        original_offset,
         upload_datetime,
     )
     .await
     .map_err(|err| Error::RecordPhotoFailed(err))?;
 
     log::debug!("  PHOTO ID {}", photo_id);
+    log::debug!("  JOB ID {}", job_id);
 
-    let image_matrix = dlib_face_recognition::ImageMatrix::from_image(&image.into_rgb8());
-
-    let known_faces = db
-        .run_query(move |db_connection| {
-            use crate::db::schema::appearances;
-            use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
-
-            appearances::table
-                .select((appearances::person, appearances::face_encoding))
-                .filter(appearances::reference.eq(true))
-                .load::<(i32, crate::db::model::FaceEncoding)>(&db_connection)
-        })
-        .await
-        .map_err(|err| Error::FetchKnownFacesFailed(err))?;
-
-    log::debug!("  KNOWN FACE COUNT {}", known_faces.len());
-
-    let face_locations = dlib_face_recognition::FaceDetector::new().face_locations(&image_matrix);
-
-    log::debug!("  FOUND FACE COUNT {}", face_locations.len());
-
-    let mut face_landmark_predictor = dlib_face_recognition::LandmarkPredictor::new(
-        face_landmark_predictor_model_file_path.as_path(),
-    )
-    .map_err(|err| Error::FaceLandmarkPredictorInitFailed(err))?;
-
-    let mut face_encoder =
-        dlib_face_recognition::FaceEncoderNetwork::new(face_encoder_model_file_path.as_path())
-            .map_err(|err| Error::FaceEncoderInitFailed(err))?;
+    Ok(HttpResponse::Accepted().json(photos_web_core::AcceptedPhoto {
+        photo: photo_id,
+        job: job_id,
+    }))
+}
 
-    for face_location in face_locations.into_iter() {
-        const TOLERANCE: f64 = 0.6;
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, web, App};
 
-        log::debug!(
-            "  FOUND FACE @ {} {} {} {}",
-            face_location.top,
-            face_location.left,
-            face_location.bottom,
-            face_location.right
-        );
+    #[actix_rt::test]
+    async fn refuses_a_photo_that_is_in_the_trash() {
+        let dir_path =
+            std::env::temp_dir().join(format!("photosd-post-photo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
 
-        let landmarks = face_landmark_predictor.face_landmarks(&image_matrix, face_location);
-        if landmarks.is_empty() {
-            continue;
-        }
+        let image = image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let mut contents = Vec::new();
+        image::DynamicImage::ImageRgb8(image)
+            .write_to(&mut contents, image::ImageFormat::Png)
+            .unwrap();
 
-        let face_encoding = crate::db::model::FaceEncoding::from(
-            face_encoder
-                .get_face_encodings(&image_matrix, &[landmarks], 0)
-                .get(0)
-                .unwrap()
-                .clone(),
-        );
+        let fixture_file_path = dir_path.join("fixture.json");
+        std::fs::write(&fixture_file_path, "{}").unwrap();
 
-        let (person_id, new_person) =
-            match known_faces
-                .iter()
-                .fold(None, |best, (known_person_id, known_face_encoding)| {
-                    let distance = known_face_encoding.distance(&face_encoding);
-                    if distance
-                        < best
-                            .map(|(_, best_distance)| best_distance)
-                            .unwrap_or(TOLERANCE)
-                    {
-                        Some((*known_person_id, distance))
-                    } else {
-                        best
-                    }
-                }) {
-                Some((person_id, _)) => {
-                    log::debug!("  FOUND PERSON {}", person_id);
-                    (person_id, false)
-                }
-                None => crate::db::model::Person::insert(&db)
-                    .await
-                    .map(|person_id| (person_id, true))
-                    .map_err(Error::RecordPersonFailed)?,
-            };
-
-        let appearance_id = crate::db::model::Appearance::insert(
+        let db = crate::db::System::new(&dir_path.join("photos.db")).unwrap();
+        let recognition = crate::recognition::Service::new(
             &db,
-            person_id,
-            photo_id,
-            new_person,
-            face_location.top as i32,
-            face_location.left as i32,
-            face_location.bottom as i32,
-            face_location.right as i32,
-            face_encoding,
+            crate::recognition::Backend::Fake { fixture_file_path },
+            1,
+            crate::recognition::Matcher {
+                tolerance: 0.6,
+                strategy: crate::recognition::Strategy::Nearest,
+            },
+            crate::recognition::Detector::default(),
         )
         .await
-        .map_err(Error::RecordAppearanceFailed)?;
+        .unwrap();
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .data(recognition)
+                .data(crate::api::PhotoDirPath::from(dir_path.join("photos")))
+                .data(crate::api::ThumbDirPath::from(dir_path.join("thumbs")))
+                .data(crate::api::TrashDirPath::from(dir_path.join("trash")))
+                .service(web::scope("/api").configure(crate::api::configure)),
+        )
+        .await;
+
+        let accepted: photos_web_core::AcceptedPhoto = test::read_response_json(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/api/photos")
+                .set_payload(contents.clone())
+                .to_request(),
+        )
+        .await;
 
-        if new_person {
-            crate::db::model::Avatar::insert(&db, person_id, appearance_id)
-                .await
-                .map_err(Error::RecordAvatarFailed)?;
-        }
-    }
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::delete()
+                .uri(&format!("/api/photos/{}", accepted.photo))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/api/photos")
+                .set_payload(contents)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let error: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(
+            error["description"],
+            format!(
+                "Photo {} is in the trash, and can be restored from there",
+                accepted.photo
+            )
+        );
 
-    Ok(HttpResponse::Ok().finish())
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
 }
@@ -0,0 +1,263 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("No matching photo")]
+    #[status_code(NOT_FOUND)]
+    NotFound,
+    #[error("Failed to load image")]
+    ImageLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+    #[error("Region lies outside of the photo")]
+    #[status_code(BAD_REQUEST)]
+    InvalidRegion,
+    #[error("Failed to encode face")]
+    FaceEncodingFailed(#[source] crate::recognition::ServiceError),
+    #[error("No face found in region")]
+    #[status_code(BAD_REQUEST)]
+    NoFaceFound,
+}
+
+#[post("/photos/{id:\\d+}/appearances")]
+pub async fn endpoint(
+    photo_id: web::Path<i32>,
+    appearance: web::Json<photos_web_core::NewAppearance>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use actix_web::error::BlockingError;
+
+    let photo_id = *photo_id;
+    let appearance = appearance.into_inner();
+
+    let photo = crate::db::model::Photo::fetch(&db, photo_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let image = web::block({
+        let photo_file_path = photo_dir.join(&photo.file_name);
+        let orientation = photo
+            .orientation
+            .and_then(|orientation| crate::image_ext::Orientation::from_exif(orientation.into()));
+        move || {
+            crate::image_ext::load_rgb(&photo_file_path, orientation)
+                .map_err(Error::ImageLoadFailed)
+        }
+    })
+    .await
+    .map_err(|err| match err {
+        BlockingError::Error(err) => err,
+        BlockingError::Canceled => Error::OperationCancelled,
+    })?;
+
+    if appearance.top < 0
+        || appearance.left < 0
+        || appearance.bottom > image.height() as i32
+        || appearance.right > image.width() as i32
+        || appearance.top >= appearance.bottom
+        || appearance.left >= appearance.right
+    {
+        return Err(Error::InvalidRegion);
+    }
+
+    let face_box = crate::recognition::FaceBox {
+        top: appearance.top,
+        left: appearance.left,
+        bottom: appearance.bottom,
+        right: appearance.right,
+    };
+
+    let face = recognition
+        .encode_face(image, face_box)
+        .await
+        .map_err(Error::FaceEncodingFailed)?
+        .ok_or(Error::NoFaceFound)?;
+
+    let (person_id, matched, confirmed) = match appearance.person {
+        Some(person_id) => (Some(person_id), None, true),
+        None => match recognition.identify(&face.face_encoding) {
+            Some(matched) => (
+                Some(matched.person),
+                Some((matched.appearance, matched.distance)),
+                false,
+            ),
+            None => (None, None, false),
+        },
+    };
+
+    let appearance_id = crate::db::model::Appearance::insert(
+        &db,
+        person_id,
+        photo_id,
+        false,
+        face_box.top,
+        face_box.left,
+        face_box.bottom,
+        face_box.right,
+        face.face_encoding.clone(),
+        recognition.encoder().to_owned(),
+        face.landmarks,
+        face.quality,
+        matched,
+        confirmed,
+        true,
+    )
+    .await?;
+
+    recognition.known_faces_mut().insert(
+        appearance_id,
+        crate::recognition::Face {
+            person: person_id,
+            reference: false,
+            confirmed,
+        },
+        face.face_encoding,
+    );
+
+    Ok(HttpResponse::Ok().json(photos_web_core::Appearances::from(vec![(
+        appearance_id,
+        photos_web_core::Appearance {
+            person: person_id,
+            photo: photo_id,
+            reference: false,
+            top: face_box.top,
+            left: face_box.left,
+            bottom: face_box.bottom,
+            right: face_box.right,
+            match_distance: matched.map(|(_, distance)| distance),
+            match_appearance: matched.map(|(appearance_id, _)| appearance_id),
+            confirmed,
+            landmarks: None,
+        },
+    )])))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, web, App};
+
+    #[actix_rt::test]
+    async fn records_a_face_drawn_around_by_hand() {
+        let dir_path = std::env::temp_dir().join(format!(
+            "photosd-post-photo-appearance-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+
+        let image = image::RgbImage::from_fn(320, 240, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let mut contents = Vec::new();
+        image::DynamicImage::ImageRgb8(image.clone())
+            .write_to(&mut contents, image::ImageFormat::Png)
+            .unwrap();
+
+        let fixture_file_path = dir_path.join("fixture.json");
+        std::fs::write(
+            &fixture_file_path,
+            serde_json::json!({
+                crate::db::model::Digest::compute(image.as_raw()).to_string(): [
+                    { "top": 10, "left": 10, "bottom": 90, "right": 90, "identity": "alice" },
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let db = crate::db::System::new(&dir_path.join("photos.db")).unwrap();
+        let recognition = crate::recognition::Service::new(
+            &db,
+            crate::recognition::Backend::Fake { fixture_file_path },
+            1,
+            crate::recognition::Matcher {
+                tolerance: 0.6,
+                strategy: crate::recognition::Strategy::Nearest,
+            },
+            crate::recognition::Detector::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .data(recognition)
+                .data(crate::api::PhotoDirPath::from(dir_path.join("photos")))
+                .data(crate::api::ThumbDirPath::from(dir_path.join("thumbs")))
+                .service(web::scope("/api").configure(crate::api::configure)),
+        )
+        .await;
+
+        let accepted: photos_web_core::AcceptedPhoto = test::read_response_json(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/api/photos")
+                .set_payload(contents)
+                .to_request(),
+        )
+        .await;
+
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::post()
+                .uri(&format!("/api/photos/{}/appearances", accepted.photo))
+                .set_json(&photos_web_core::NewAppearance {
+                    person: None,
+                    top: 12,
+                    left: 8,
+                    bottom: 88,
+                    right: 92,
+                })
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let appearances: serde_json::Value = test::read_body_json(response).await;
+        let appearances = appearances.as_object().unwrap();
+        assert_eq!(appearances.len(), 1);
+
+        let appearance = appearances.values().next().unwrap();
+        assert_eq!(appearance["photo"], accepted.photo);
+        assert_eq!(appearance["top"], 12);
+        assert_eq!(appearance["right"], 92);
+        assert!(appearance["person"].is_null());
+
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::post()
+                .uri(&format!("/api/photos/{}/appearances", accepted.photo))
+                .set_json(&photos_web_core::NewAppearance {
+                    person: None,
+                    top: 200,
+                    left: 300,
+                    bottom: 260,
+                    right: 340,
+                })
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = test::call_service(
+            &mut app,
+            test::TestRequest::post()
+                .uri(&format!("/api/photos/{}/appearances", accepted.photo + 1))
+                .set_json(&photos_web_core::NewAppearance {
+                    person: None,
+                    top: 12,
+                    left: 8,
+                    bottom: 88,
+                    right: 92,
+                })
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+}
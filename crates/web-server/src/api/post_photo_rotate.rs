@@ -0,0 +1,137 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+    #[error("No matching photo")]
+    NotFound,
+    #[error("Failed to read the photo")]
+    PhotoReadError(#[source] std::io::Error),
+    #[error("Failed to decode the photo")]
+    PhotoLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    #[error("Failed to store the photo's thumbnail")]
+    SaveThumbFailed(#[source] image::ImageError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+}
+
+/// Turns a photo clockwise or flips it, as for one whose orientation was recorded wrongly, making
+/// its thumbnail again and moving its faces along with it. JPEGs are turned without being decoded
+/// and encoded again, by rewriting the orientation that they record, so that they're the right way
+/// round wherever they're shown. Other photos' files are left as they were uploaded.
+#[post("/photos/{id:\\d+}/rotate")]
+pub async fn endpoint(
+    photo_id: web::Path<i32>,
+    rotation: web::Json<photos_web_core::PhotoRotation>,
+    db: web::Data<crate::db::System>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+    avatar_dir: web::Data<crate::api::AvatarDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use crate::image_ext::Orientation;
+    use actix_web::error::BlockingError;
+    use image::GenericImageView;
+
+    let photo_id = *photo_id;
+
+    let rotation = match rotation.into_inner().rotation {
+        photos_web_core::Rotation::Clockwise90 => Orientation::Rotate90Cw,
+        photos_web_core::Rotation::Clockwise180 => Orientation::Rotate180,
+        photos_web_core::Rotation::Clockwise270 => Orientation::Rotate270Cw,
+        photos_web_core::Rotation::Flip => Orientation::FlipHorz,
+    };
+
+    let photo = crate::db::model::Photo::fetch(&db, photo_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let photo_file_path = photo_dir.join(&photo.file_name);
+    let thumb_file_path = thumb_dir.join(&photo.file_name);
+
+    let (orientation, thumb, oriented_jpeg) = web::block({
+        let photo_file_path = photo_file_path.clone();
+        let orientation = photo
+            .orientation
+            .and_then(|orientation| Orientation::from_exif(orientation.into()));
+        move || {
+            let contents = std::fs::read(&photo_file_path).map_err(Error::PhotoReadError)?;
+            let image = crate::image_ext::ImageExt::new(std::io::Cursor::new(&contents))
+                .map_err(Error::PhotoLoadFailed)?;
+
+            let orientation = orientation
+                .unwrap_or_else(|| image.orientation())
+                .then(rotation);
+
+            let oriented_jpeg = match image.format() {
+                image::ImageFormat::Jpeg => crate::image_ext::orient_jpeg(&contents, orientation),
+                _ => None,
+            };
+
+            let thumb = crate::image_ext::thumbnail(
+                &image.reorient_as(Some(orientation)),
+                crate::image_ext::THUMB_SIZE,
+            );
+
+            Ok((orientation, thumb, oriented_jpeg))
+        }
+    })
+    .await
+    .map_err(|err| match err {
+        BlockingError::Error(err) => err,
+        BlockingError::Canceled => Error::OperationCancelled,
+    })?;
+
+    let (thumb_width, thumb_height) = thumb.dimensions();
+
+    let appearance_ids = crate::db::model::Photo::rotate(
+        &db,
+        photo_id,
+        rotation,
+        orientation,
+        thumb_width,
+        thumb_height,
+    )
+    .await?;
+
+    web::block(move || {
+        thumb.save(&thumb_file_path).map_err(Error::SaveThumbFailed)?;
+
+        // The orientation that's been recorded stands in for the file's, so the photo is the right
+        // way round here whether or not its file could be rewritten.
+        match oriented_jpeg {
+            Some(contents) => {
+                let temp_file_path = photo_file_path.with_extension("tmp");
+                if let Err(err) = std::fs::write(&temp_file_path, contents)
+                    .and_then(|_| std::fs::rename(&temp_file_path, &photo_file_path))
+                {
+                    log::warn!("Failed to turn {}: {}", photo_file_path.display(), err);
+                }
+            }
+            None => log::info!(
+                "Left {} as it was uploaded, as it can't be turned without being encoded again",
+                photo_file_path.display()
+            ),
+        }
+
+        Ok(())
+    })
+    .await
+    .map_err(|err| match err {
+        BlockingError::Error(err) => err,
+        BlockingError::Canceled => Error::OperationCancelled,
+    })?;
+
+    crate::files::remove_all(
+        Vec::new(),
+        appearance_ids
+            .iter()
+            .map(|appearance_id| avatar_dir.join(appearance_id.to_string()))
+            .collect(),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(()))
+}
@@ -0,0 +1,114 @@
+use actix_web::{post, web, HttpResponse};
+
+const DEFAULT_NEIGHBOUR_COUNT: usize = 5;
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+}
+
+/// Matches every face that was left to automatic matching again, optionally with a different
+/// strategy or tolerance, reporting who each would now be assigned to. The new assignments are
+/// only recorded if asked for.
+#[post("/admin/rematch")]
+pub async fn endpoint(
+    rematch: web::Json<photos_web_core::Rematch>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    use crate::recognition::Strategy;
+    use photos_web_core::MatchStrategy;
+
+    let rematch = rematch.into_inner();
+    let default_matcher = recognition.matcher();
+
+    let neighbour_count = rematch
+        .neighbour_count
+        .or(match default_matcher.strategy {
+            Strategy::Vote { neighbour_count } => Some(neighbour_count),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_NEIGHBOUR_COUNT);
+
+    let matcher = crate::recognition::Matcher {
+        tolerance: rematch.tolerance.unwrap_or(default_matcher.tolerance),
+        strategy: match rematch.strategy {
+            Some(MatchStrategy::Nearest) => Strategy::Nearest,
+            Some(MatchStrategy::Vote) => Strategy::Vote { neighbour_count },
+            Some(MatchStrategy::Centroid) => Strategy::Centroid,
+            None => default_matcher.strategy,
+        },
+    };
+
+    let faces = crate::db::model::Appearance::fetch_unconfirmed_encodings(
+        &db,
+        recognition.encoder().to_owned(),
+    )
+    .await?;
+
+    let matches = web::block({
+        let recognition = recognition.get_ref().clone();
+        move || {
+            Ok::<_, ()>(
+                faces
+                    .into_iter()
+                    .map(|(appearance_id, previous_person_id, face_encoding)| {
+                        (
+                            appearance_id,
+                            previous_person_id,
+                            recognition.identify_with(&face_encoding, &matcher),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+    })
+    .await
+    .map_err(|_| Error::OperationCancelled)?;
+
+    if rematch.apply {
+        crate::db::model::Appearance::record_matches(
+            &db,
+            matches
+                .iter()
+                .map(|(appearance_id, _, matched)| {
+                    (
+                        *appearance_id,
+                        matched.as_ref().map(|matched| matched.person),
+                        matched
+                            .as_ref()
+                            .map(|matched| (matched.appearance, matched.distance)),
+                    )
+                })
+                .collect(),
+        )
+        .await?;
+
+        let mut known_faces = recognition.known_faces_mut();
+        for (appearance_id, _, matched) in matches.iter() {
+            known_faces.rematch(
+                *appearance_id,
+                matched.as_ref().map(|matched| matched.person),
+            );
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(photos_web_core::RematchOutcomes::from(
+        matches
+            .into_iter()
+            .map(|(appearance_id, previous_person_id, matched)| {
+                (
+                    appearance_id,
+                    photos_web_core::RematchOutcome {
+                        previous_person: previous_person_id,
+                        person: matched.as_ref().map(|matched| matched.person),
+                        distance: matched.as_ref().map(|matched| matched.distance),
+                    },
+                )
+            })
+            .collect::<Vec<_>>(),
+    )))
+}
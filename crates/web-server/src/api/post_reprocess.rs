@@ -0,0 +1,52 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database query failed")]
+    DatabaseQueryError(#[from] crate::db::QueryError),
+}
+
+/// Queues a job to run face detection over each of the chosen photos again, yielding the ids of
+/// the jobs, which report the appearances that were added, removed and changed once they're done.
+/// Detection settings given with the request override the server's own for these jobs only, so
+/// that a lower minimum face size, say, can be tried on a few photos.
+#[post("/admin/reprocess")]
+pub async fn endpoint(
+    reprocess: web::Json<photos_web_core::Reprocess>,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photos_web_core::Reprocess {
+        photos,
+        from,
+        to,
+        without_appearances,
+        min_face_pixels,
+        min_face_fraction,
+        upsample,
+        max_image_size,
+    } = reprocess.into_inner();
+
+    let photo_ids =
+        crate::db::model::Photo::fetch_ids(&db, photos, from, to, without_appearances).await?;
+
+    let job_ids = crate::db::model::Job::insert_reprocessing(
+        &db,
+        photo_ids.clone(),
+        min_face_pixels,
+        min_face_fraction,
+        upsample,
+        max_image_size,
+    )
+    .await?;
+
+    Ok(HttpResponse::Accepted().json(
+        photo_ids
+            .into_iter()
+            .zip(job_ids)
+            .map(|(photo_id, job_id)| photos_web_core::AcceptedPhoto {
+                photo: photo_id,
+                job: job_id,
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
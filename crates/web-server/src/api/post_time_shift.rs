@@ -0,0 +1,30 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to shift the photos")]
+    RedateFailed(#[from] crate::db::model::photo::RedateError),
+}
+
+/// Moves when each of the chosen photos was taken, responding with the ids of those that were
+/// changed.
+#[post("/photos/time-shift")]
+pub async fn endpoint(
+    time_shift: web::Json<photos_web_core::TimeShift>,
+    db: web::Data<crate::db::System>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photos_web_core::TimeShift { photos, seconds } = time_shift.into_inner();
+
+    let photo_ids = crate::db::model::Photo::shift_original_datetimes(
+        &db,
+        &photo_dir,
+        &thumb_dir,
+        photos,
+        chrono::Duration::seconds(seconds),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(photo_ids))
+}
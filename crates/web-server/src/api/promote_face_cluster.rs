@@ -0,0 +1,30 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[post("/face-clusters/promote")]
+pub async fn endpoint(
+    cluster: web::Json<photos_web_core::PromotedFaceCluster>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photos_web_core::PromotedFaceCluster {
+        person,
+        appearances,
+    } = cluster.into_inner();
+
+    let person_id = crate::db::model::Person::from(person)
+        .insert_from_appearances(&db, None, appearances.clone())
+        .await?;
+
+    let mut known_faces = recognition.known_faces_mut();
+    for appearance_id in appearances.into_iter() {
+        known_faces.reassign(appearance_id, None, Some(person_id), true);
+    }
+
+    Ok(HttpResponse::Ok().json(person_id))
+}
@@ -0,0 +1,41 @@
+use actix_web::{delete, web, HttpRequest, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid query string")]
+    QueryStringError(#[from] serde_qs::Error),
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+/// Deletes a photo in the trash for good, along with its appearances and files.
+#[delete("/trash/{id:\\d+}")]
+pub async fn endpoint(
+    req: HttpRequest,
+    photo_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+    avatar_dir: web::Data<crate::api::AvatarDirPath>,
+    trash_dir: web::Data<crate::api::TrashDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let params: photos_web_core::PurgeQueryParams = serde_qs::from_str(req.query_string())?;
+
+    let deleted = crate::trash::purge(
+        &db,
+        &recognition,
+        &photo_dir,
+        &thumb_dir,
+        &avatar_dir,
+        &trash_dir,
+        *photo_id,
+        params.delete_empty_people,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(photos_web_core::DeletedPhoto {
+        appearances: deleted.appearances,
+        people: deleted.people,
+    }))
+}
@@ -0,0 +1,33 @@
+use actix_web::{put, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[put("/appearances/{id:\\d+}")]
+pub async fn endpoint(
+    appearance_id: web::Path<i32>,
+    appearance: web::Json<photos_web_core::AppearanceUpdate>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photos_web_core::AppearanceUpdate { person, reference } = appearance.into_inner();
+
+    // Assigning a face by hand is as good as confirming it.
+    let previous_person = crate::db::model::Appearance::update(
+        &db,
+        *appearance_id,
+        person,
+        reference,
+        person.is_some(),
+    )
+    .await?;
+
+    recognition
+        .known_faces_mut()
+        .reassign(*appearance_id, previous_person, person, reference);
+
+    Ok(HttpResponse::Ok().json(()))
+}
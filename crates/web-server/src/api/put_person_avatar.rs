@@ -0,0 +1,19 @@
+use actix_web::{put, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+/// Makes one of a person's appearances, given by id, their avatar.
+#[put("/people/{id:\\d+}/avatar")]
+pub async fn endpoint(
+    person_id: web::Path<i32>,
+    appearance_id: web::Json<i32>,
+    db: web::Data<crate::db::System>,
+) -> Result<actix_web::HttpResponse, Error> {
+    crate::db::model::Avatar::set(&db, *person_id, appearance_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,39 @@
+use actix_web::{put, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Offset of {0} seconds from UTC out of range")]
+    OffsetOutOfRange(i32),
+    #[error("Failed to update the photo")]
+    RedateFailed(#[from] crate::db::model::photo::RedateError),
+}
+
+/// Sets or clears when a photo was taken, and at what offset from UTC, moving its files into the
+/// month it now belongs in.
+#[put("/photos/{id:\\d+}")]
+pub async fn endpoint(
+    photo_id: web::Path<i32>,
+    photo: web::Json<photos_web_core::PhotoUpdate>,
+    db: web::Data<crate::db::System>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photo = photo.into_inner();
+
+    let original_offset = photo
+        .original_offset
+        .map(|offset| chrono::FixedOffset::east_opt(offset).ok_or(Error::OffsetOutOfRange(offset)))
+        .transpose()?;
+
+    crate::db::model::Photo::set_original_datetime(
+        &db,
+        &photo_dir,
+        &thumb_dir,
+        *photo_id,
+        photo.original_datetime,
+        original_offset,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(()))
+}
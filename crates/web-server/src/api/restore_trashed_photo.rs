@@ -0,0 +1,31 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[post("/trash/{id:\\d+}/restore")]
+pub async fn endpoint(
+    photo_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+    photo_dir: web::Data<crate::api::PhotoDirPath>,
+    thumb_dir: web::Data<crate::api::ThumbDirPath>,
+    trash_dir: web::Data<crate::api::TrashDirPath>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let trashed =
+        crate::db::model::Photo::restore(&db, *photo_id, recognition.encoder().to_owned()).await?;
+
+    crate::trash::move_out(&photo_dir, &thumb_dir, &trash_dir, &trashed.file_name).await;
+
+    {
+        let mut known_faces = recognition.known_faces_mut();
+        for (appearance_id, face_encoding, face) in trashed.faces.into_iter() {
+            known_faces.insert(appearance_id, face, face_encoding);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(()))
+}
@@ -0,0 +1,31 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Database update failed")]
+    DatabaseUpdateQueryError(#[from] crate::db::UpdateQueryError),
+}
+
+#[post("/people/{id:\\d+}/split")]
+pub async fn endpoint(
+    person_id: web::Path<i32>,
+    split: web::Json<photos_web_core::SplitPerson>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let photos_web_core::SplitPerson {
+        person,
+        appearances,
+    } = split.into_inner();
+
+    let new_person_id = crate::db::model::Person::from(person)
+        .insert_from_appearances(&db, Some(*person_id), appearances.clone())
+        .await?;
+
+    let mut known_faces = recognition.known_faces_mut();
+    for appearance_id in appearances.into_iter() {
+        known_faces.reassign(appearance_id, Some(*person_id), Some(new_person_id), true);
+    }
+
+    Ok(HttpResponse::Ok().json(new_person_id))
+}
@@ -0,0 +1,26 @@
+use actix_web::{post, web, HttpResponse};
+
+#[derive(Debug, super::Error, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to undo merge")]
+    UndoFailed(#[from] crate::db::model::merge::UndoError),
+}
+
+/// Restores a merged person, responding with their id.
+#[post("/merges/{id:\\d+}/undo")]
+pub async fn endpoint(
+    merge_id: web::Path<i32>,
+    db: web::Data<crate::db::System>,
+    recognition: web::Data<crate::recognition::Service>,
+) -> Result<actix_web::HttpResponse, Error> {
+    let undone = crate::db::model::Merge::undo(&db, *merge_id).await?;
+
+    recognition.known_faces_mut().unmerge(
+        undone.dst,
+        undone.person,
+        &undone.appearances,
+        &undone.excluded_appearances,
+    );
+
+    Ok(HttpResponse::Ok().json(undone.person))
+}
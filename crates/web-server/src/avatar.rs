@@ -0,0 +1,349 @@
+//! Renders avatars from the faces in photos. Each avatar is a square around the face, turned so
+//! that the eyes are level where they can be made out, with anything past the edges of the photo
+//! left transparent. Rendered avatars are cached on disk by appearance, region and size, so a face
+//! that's redetected elsewhere in its photo is rendered afresh.
+
+const DEFAULT_SIZE: u32 = 128;
+const MAX_SIZE: u32 = 1024;
+
+const JPEG_QUALITY: u8 = 90;
+const WEBP_QUALITY: f32 = 90.0;
+
+/// What shows through where an avatar is transparent, for formats without transparency.
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("Avatar size must be between 1 and {}", MAX_SIZE)]
+    InvalidSize,
+    #[error("Failed to load image")]
+    ImageLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    #[error("Failed to locate face")]
+    FaceLandmarksFailed(#[source] crate::recognition::ServiceError),
+    #[error("Failed to encode avatar")]
+    EncodeError(#[source] image::ImageError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+}
+
+/// Responds with the avatar cut from `avatar`'s face as described by `params`, rendering it if it
+/// hasn't been already.
+pub async fn respond(
+    avatar: crate::db::model::Avatar,
+    params: &photos_web_core::AvatarQueryParams,
+    photo_dir_path: &std::path::Path,
+    avatar_dir_path: &std::path::Path,
+    recognition: &crate::recognition::Service,
+) -> Result<actix_web::HttpResponse, RenderError> {
+    let size = params.size.unwrap_or(DEFAULT_SIZE);
+    if size == 0 || size > MAX_SIZE {
+        return Err(RenderError::InvalidSize);
+    }
+
+    let format = params.format.unwrap_or(photos_web_core::ImageFormat::Png);
+    let circle = params.circle;
+
+    let face_box = crate::recognition::FaceBox {
+        top: avatar.top,
+        left: avatar.left,
+        bottom: avatar.bottom,
+        right: avatar.right,
+    };
+
+    let cache_file_path = avatar_dir_path
+        .join(avatar.appearance.to_string())
+        .join(format!(
+            "{}-{}-{}-{}-{}{}.{}",
+            face_box.top,
+            face_box.left,
+            face_box.bottom,
+            face_box.right,
+            size,
+            if circle { "-circle" } else { "" },
+            extension(format),
+        ));
+
+    let cached = block({
+        let cache_file_path = cache_file_path.clone();
+        move || Ok(std::fs::read(&cache_file_path).ok())
+    })
+    .await?;
+
+    if let Some(contents) = cached {
+        return Ok(response(format, contents));
+    }
+
+    let image = block({
+        let photo_file_path = photo_dir_path.join(&avatar.file_name);
+        let orientation = avatar
+            .orientation
+            .and_then(|orientation| crate::image_ext::Orientation::from_exif(orientation.into()));
+        move || {
+            crate::image_ext::load_rgb(&photo_file_path, orientation)
+                .map_err(RenderError::ImageLoadFailed)
+        }
+    })
+    .await?;
+
+    // Faces recorded before their landmarks were kept have them located afresh.
+    let landmarks = match avatar.landmarks {
+        Some(landmarks) => landmarks,
+        None => recognition
+            .encode_face(image.clone(), face_box)
+            .await
+            .map_err(RenderError::FaceLandmarksFailed)?
+            .map(|face| face.landmarks)
+            .unwrap_or_default(),
+    };
+
+    let contents = block(move || {
+        let rendered = render(&image, &face_box, &landmarks, size, circle);
+        let contents = encode(&rendered, format)?;
+
+        if let Err(err) = store(&cache_file_path, &contents) {
+            log::warn!("Failed to cache avatar {}: {}", cache_file_path.display(), err);
+        }
+
+        Ok(contents)
+    })
+    .await?;
+
+    Ok(response(format, contents))
+}
+
+async fn block<F, T>(f: F) -> Result<T, RenderError>
+where
+    F: FnOnce() -> Result<T, RenderError> + Send + 'static,
+    T: Send + 'static,
+{
+    use actix_web::error::BlockingError;
+
+    actix_web::web::block(f).await.map_err(|err| match err {
+        BlockingError::Error(err) => err,
+        BlockingError::Canceled => RenderError::OperationCancelled,
+    })
+}
+
+/// Writes a rendered avatar to the cache, by way of a temporary file so that a partly written one
+/// is never served.
+fn store(cache_file_path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(cache_dir_path) = cache_file_path.parent() {
+        std::fs::create_dir_all(cache_dir_path)?;
+    }
+
+    let temp_file_path = cache_file_path.with_extension("tmp");
+    std::fs::write(&temp_file_path, contents)?;
+    std::fs::rename(&temp_file_path, cache_file_path)
+}
+
+/// The angle, in radians, by which the line between the eyes dips from the subject's right eye to
+/// their left, found from the middles of the landmarks around each.
+fn eye_angle(landmarks: &crate::db::model::FaceLandmarks) -> Option<f64> {
+    // Indexes in the 68 point scheme.
+    const RIGHT_EYE: std::ops::Range<usize> = 36..42;
+    const LEFT_EYE: std::ops::Range<usize> = 42..48;
+
+    let middle = |range: std::ops::Range<usize>| {
+        let points = landmarks.get(range)?;
+        let count = points.len() as f64;
+        Some((
+            points.iter().map(|(x, _)| *x as f64).sum::<f64>() / count,
+            points.iter().map(|(_, y)| *y as f64).sum::<f64>() / count,
+        ))
+    };
+
+    let (right_x, right_y) = middle(RIGHT_EYE)?;
+    let (left_x, left_y) = middle(LEFT_EYE)?;
+
+    Some((left_y - right_y).atan2(left_x - right_x))
+}
+
+/// Renders a `size` pixel square showing the region of twice the size of the face around its
+/// middle, levelled by the angle of the eyes.
+fn render(
+    image: &image::RgbImage,
+    face_box: &crate::recognition::FaceBox,
+    landmarks: &crate::db::model::FaceLandmarks,
+    size: u32,
+    circle: bool,
+) -> image::RgbaImage {
+    let centre_x = (face_box.left + face_box.right) as f64 / 2.0;
+    let centre_y = (face_box.top + face_box.bottom) as f64 / 2.0;
+    let side = std::cmp::max(
+        std::cmp::max(face_box.right - face_box.left, face_box.bottom - face_box.top) * 2,
+        1,
+    ) as f64;
+    let (sin, cos) = eye_angle(landmarks).unwrap_or(0.0).sin_cos();
+
+    // Shrinking the region first, rather than sampling it sparsely, keeps the detail of large
+    // faces from aliasing. It's cut out with enough margin to be turned any way.
+    let reach = side * std::f64::consts::FRAC_1_SQRT_2 + 1.0;
+    let clamp = |value: f64, limit: u32| value.max(0.0).min(limit as f64) as u32;
+    let region_left = clamp((centre_x - reach).floor(), image.width());
+    let region_top = clamp((centre_y - reach).floor(), image.height());
+    let region_right = clamp((centre_x + reach).ceil(), image.width());
+    let region_bottom = clamp((centre_y + reach).ceil(), image.height());
+
+    if region_right <= region_left || region_bottom <= region_top {
+        return image::RgbaImage::new(size, size);
+    }
+
+    let region = image::imageops::crop_imm(
+        image,
+        region_left,
+        region_top,
+        region_right - region_left,
+        region_bottom - region_top,
+    )
+    .to_image();
+
+    let scale = (size as f64 / side).min(1.0);
+    let region = if scale < 1.0 {
+        image::imageops::resize(
+            &region,
+            std::cmp::max((region.width() as f64 * scale).round() as u32, 1),
+            std::cmp::max((region.height() as f64 * scale).round() as u32, 1),
+            image::imageops::FilterType::Lanczos3,
+        )
+    } else {
+        region
+    };
+    let scale_x = region.width() as f64 / (region_right - region_left) as f64;
+    let scale_y = region.height() as f64 / (region_bottom - region_top) as f64;
+
+    let radius = size as f64 / 2.0;
+
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let offset_x = ((x as f64 + 0.5) / size as f64 - 0.5) * side;
+        let offset_y = ((y as f64 + 0.5) / size as f64 - 0.5) * side;
+
+        let source_x = centre_x + offset_x * cos - offset_y * sin;
+        let source_y = centre_y + offset_x * sin + offset_y * cos;
+
+        let mut pixel = sample(
+            &region,
+            (source_x - region_left as f64) * scale_x - 0.5,
+            (source_y - region_top as f64) * scale_y - 0.5,
+        );
+
+        if circle {
+            let distance = ((x as f64 + 0.5 - radius).powi(2) + (y as f64 + 0.5 - radius).powi(2))
+                .sqrt();
+            let coverage = (radius - distance + 0.5).max(0.0).min(1.0);
+            pixel[3] = (pixel[3] as f64 * coverage).round() as u8;
+        }
+
+        pixel
+    })
+}
+
+/// Samples an image between its pixels, treating everything beyond its edges as transparent.
+fn sample(image: &image::RgbImage, x: f64, y: f64) -> image::Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fraction_x, fraction_y) = (x - x0, y - y0);
+
+    let mut colour = [0f64; 3];
+    let mut coverage = 0f64;
+
+    for (dx, dy, weight) in [
+        (0, 0, (1.0 - fraction_x) * (1.0 - fraction_y)),
+        (1, 0, fraction_x * (1.0 - fraction_y)),
+        (0, 1, (1.0 - fraction_x) * fraction_y),
+        (1, 1, fraction_x * fraction_y),
+    ]
+    .iter()
+    {
+        let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+        if px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+            continue;
+        }
+
+        let pixel = image.get_pixel(px as u32, py as u32);
+        for (channel, value) in colour.iter_mut().zip(pixel.0.iter()) {
+            *channel += *value as f64 * weight;
+        }
+        coverage += weight;
+    }
+
+    if coverage <= 0.0 {
+        return image::Rgba([0, 0, 0, 0]);
+    }
+
+    image::Rgba([
+        (colour[0] / coverage).round() as u8,
+        (colour[1] / coverage).round() as u8,
+        (colour[2] / coverage).round() as u8,
+        (coverage * 255.0).round() as u8,
+    ])
+}
+
+fn encode(
+    image: &image::RgbaImage,
+    format: photos_web_core::ImageFormat,
+) -> Result<Vec<u8>, RenderError> {
+    use photos_web_core::ImageFormat;
+
+    let mut contents = Vec::new();
+
+    match format {
+        ImageFormat::Jpeg => {
+            let flattened = image::RgbImage::from_fn(image.width(), image.height(), |x, y| {
+                let pixel = image.get_pixel(x, y);
+                let alpha = pixel[3] as u32;
+                let blend = |channel: usize| {
+                    ((pixel[channel] as u32 * alpha + BACKGROUND[channel] as u32 * (255 - alpha))
+                        / 255) as u8
+                };
+                image::Rgb([blend(0), blend(1), blend(2)])
+            });
+
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut contents, JPEG_QUALITY)
+                .encode(
+                    flattened.as_raw(),
+                    flattened.width(),
+                    flattened.height(),
+                    image::ColorType::Rgb8,
+                )
+                .map_err(RenderError::EncodeError)?;
+        }
+        ImageFormat::Png => {
+            image::codecs::png::PngEncoder::new(&mut contents)
+                .encode(
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    image::ColorType::Rgba8,
+                )
+                .map_err(RenderError::EncodeError)?;
+        }
+        ImageFormat::Webp => {
+            // The image crate can only read WebP, so it's written with libwebp instead.
+            contents.extend_from_slice(
+                &webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height())
+                    .encode(WEBP_QUALITY),
+            );
+        }
+    }
+
+    Ok(contents)
+}
+
+fn extension(format: photos_web_core::ImageFormat) -> &'static str {
+    match format {
+        photos_web_core::ImageFormat::Jpeg => "jpg",
+        photos_web_core::ImageFormat::Png => "png",
+        photos_web_core::ImageFormat::Webp => "webp",
+    }
+}
+
+fn response(format: photos_web_core::ImageFormat, contents: Vec<u8>) -> actix_web::HttpResponse {
+    let content_type = match format {
+        photos_web_core::ImageFormat::Jpeg => "image/jpeg",
+        photos_web_core::ImageFormat::Png => "image/png",
+        photos_web_core::ImageFormat::Webp => "image/webp",
+    };
+
+    actix_web::HttpResponse::Ok()
+        .header(actix_web::http::header::CONTENT_TYPE, content_type)
+        .body(contents)
+}
@@ -41,6 +41,7 @@ pub struct System {
     connection_pool: ConnectionPool,
     appearances_guard: Guard,
     avatars_guard: Guard,
+    jobs_guard: Guard,
     people_guard: Guard,
     photos_guard: Guard,
 }
@@ -80,6 +81,7 @@ impl System {
             connection_pool,
             appearances_guard: Guard::new(),
             avatars_guard: Guard::new(),
+            jobs_guard: Guard::new(),
             people_guard: Guard::new(),
             photos_guard: Guard::new(),
         })
@@ -93,6 +95,10 @@ impl System {
         &self.avatars_guard
     }
 
+    pub fn jobs_insertion_guard(&self) -> &Guard {
+        &self.jobs_guard
+    }
+
     pub fn people_insertion_guard(&self) -> &Guard {
         &self.people_guard
     }
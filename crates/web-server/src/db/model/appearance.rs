@@ -4,7 +4,7 @@ use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 #[derive(diesel::Insertable)]
 #[table_name = "appearances"]
 struct NewAppearance {
-    pub person: i32,
+    pub person: Option<i32>,
     pub photo: i32,
     pub reference: bool,
     pub top: i32,
@@ -12,11 +12,19 @@ struct NewAppearance {
     pub bottom: i32,
     pub right: i32,
     pub face_encoding: crate::db::model::FaceEncoding,
+    pub match_distance: Option<f64>,
+    pub match_appearance: Option<i32>,
+    pub confirmed: bool,
+    pub encoder: Option<String>,
+    pub sharpness: Option<f64>,
+    pub frontalness: Option<f64>,
+    pub landmarks: Option<crate::db::model::FaceLandmarks>,
+    pub manual: bool,
 }
 
 #[derive(diesel::Queryable)]
 pub struct Appearance {
-    pub person: i32,
+    pub person: Option<i32>,
     pub photo: i32,
     pub reference: bool,
     pub top: i32,
@@ -24,6 +32,94 @@ pub struct Appearance {
     pub bottom: i32,
     pub right: i32,
     pub face_encoding: crate::db::model::FaceEncoding,
+    pub match_distance: Option<f64>,
+    pub match_appearance: Option<i32>,
+    pub confirmed: bool,
+    pub encoder: Option<String>,
+    pub sharpness: Option<f64>,
+    pub frontalness: Option<f64>,
+    pub landmarks: Option<crate::db::model::FaceLandmarks>,
+    pub manual: bool,
+}
+
+/// Landmarks are only recorded where the recognizer located them.
+fn non_empty(
+    landmarks: crate::db::model::FaceLandmarks,
+) -> Option<crate::db::model::FaceLandmarks> {
+    if landmarks.is_empty() {
+        None
+    } else {
+        Some(landmarks)
+    }
+}
+
+/// Records a face found by detection, with the automatic match that assigned it to its person if
+/// any, yielding its id.
+///
+/// Meant to be called within a transaction while holding the appearances insertion guard.
+fn insert_detected(
+    db_connection: &diesel::SqliteConnection,
+    photo_id: i32,
+    encoder: &str,
+    face: crate::recognition::DetectedFace,
+    matched: Option<&crate::recognition::Match>,
+) -> Result<i32, diesel::result::Error> {
+    diesel::insert_into(appearances::table)
+        .values(&NewAppearance {
+            person: matched.map(|matched| matched.person),
+            photo: photo_id,
+            reference: false,
+            top: face.face_box.top,
+            left: face.face_box.left,
+            bottom: face.face_box.bottom,
+            right: face.face_box.right,
+            face_encoding: face.face_encoding,
+            match_distance: matched.map(|matched| matched.distance),
+            match_appearance: matched.map(|matched| matched.appearance),
+            confirmed: false,
+            encoder: Some(encoder.to_owned()),
+            sharpness: Some(face.quality.sharpness),
+            frontalness: face.quality.frontalness,
+            landmarks: non_empty(face.landmarks),
+            manual: false,
+        })
+        .execute(db_connection)?;
+
+    Ok(*appearances::table
+        .select(appearances::id)
+        .order(appearances::id.desc())
+        .limit(1)
+        .load(db_connection)?
+        .get(0)
+        .unwrap())
+}
+
+/// Deletes appearances along with their avatars and exclusions, leaving any appearances matched
+/// against them unmatched, and yields how many were deleted. Their owners' avatars are left for
+/// the caller to repair.
+///
+/// Meant to be called within a transaction while holding the avatars insertion guard.
+fn delete_all(
+    db_connection: &diesel::SqliteConnection,
+    appearance_ids: &[i32],
+) -> Result<usize, diesel::result::Error> {
+    use crate::db::schema::{appearance_exclusions, avatars};
+
+    diesel::delete(avatars::table.filter(avatars::appearance.eq_any(appearance_ids)))
+        .execute(db_connection)?;
+
+    diesel::delete(
+        appearance_exclusions::table
+            .filter(appearance_exclusions::appearance.eq_any(appearance_ids)),
+    )
+    .execute(db_connection)?;
+
+    diesel::update(appearances::table.filter(appearances::match_appearance.eq_any(appearance_ids)))
+        .set(appearances::match_appearance.eq(None::<i32>))
+        .execute(db_connection)?;
+
+    diesel::delete(appearances::table.filter(appearances::id.eq_any(appearance_ids)))
+        .execute(db_connection)
 }
 
 impl Into<photos_web_core::Appearance> for Appearance {
@@ -36,14 +132,23 @@ impl Into<photos_web_core::Appearance> for Appearance {
             left: self.left,
             bottom: self.bottom,
             right: self.right,
+            match_distance: self.match_distance,
+            match_appearance: self.match_appearance,
+            confirmed: self.confirmed,
+            landmarks: None,
         }
     }
 }
 
 impl Appearance {
+    /// Records an appearance, giving its person an avatar if they don't already have one.
+    /// `matched` is the automatic match that assigned it to its person, if any, as the matched
+    /// appearance and its distance, `encoder` identifies the model that encoded its face,
+    /// `landmarks` are the points located on the face and `quality` is how well it's shown.
+    /// `manual` marks a face that was drawn around by hand rather than found by detection.
     pub async fn insert(
         db: &crate::db::System,
-        person_id: i32,
+        person_id: Option<i32>,
         photo_id: i32,
         reference: bool,
         top: i32,
@@ -51,44 +156,512 @@ impl Appearance {
         bottom: i32,
         right: i32,
         face_encoding: crate::db::model::FaceEncoding,
+        encoder: String,
+        landmarks: crate::db::model::FaceLandmarks,
+        quality: crate::recognition::FaceQuality,
+        matched: Option<(i32, f64)>,
+        confirmed: bool,
+        manual: bool,
     ) -> Result<i32, crate::db::QueryError> {
         db.run_query({
             let db = db.clone();
             move |db_connection| {
+                use crate::diesel::Connection;
+
+                let _guard = db.appearances_insertion_guard().lock();
+                let _avatars_guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    diesel::insert_into(crate::db::schema::appearances::table)
+                        .values(&NewAppearance {
+                            person: person_id,
+                            photo: photo_id,
+                            reference,
+                            top,
+                            left,
+                            bottom,
+                            right,
+                            face_encoding,
+                            match_distance: matched.map(|(_, distance)| distance),
+                            match_appearance: matched.map(|(appearance_id, _)| appearance_id),
+                            confirmed,
+                            encoder: Some(encoder),
+                            sharpness: Some(quality.sharpness),
+                            frontalness: quality.frontalness,
+                            landmarks: non_empty(landmarks),
+                            manual,
+                        })
+                        .execute(&db_connection)?;
+
+                    let appearance_id = *appearances::table
+                        .select(appearances::id)
+                        .order(appearances::id.desc())
+                        .limit(1)
+                        .load(&db_connection)?
+                        .get(0)
+                        .unwrap();
+
+                    if let Some(person_id) = person_id {
+                        crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    }
+
+                    Ok(appearance_id)
+                })
+            }
+        })
+        .await
+    }
+
+    /// Records the faces that detection found in a photo, each with the automatic match that
+    /// assigned it to its person if any, in place of those recorded by an earlier attempt at
+    /// processing it. Appearances that have since been confirmed or made references, or that were
+    /// drawn around by hand, are left be. Yields the ids of the appearances removed and of those
+    /// recorded, in the order the faces were given.
+    pub async fn replace_detected(
+        db: &crate::db::System,
+        photo_id: i32,
+        encoder: String,
+        faces: Vec<(crate::recognition::DetectedFace, Option<crate::recognition::Match>)>,
+    ) -> Result<(Vec<i32>, Vec<i32>), crate::db::QueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+
+                let _guard = db.appearances_insertion_guard().lock();
+                let _avatars_guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let previous = appearances::table
+                        .select((appearances::id, appearances::person))
+                        .filter(appearances::photo.eq(photo_id))
+                        .filter(appearances::confirmed.eq(false))
+                        .filter(appearances::reference.eq(false))
+                        .filter(appearances::manual.eq(false))
+                        .order(appearances::id)
+                        .load::<(i32, Option<i32>)>(&db_connection)?;
+
+                    let removed_ids: Vec<i32> =
+                        previous.iter().map(|(appearance_id, _)| *appearance_id).collect();
+
+                    let mut person_ids: Vec<i32> =
+                        previous.into_iter().filter_map(|(_, person_id)| person_id).collect();
+
+                    delete_all(&db_connection, &removed_ids)?;
+
+                    let mut added_ids = Vec::with_capacity(faces.len());
+                    for (face, matched) in faces.into_iter() {
+                        person_ids.extend(matched.as_ref().map(|matched| matched.person));
+                        added_ids.push(insert_detected(
+                            &db_connection,
+                            photo_id,
+                            &encoder,
+                            face,
+                            matched.as_ref(),
+                        )?);
+                    }
+
+                    person_ids.sort_unstable();
+                    person_ids.dedup();
+
+                    for person_id in person_ids.into_iter() {
+                        crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    }
+
+                    Ok((removed_ids, added_ids))
+                })
+            }
+        })
+        .await
+    }
+
+    /// Records what reprocessing a photo found, all at once: `changed` appearances are given the
+    /// face that now coincides with each, along with the result of matching it again, `added`
+    /// faces are recorded and `removed` appearances are deleted. Yields the ids of the appearances
+    /// recorded, in the order the faces were given. Fails with `NoSuchRecord`, changing nothing,
+    /// if any appearance to be changed or removed has since been confirmed, made a reference or
+    /// deleted.
+    pub async fn apply_reprocessed(
+        db: &crate::db::System,
+        photo_id: i32,
+        encoder: String,
+        changed: Vec<(i32, crate::recognition::DetectedFace, Option<crate::recognition::Match>)>,
+        added: Vec<(crate::recognition::DetectedFace, Option<crate::recognition::Match>)>,
+        removed: Vec<i32>,
+    ) -> Result<Vec<i32>, crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+
                 let _guard = db.appearances_insertion_guard().lock();
+                let _avatars_guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let appearance_ids: Vec<i32> = changed
+                        .iter()
+                        .map(|(appearance_id, _, _)| *appearance_id)
+                        .chain(removed.iter().copied())
+                        .collect();
+
+                    let previous = appearances::table
+                        .select(appearances::person)
+                        .filter(appearances::id.eq_any(&appearance_ids))
+                        .filter(appearances::photo.eq(photo_id))
+                        .filter(appearances::confirmed.eq(false))
+                        .filter(appearances::reference.eq(false))
+                        .filter(appearances::manual.eq(false))
+                        .load::<Option<i32>>(&db_connection)?;
+
+                    if previous.len() != appearance_ids.len() {
+                        return Err(diesel::result::Error::RollbackTransaction);
+                    }
+
+                    let mut person_ids: Vec<i32> = previous.into_iter().flatten().collect();
+
+                    for (appearance_id, face, matched) in changed.into_iter() {
+                        let person_id = matched.as_ref().map(|matched| matched.person);
+                        person_ids.extend(person_id);
+
+                        diesel::update(appearances::table.filter(appearances::id.eq(appearance_id)))
+                            .set((
+                                appearances::top.eq(face.face_box.top),
+                                appearances::left.eq(face.face_box.left),
+                                appearances::bottom.eq(face.face_box.bottom),
+                                appearances::right.eq(face.face_box.right),
+                                appearances::face_encoding.eq(face.face_encoding),
+                                appearances::encoder.eq(&encoder),
+                                appearances::sharpness.eq(face.quality.sharpness),
+                                appearances::frontalness.eq(face.quality.frontalness),
+                                appearances::landmarks.eq(non_empty(face.landmarks)),
+                                appearances::person.eq(person_id),
+                                appearances::match_distance
+                                    .eq(matched.as_ref().map(|matched| matched.distance)),
+                                appearances::match_appearance
+                                    .eq(matched.as_ref().map(|matched| matched.appearance)),
+                            ))
+                            .execute(&db_connection)?;
+                    }
+
+                    delete_all(&db_connection, &removed)?;
+
+                    let mut added_ids = Vec::with_capacity(added.len());
+                    for (face, matched) in added.into_iter() {
+                        person_ids.extend(matched.as_ref().map(|matched| matched.person));
+                        added_ids.push(insert_detected(
+                            &db_connection,
+                            photo_id,
+                            &encoder,
+                            face,
+                            matched.as_ref(),
+                        )?);
+                    }
+
+                    person_ids.sort_unstable();
+                    person_ids.dedup();
+
+                    for person_id in person_ids.into_iter() {
+                        crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    }
 
-                diesel::insert_into(crate::db::schema::appearances::table)
-                    .values(&NewAppearance {
-                        person: person_id,
-                        photo: photo_id,
+                    Ok(added_ids)
+                })
+            }
+        })
+        .await
+        .map_err(|err| match err {
+            crate::db::QueryError::QueryError(diesel::result::Error::RollbackTransaction) => {
+                crate::db::UpdateQueryError::NoSuchRecord
+            }
+            err => crate::db::UpdateQueryError::QueryError(err),
+        })
+    }
+
+    pub async fn fetch_all_for_photo(
+        db: &crate::db::System,
+        photo_id: i32,
+    ) -> Result<Vec<(i32, Appearance)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::appearances::dsl::*;
+            appearances
+                .select((
+                    id,
+                    (
+                        person,
+                        photo,
                         reference,
                         top,
                         left,
                         bottom,
                         right,
                         face_encoding,
-                    })
-                    .execute(&db_connection)?;
-
-                Ok(*appearances::table
-                    .select(appearances::id)
-                    .order(appearances::id.desc())
-                    .limit(1)
-                    .load(&db_connection)?
-                    .get(0)
-                    .unwrap())
+                        match_distance,
+                        match_appearance,
+                        confirmed,
+                        encoder,
+                        sharpness,
+                        frontalness,
+                        landmarks,
+                        manual,
+                    ),
+                ))
+                .filter(photo.eq(photo_id))
+                .order_by(id)
+                .load::<(i32, Appearance)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Fetches the encodings of every appearance that hasn't been assigned to a person, out of
+    /// those encoded by `current_encoder`. Photos in the trash are left out.
+    pub async fn fetch_unassigned_encodings(
+        db: &crate::db::System,
+        current_encoder: String,
+    ) -> Result<Vec<(i32, crate::db::model::FaceEncoding)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::appearances::dsl::*;
+            use crate::db::schema::trashed_photos;
+            appearances
+                .select((id, face_encoding))
+                .filter(person.is_null())
+                .filter(encoder.eq(current_encoder))
+                .filter(photo.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                .order_by(id)
+                .load::<(i32, crate::db::model::FaceEncoding)>(&db_connection)
+        })
+        .await
+    }
+
+    pub async fn fetch(
+        db: &crate::db::System,
+        appearance_id: i32,
+    ) -> Result<Option<Appearance>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::appearances::dsl::*;
+            appearances
+                .select((
+                    person,
+                    photo,
+                    reference,
+                    top,
+                    left,
+                    bottom,
+                    right,
+                    face_encoding,
+                    match_distance,
+                    match_appearance,
+                    confirmed,
+                    encoder,
+                    sharpness,
+                    frontalness,
+                    landmarks,
+                    manual,
+                ))
+                .filter(id.eq(appearance_id))
+                .load::<Appearance>(&db_connection)
+        })
+        .await
+        .map(|mut appearances| appearances.pop())
+    }
+
+    /// Assigns an appearance to a person, or detaches it if `person_id` is `None`, fixing up the
+    /// avatars of both its previous and new owner. The appearance is remembered as not being of
+    /// its previous owner, and any automatic match is forgotten. Yields that previous owner.
+    pub async fn update(
+        db: &crate::db::System,
+        appearance_id: i32,
+        person_id: Option<i32>,
+        reference: bool,
+        confirmed: bool,
+    ) -> Result<Option<i32>, crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let previous_person_id = match appearances::table
+                        .select(appearances::person)
+                        .filter(appearances::id.eq(appearance_id))
+                        .first::<Option<i32>>(&db_connection)
+                        .optional()?
+                    {
+                        Some(previous_person_id) => previous_person_id,
+                        None => return Ok(None),
+                    };
+
+                    diesel::update(appearances::table.filter(appearances::id.eq(appearance_id)))
+                        .set((
+                            appearances::person.eq(person_id),
+                            appearances::reference.eq(reference),
+                            appearances::match_distance.eq(None::<f64>),
+                            appearances::match_appearance.eq(None::<i32>),
+                            appearances::confirmed.eq(confirmed),
+                        ))
+                        .execute(&db_connection)?;
+
+                    if let Some(person_id) = person_id {
+                        crate::db::model::exclusion::include_appearance(
+                            &db_connection,
+                            appearance_id,
+                            person_id,
+                        )?;
+                    }
+
+                    if let Some(previous_person_id) = previous_person_id {
+                        if Some(previous_person_id) != person_id {
+                            crate::db::model::exclusion::exclude_appearance(
+                                &db_connection,
+                                appearance_id,
+                                previous_person_id,
+                            )?;
+                        }
+                    }
+
+                    for person_id in previous_person_id.iter().chain(person_id.iter()) {
+                        crate::db::model::Avatar::repair(&db_connection, *person_id)?;
+                    }
+
+                    Ok(Some(previous_person_id))
+                })
             }
         })
         .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| result.ok_or(crate::db::UpdateQueryError::NoSuchRecord))
     }
 
-    pub async fn fetch_all_for_photo(
+    /// Fetches the photos with appearances that weren't encoded by `current_encoder`, including
+    /// any recorded before encoders were tracked. Photos in the trash are left out.
+    pub async fn fetch_stale_photo_ids(
         db: &crate::db::System,
-        photo_id: i32,
-    ) -> Result<Vec<(i32, Appearance)>, crate::db::QueryError> {
+        current_encoder: String,
+    ) -> Result<Vec<i32>, crate::db::QueryError> {
         db.run_query(move |db_connection| {
             use crate::db::schema::appearances::dsl::*;
+            use crate::db::schema::trashed_photos;
+            use diesel::BoolExpressionMethods;
+
             appearances
+                .select(photo)
+                .distinct()
+                .filter(encoder.is_null().or(encoder.ne(current_encoder)))
+                .filter(photo.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                .order_by(photo)
+                .load::<i32>(&db_connection)
+        })
+        .await
+    }
+
+    /// Replaces the encoding of an appearance with one produced by another model, along with
+    /// the landmarks located on its face and how well it's shown, leaving everything else about
+    /// it as it is.
+    pub async fn reencode(
+        db: &crate::db::System,
+        appearance_id: i32,
+        face_encoding: crate::db::model::FaceEncoding,
+        encoder: String,
+        landmarks: crate::db::model::FaceLandmarks,
+        quality: crate::recognition::FaceQuality,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        db.run_query(move |db_connection| {
+            diesel::update(appearances::table.filter(appearances::id.eq(appearance_id)))
+                .set((
+                    appearances::face_encoding.eq(face_encoding),
+                    appearances::encoder.eq(encoder),
+                    appearances::sharpness.eq(quality.sharpness),
+                    appearances::frontalness.eq(quality.frontalness),
+                    appearances::landmarks.eq(non_empty(landmarks)),
+                ))
+                .execute(&db_connection)
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+
+    /// Deletes an appearance, giving its owner a new avatar if it was theirs.
+    pub async fn delete(
+        db: &crate::db::System,
+        appearance_id: i32,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let person_id = match appearances::table
+                        .select(appearances::person)
+                        .filter(appearances::id.eq(appearance_id))
+                        .first::<Option<i32>>(&db_connection)
+                        .optional()?
+                    {
+                        Some(person_id) => person_id,
+                        None => return Ok(0),
+                    };
+
+                    let deleted = delete_all(&db_connection, &[appearance_id])?;
+
+                    if let Some(person_id) = person_id {
+                        crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    }
+
+                    Ok(deleted)
+                })
+            }
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+
+    pub async fn confirm(
+        db: &crate::db::System,
+        appearance_id: i32,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        db.run_query(move |db_connection| {
+            diesel::update(
+                appearances::table
+                    .filter(appearances::id.eq(appearance_id))
+                    .filter(appearances::person.is_not_null()),
+            )
+            .set(appearances::confirmed.eq(true))
+            .execute(&db_connection)
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+
+    /// Fetches the unconfirmed appearances that have been assigned to someone, least certain
+    /// match first. Photos in the trash are left out.
+    pub async fn fetch_unconfirmed(
+        db: &crate::db::System,
+        limit: Option<i64>,
+    ) -> Result<Vec<(i32, Appearance)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::appearances::dsl::*;
+            use crate::db::schema::trashed_photos;
+            let query = appearances
                 .select((
                     id,
                     (
@@ -100,11 +673,109 @@ impl Appearance {
                         bottom,
                         right,
                         face_encoding,
+                        match_distance,
+                        match_appearance,
+                        confirmed,
+                        encoder,
+                        sharpness,
+                        frontalness,
+                        landmarks,
+                        manual,
                     ),
                 ))
-                .filter(photo.eq(photo_id))
+                .filter(person.is_not_null())
+                .filter(confirmed.eq(false))
+                .filter(photo.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                .order_by(match_distance.is_null())
+                .then_order_by(match_distance.desc())
+                .then_order_by(id)
+                .into_boxed();
+
+            match limit {
+                Some(limit) => query.limit(limit),
+                None => query,
+            }
+            .load::<(i32, Appearance)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Fetches the owner and encoding of every appearance that was left to automatic matching,
+    /// that is, every one that's neither confirmed nor a reference, out of those encoded by
+    /// `current_encoder`. Photos in the trash are left out.
+    pub async fn fetch_unconfirmed_encodings(
+        db: &crate::db::System,
+        current_encoder: String,
+    ) -> Result<Vec<(i32, Option<i32>, crate::db::model::FaceEncoding)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::appearances::dsl::*;
+            use crate::db::schema::trashed_photos;
+            appearances
+                .select((id, person, face_encoding))
+                .filter(confirmed.eq(false))
+                .filter(reference.eq(false))
+                .filter(encoder.eq(current_encoder))
+                .filter(photo.ne_all(trashed_photos::table.select(trashed_photos::photo)))
                 .order_by(id)
-                .load::<(i32, Appearance)>(&db_connection)
+                .load::<(i32, Option<i32>, crate::db::model::FaceEncoding)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Reassigns automatically matched appearances, given as `(appearance, person, matched)`
+    /// where `matched` is the matched appearance and its distance, fixing up the avatars of
+    /// everyone affected. Appearances that have since been confirmed or made references are left
+    /// alone.
+    pub async fn record_matches(
+        db: &crate::db::System,
+        matches: Vec<(i32, Option<i32>, Option<(i32, f64)>)>,
+    ) -> Result<(), crate::db::QueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let mut affected = std::collections::BTreeSet::new();
+
+                    for (appearance_id, person_id, matched) in matches.into_iter() {
+                        let previous_person_ids = appearances::table
+                            .select(appearances::person)
+                            .filter(appearances::id.eq(appearance_id))
+                            .filter(appearances::confirmed.eq(false))
+                            .filter(appearances::reference.eq(false))
+                            .load::<Option<i32>>(&db_connection)?;
+
+                        let previous_person_id = match previous_person_ids.first() {
+                            Some(previous_person_id) => *previous_person_id,
+                            None => continue,
+                        };
+
+                        diesel::update(appearances::table.filter(appearances::id.eq(appearance_id)))
+                            .set((
+                                appearances::person.eq(person_id),
+                                appearances::match_distance
+                                    .eq(matched.map(|(_, distance)| distance)),
+                                appearances::match_appearance
+                                    .eq(matched.map(|(appearance_id, _)| appearance_id)),
+                            ))
+                            .execute(&db_connection)?;
+
+                        if previous_person_id != person_id {
+                            affected.extend(previous_person_id);
+                            affected.extend(person_id);
+                        }
+                    }
+
+                    for person_id in affected.into_iter() {
+                        crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    }
+
+                    Ok(())
+                })
+            }
         })
         .await
     }
@@ -8,51 +8,175 @@ struct NewAvatar {
     pub appearance: i32,
 }
 
+/// An appearance that could become its person's avatar.
+#[derive(diesel::Queryable)]
+struct Candidate {
+    id: i32,
+    /// Whether it has been confirmed or made a reference.
+    certain: bool,
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    sharpness: Option<f64>,
+    frontalness: Option<f64>,
+    original_datetime: Option<chrono::NaiveDateTime>,
+    upload_datetime: chrono::NaiveDateTime,
+}
+
+impl Candidate {
+    fn score(&self, now: chrono::NaiveDateTime) -> f64 {
+        crate::recognition::quality::score(
+            &crate::recognition::FaceBox {
+                top: self.top,
+                left: self.left,
+                bottom: self.bottom,
+                right: self.right,
+            },
+            self.sharpness,
+            self.frontalness,
+            self.original_datetime.unwrap_or(self.upload_datetime),
+            now,
+        )
+    }
+}
+
 #[derive(diesel::Queryable)]
 pub struct Avatar {
+    pub appearance: i32,
     pub file_name: String,
     pub top: i32,
     pub left: i32,
     pub bottom: i32,
     pub right: i32,
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum ImageError {
-    #[error("Failed to open image")]
-    ImageOpenError(#[source] std::io::Error),
-    #[error("Failed to load image")]
-    ImageLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    pub landmarks: Option<crate::db::model::FaceLandmarks>,
+    pub orientation: Option<i32>,
 }
 
 impl Avatar {
-    pub async fn insert(
+    /// Makes `appearance_id` the avatar of `person_id`, which it must be an appearance of in a
+    /// photo that isn't in the trash. It stays their avatar for as long as that remains so.
+    pub async fn set(
         db: &crate::db::System,
         person_id: i32,
         appearance_id: i32,
-    ) -> Result<i32, crate::db::QueryError> {
+    ) -> Result<(), crate::db::UpdateQueryError> {
         db.run_query({
             let db = db.clone();
             move |db_connection| {
+                use crate::db::schema::{appearances, trashed_photos};
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
                 let _guard = db.avatars_insertion_guard().lock();
 
-                diesel::insert_into(avatars::table)
-                    .values(&NewAvatar {
-                        person: person_id,
-                        appearance: appearance_id,
-                    })
-                    .execute(&db_connection)?;
-
-                Ok(*avatars::table
-                    .select(avatars::id)
-                    .order(avatars::id.desc())
-                    .limit(1)
-                    .load(&db_connection)?
-                    .get(0)
-                    .unwrap())
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    if appearances::table
+                        .select(appearances::id)
+                        .filter(appearances::id.eq(appearance_id))
+                        .filter(appearances::person.eq(person_id))
+                        .filter(
+                            appearances::photo
+                                .ne_all(trashed_photos::table.select(trashed_photos::photo)),
+                        )
+                        .first::<i32>(&db_connection)
+                        .optional()?
+                        .is_none()
+                    {
+                        return Ok(0);
+                    }
+
+                    diesel::delete(avatars::table.filter(avatars::person.eq(person_id)))
+                        .execute(&db_connection)?;
+
+                    diesel::insert_into(avatars::table)
+                        .values(&NewAvatar {
+                            person: person_id,
+                            appearance: appearance_id,
+                        })
+                        .execute(&db_connection)
+                })
             }
         })
         .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+
+    /// Makes sure `person_id`'s avatar is one of their own appearances in a photo that isn't in
+    /// the trash, keeping the current one if it still is and otherwise picking whichever of their
+    /// faces scores best. Faces that have been confirmed or made references are preferred over
+    /// those matched automatically, as they're surely of the right person. The avatar is removed
+    /// if they have no appearances left.
+    ///
+    /// Meant to be called within a transaction while holding the avatars insertion guard.
+    pub fn repair(
+        db_connection: &diesel::SqliteConnection,
+        person_id: i32,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::db::schema::{appearances, photos, trashed_photos};
+        use diesel::{BoolExpressionMethods, OptionalExtension};
+
+        let current = avatars::table
+            .inner_join(appearances::table)
+            .select(avatars::id)
+            .filter(avatars::person.eq(person_id))
+            .filter(appearances::person.eq(person_id))
+            .filter(appearances::photo.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+            .first::<i32>(db_connection)
+            .optional()?;
+
+        if current.is_some() {
+            return Ok(());
+        }
+
+        diesel::delete(avatars::table.filter(avatars::person.eq(person_id)))
+            .execute(db_connection)?;
+
+        let candidates = appearances::table
+            .inner_join(photos::table)
+            .select((
+                appearances::id,
+                appearances::reference.or(appearances::confirmed),
+                appearances::top,
+                appearances::left,
+                appearances::bottom,
+                appearances::right,
+                appearances::sharpness,
+                appearances::frontalness,
+                photos::original_datetime,
+                photos::upload_datetime,
+            ))
+            .filter(appearances::person.eq(person_id))
+            .filter(photos::id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+            .order(appearances::id)
+            .load::<Candidate>(db_connection)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let replacement = candidates
+            .iter()
+            .map(|candidate| (candidate, candidate.score(now)))
+            .max_by(|(a, a_score), (b, b_score)| {
+                a.certain
+                    .cmp(&b.certain)
+                    .then(a_score.partial_cmp(b_score).unwrap_or(std::cmp::Ordering::Equal))
+            })
+            .map(|(candidate, _)| candidate.id);
+
+        if let Some(appearance_id) = replacement {
+            diesel::insert_into(avatars::table)
+                .values(&NewAvatar {
+                    person: person_id,
+                    appearance: appearance_id,
+                })
+                .execute(db_connection)?;
+        }
+
+        Ok(())
     }
 
     pub async fn fetch_for_person(
@@ -64,11 +188,14 @@ impl Avatar {
             avatars::table
                 .inner_join(appearances::table.inner_join(photos::table))
                 .select((
+                    appearances::id,
                     photos::file_name,
                     appearances::top,
                     appearances::left,
                     appearances::bottom,
                     appearances::right,
+                    appearances::landmarks,
+                    photos::orientation,
                 ))
                 .filter(avatars::person.eq(person_id))
                 .load::<Self>(&db_connection)
@@ -86,11 +213,14 @@ impl Avatar {
             appearances::table
                 .inner_join(photos::table)
                 .select((
+                    appearances::id,
                     photos::file_name,
                     appearances::top,
                     appearances::left,
                     appearances::bottom,
                     appearances::right,
+                    appearances::landmarks,
+                    photos::orientation,
                 ))
                 .filter(appearances::id.eq(appearance_id))
                 .load::<Self>(&db_connection)
@@ -98,31 +228,4 @@ impl Avatar {
         .await
         .map(|mut avatars| avatars.pop())
     }
-
-    pub fn image(
-        &self,
-        size: u32,
-        photo_dir: &std::path::Path,
-    ) -> Result<image::DynamicImage, ImageError> {
-        let image = crate::image_ext::ImageExt::new(std::io::BufReader::new(
-            std::fs::File::open(photo_dir.join(&self.file_name))
-                .map_err(ImageError::ImageOpenError)?,
-        ))
-        .map_err(ImageError::ImageLoadFailed)?;
-
-        let mut image = image.reorient();
-
-        let centre_x = (self.left + self.right) / 2;
-        let centre_y = (self.top + self.bottom) / 2;
-        let max_dim = std::cmp::max(self.right - self.left, self.bottom - self.top);
-
-        Ok(image
-            .crop(
-                std::cmp::max(0i32, centre_x - max_dim) as u32,
-                std::cmp::max(0i32, centre_y - max_dim) as u32,
-                (max_dim * 2) as u32,
-                (max_dim * 2) as u32,
-            )
-            .resize_exact(size, size, image::imageops::FilterType::Lanczos3))
-    }
 }
@@ -0,0 +1,185 @@
+use crate::db::schema::{appearance_exclusions, person_exclusions};
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+/// Records that an appearance isn't of a person. Meant to be called within a transaction.
+pub fn exclude_appearance(
+    db_connection: &diesel::SqliteConnection,
+    appearance_id: i32,
+    person_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::insert_or_ignore_into(appearance_exclusions::table)
+        .values((
+            appearance_exclusions::appearance.eq(appearance_id),
+            appearance_exclusions::person.eq(person_id),
+        ))
+        .execute(db_connection)
+        .map(|_| ())
+}
+
+/// Forgets that an appearance isn't of a person, as when it's explicitly assigned to them.
+pub fn include_appearance(
+    db_connection: &diesel::SqliteConnection,
+    appearance_id: i32,
+    person_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(
+        appearance_exclusions::table
+            .filter(appearance_exclusions::appearance.eq(appearance_id))
+            .filter(appearance_exclusions::person.eq(person_id)),
+    )
+    .execute(db_connection)
+    .map(|_| ())
+}
+
+/// Moves the exclusions of `src_id` onto `dst_id`, as when the two are merged. Meant to be called
+/// within a transaction, before `src_id` is deleted.
+pub fn merge(
+    db_connection: &diesel::SqliteConnection,
+    dst_id: i32,
+    src_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let appearance_ids = appearance_exclusions::table
+        .select(appearance_exclusions::appearance)
+        .filter(appearance_exclusions::person.eq(src_id))
+        .load::<i32>(db_connection)?;
+
+    for appearance_id in appearance_ids.into_iter() {
+        exclude_appearance(db_connection, appearance_id, dst_id)?;
+    }
+
+    diesel::delete(appearance_exclusions::table.filter(appearance_exclusions::person.eq(src_id)))
+        .execute(db_connection)?;
+
+    let person_ids = person_exclusions::table
+        .select((person_exclusions::person_a, person_exclusions::person_b))
+        .filter(
+            person_exclusions::person_a
+                .eq(src_id)
+                .or(person_exclusions::person_b.eq(src_id)),
+        )
+        .load::<(i32, i32)>(db_connection)?;
+
+    diesel::delete(
+        person_exclusions::table.filter(
+            person_exclusions::person_a
+                .eq(src_id)
+                .or(person_exclusions::person_b.eq(src_id)),
+        ),
+    )
+    .execute(db_connection)?;
+
+    for (person_a, person_b) in person_ids.into_iter() {
+        let other_id = if person_a == src_id { person_b } else { person_a };
+        if other_id != dst_id {
+            exclude_person(db_connection, dst_id, other_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records that two people are distinct. Meant to be called within a transaction.
+pub fn exclude_person(
+    db_connection: &diesel::SqliteConnection,
+    person_a_id: i32,
+    person_b_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::insert_or_ignore_into(person_exclusions::table)
+        .values((
+            person_exclusions::person_a.eq(std::cmp::min(person_a_id, person_b_id)),
+            person_exclusions::person_b.eq(std::cmp::max(person_a_id, person_b_id)),
+        ))
+        .execute(db_connection)
+        .map(|_| ())
+}
+
+/// Forgets that two people are distinct. Meant to be called within a transaction.
+pub fn include_person(
+    db_connection: &diesel::SqliteConnection,
+    person_a_id: i32,
+    person_b_id: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(
+        person_exclusions::table
+            .filter(person_exclusions::person_a.eq(std::cmp::min(person_a_id, person_b_id)))
+            .filter(person_exclusions::person_b.eq(std::cmp::max(person_a_id, person_b_id))),
+    )
+    .execute(db_connection)
+    .map(|_| ())
+}
+
+/// Records that two people are distinct, so they shouldn't be suggested as the same person.
+pub async fn insert_for_people(
+    db: &crate::db::System,
+    person_a_id: i32,
+    person_b_id: i32,
+) -> Result<(), crate::db::QueryError> {
+    db.run_query(move |db_connection| exclude_person(&db_connection, person_a_id, person_b_id))
+        .await
+}
+
+/// Fetches every appearance exclusion as `(appearance, person)` pairs.
+pub async fn fetch_all_for_appearances(
+    db: &crate::db::System,
+) -> Result<Vec<(i32, i32)>, crate::db::QueryError> {
+    db.run_query(move |db_connection| {
+        appearance_exclusions::table
+            .select((
+                appearance_exclusions::appearance,
+                appearance_exclusions::person,
+            ))
+            .load::<(i32, i32)>(&db_connection)
+    })
+    .await
+}
+
+/// Fetches every pair of people known to be distinct, lowest first.
+pub async fn fetch_all_for_people(
+    db: &crate::db::System,
+) -> Result<Vec<(i32, i32)>, crate::db::QueryError> {
+    db.run_query(move |db_connection| {
+        person_exclusions::table
+            .select((person_exclusions::person_a, person_exclusions::person_b))
+            .load::<(i32, i32)>(&db_connection)
+    })
+    .await
+}
+
+/// Fetches the people an appearance has been excluded from, along with the people that its
+/// current owner, if any, is known to be distinct from.
+pub async fn fetch_excluded_people(
+    db: &crate::db::System,
+    appearance_id: i32,
+    person_id: Option<i32>,
+) -> Result<Vec<i32>, crate::db::QueryError> {
+    db.run_query(move |db_connection| {
+        let mut excluded = appearance_exclusions::table
+            .select(appearance_exclusions::person)
+            .filter(appearance_exclusions::appearance.eq(appearance_id))
+            .load::<i32>(&db_connection)?;
+
+        if let Some(person_id) = person_id {
+            excluded.extend(
+                person_exclusions::table
+                    .select((person_exclusions::person_a, person_exclusions::person_b))
+                    .filter(
+                        person_exclusions::person_a
+                            .eq(person_id)
+                            .or(person_exclusions::person_b.eq(person_id)),
+                    )
+                    .load::<(i32, i32)>(&db_connection)?
+                    .into_iter()
+                    .map(|(person_a, person_b)| {
+                        if person_a == person_id {
+                            person_b
+                        } else {
+                            person_a
+                        }
+                    }),
+            );
+        }
+
+        Ok(excluded)
+    })
+    .await
+}
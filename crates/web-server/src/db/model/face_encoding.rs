@@ -1,20 +1,47 @@
-const ELEMENT_COUNT: usize = 128;
+pub const ELEMENT_COUNT: usize = 128;
 const RAW_ELEMENT_SIZE: usize = std::mem::size_of::<f64>();
 const RAW_SIZE: usize = ELEMENT_COUNT * RAW_ELEMENT_SIZE;
 
 #[derive(Clone, Debug, diesel::AsExpression, diesel::FromSqlRow, PartialEq)]
 #[sql_type = "diesel::sql_types::Binary"]
-pub struct FaceEncoding(dlib_face_recognition::FaceEncoding);
+pub struct FaceEncoding([f64; ELEMENT_COUNT]);
 
 impl FaceEncoding {
+    pub fn new(elements: [f64; ELEMENT_COUNT]) -> Self {
+        Self(elements)
+    }
+
     pub fn distance(&self, other: &FaceEncoding) -> f64 {
-        self.0.distance(&other.0)
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f64>()
+            .sqrt()
     }
-}
 
-impl From<dlib_face_recognition::FaceEncoding> for FaceEncoding {
-    fn from(encoding: dlib_face_recognition::FaceEncoding) -> Self {
-        Self(encoding)
+    /// The element-wise mean of some encodings, or `None` if there aren't any.
+    pub fn centroid<'a, I>(encodings: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a FaceEncoding>,
+    {
+        let mut sum = [0f64; ELEMENT_COUNT];
+        let mut count = 0usize;
+        for encoding in encodings.into_iter() {
+            for (total, element) in sum.iter_mut().zip(encoding.0.iter()) {
+                *total += element;
+            }
+            count += 1;
+        }
+
+        if count > 0 {
+            for total in sum.iter_mut() {
+                *total /= count as f64;
+            }
+            Some(Self(sum))
+        } else {
+            None
+        }
     }
 }
 
@@ -31,9 +58,8 @@ impl serde::Serialize for FaceEncoding {
     {
         use serde::ser::SerializeSeq;
 
-        let elements = self.0.to_elements();
-        let mut seq = serializer.serialize_seq(Some(elements.len()))?;
-        for element in elements.iter() {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for element in self.0.iter() {
             seq.serialize_element(element)?;
         }
         seq.end()
@@ -63,9 +89,7 @@ where
                 elements[e] = f64::from_le_bytes(element_bytes);
             }
 
-            Ok(FaceEncoding(dlib_face_recognition::FaceEncoding::new(
-                &elements,
-            )))
+            Ok(FaceEncoding(elements))
         } else {
             Err(Box::new(BadFaceEncodingLengthError(bytes.len(), RAW_SIZE))
                 as Box<dyn std::error::Error + Send + Sync>)
@@ -81,11 +105,9 @@ where
         &self,
         out: &mut diesel::serialize::Output<W, DB>,
     ) -> diesel::serialize::Result {
-        let elements = self.0.to_elements();
-
         let mut bytes = [0u8; RAW_SIZE];
         for (e, chunk) in bytes.chunks_mut(RAW_ELEMENT_SIZE).enumerate() {
-            chunk.copy_from_slice(&elements[e].to_le_bytes());
+            chunk.copy_from_slice(&self.0[e].to_le_bytes());
         }
 
         out.write_all(&bytes)
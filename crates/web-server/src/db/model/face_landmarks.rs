@@ -0,0 +1,83 @@
+const RAW_COORDINATE_SIZE: usize = std::mem::size_of::<i32>();
+const RAW_POINT_SIZE: usize = RAW_COORDINATE_SIZE * 2;
+
+/// The points marking out the features of a face, in the 68 point scheme, as `(x, y)` pairs in
+/// the reoriented photo. Empty where the recognizer doesn't locate them.
+#[derive(Clone, Debug, Default, diesel::AsExpression, diesel::FromSqlRow, PartialEq)]
+#[sql_type = "diesel::sql_types::Binary"]
+pub struct FaceLandmarks(Vec<(i32, i32)>);
+
+impl FaceLandmarks {
+    pub fn new(points: Vec<(i32, i32)>) -> Self {
+        Self(points)
+    }
+
+    pub fn into_inner(self) -> Vec<(i32, i32)> {
+        self.0
+    }
+}
+
+impl std::ops::Deref for FaceLandmarks {
+    type Target = [(i32, i32)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unexpected SQL FaceLandmarks length {0}, should be a multiple of {1}")]
+struct BadFaceLandmarksLengthError(usize, usize);
+
+impl<ST, DB> diesel::deserialize::FromSql<ST, DB> for FaceLandmarks
+where
+    DB: diesel::backend::Backend,
+    *const [u8]: diesel::deserialize::FromSql<ST, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        let slice_ptr = <*const [u8] as diesel::deserialize::FromSql<ST, DB>>::from_sql(bytes)?;
+        // We know that the pointer impl will never return null
+        let bytes = unsafe { &*slice_ptr };
+
+        if bytes.len() % RAW_POINT_SIZE == 0 {
+            let coordinate = |chunk: &[u8]| {
+                let mut coordinate_bytes = [0u8; RAW_COORDINATE_SIZE];
+                coordinate_bytes.copy_from_slice(chunk);
+                i32::from_le_bytes(coordinate_bytes)
+            };
+
+            Ok(FaceLandmarks(
+                bytes
+                    .chunks(RAW_POINT_SIZE)
+                    .map(|chunk| {
+                        let (x, y) = chunk.split_at(RAW_COORDINATE_SIZE);
+                        (coordinate(x), coordinate(y))
+                    })
+                    .collect(),
+            ))
+        } else {
+            Err(Box::new(BadFaceLandmarksLengthError(bytes.len(), RAW_POINT_SIZE))
+                as Box<dyn std::error::Error + Send + Sync>)
+        }
+    }
+}
+
+impl<DB> diesel::serialize::ToSql<diesel::sql_types::Binary, DB> for FaceLandmarks
+where
+    DB: diesel::backend::Backend,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, DB>,
+    ) -> diesel::serialize::Result {
+        let mut bytes = Vec::with_capacity(self.0.len() * RAW_POINT_SIZE);
+        for (x, y) in self.0.iter() {
+            bytes.extend_from_slice(&x.to_le_bytes());
+            bytes.extend_from_slice(&y.to_le_bytes());
+        }
+
+        out.write_all(&bytes)
+            .map(|_| diesel::serialize::IsNull::No)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+    }
+}
@@ -0,0 +1,552 @@
+use crate::db::schema::{job_changes, jobs};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+#[derive(Clone, Copy, Debug, diesel::AsExpression, diesel::FromSqlRow, Eq, PartialEq)]
+#[sql_type = "diesel::sql_types::Text"]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl From<photos_web_core::JobStatus> for JobStatus {
+    fn from(status: photos_web_core::JobStatus) -> Self {
+        match status {
+            photos_web_core::JobStatus::Pending => Self::Pending,
+            photos_web_core::JobStatus::Running => Self::Running,
+            photos_web_core::JobStatus::Completed => Self::Completed,
+            photos_web_core::JobStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+impl std::convert::Into<photos_web_core::JobStatus> for JobStatus {
+    fn into(self) -> photos_web_core::JobStatus {
+        match self {
+            Self::Pending => photos_web_core::JobStatus::Pending,
+            Self::Running => photos_web_core::JobStatus::Running,
+            Self::Completed => photos_web_core::JobStatus::Completed,
+            Self::Failed => photos_web_core::JobStatus::Failed,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unexpected SQL job status '{0}'")]
+struct BadJobStatusError(String);
+
+impl<DB> diesel::deserialize::FromSql<diesel::sql_types::Text, DB> for JobStatus
+where
+    DB: diesel::backend::Backend,
+    String: diesel::deserialize::FromSql<diesel::sql_types::Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        let status =
+            <String as diesel::deserialize::FromSql<diesel::sql_types::Text, DB>>::from_sql(
+                bytes,
+            )?;
+
+        match status.as_str() {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "completed" => Ok(Self::Completed),
+            "failed" => Ok(Self::Failed),
+            _ => Err(Box::new(BadJobStatusError(status))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
+
+impl<DB> diesel::serialize::ToSql<diesel::sql_types::Text, DB> for JobStatus
+where
+    DB: diesel::backend::Backend,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, DB>,
+    ) -> diesel::serialize::Result {
+        <str as diesel::serialize::ToSql<diesel::sql_types::Text, DB>>::to_sql(
+            self.as_str(),
+            out,
+        )
+    }
+}
+
+/// What a reprocessing job did to one of its photo's appearances.
+#[derive(Clone, Copy, Debug, diesel::AsExpression, diesel::FromSqlRow, Eq, PartialEq)]
+#[sql_type = "diesel::sql_types::Text"]
+pub enum AppearanceChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl AppearanceChange {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unexpected SQL appearance change '{0}'")]
+struct BadAppearanceChangeError(String);
+
+impl<DB> diesel::deserialize::FromSql<diesel::sql_types::Text, DB> for AppearanceChange
+where
+    DB: diesel::backend::Backend,
+    String: diesel::deserialize::FromSql<diesel::sql_types::Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
+        let change =
+            <String as diesel::deserialize::FromSql<diesel::sql_types::Text, DB>>::from_sql(
+                bytes,
+            )?;
+
+        match change.as_str() {
+            "added" => Ok(Self::Added),
+            "removed" => Ok(Self::Removed),
+            "changed" => Ok(Self::Changed),
+            _ => Err(Box::new(BadAppearanceChangeError(change))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
+
+impl<DB> diesel::serialize::ToSql<diesel::sql_types::Text, DB> for AppearanceChange
+where
+    DB: diesel::backend::Backend,
+{
+    fn to_sql<W: std::io::Write>(
+        &self,
+        out: &mut diesel::serialize::Output<W, DB>,
+    ) -> diesel::serialize::Result {
+        <str as diesel::serialize::ToSql<diesel::sql_types::Text, DB>>::to_sql(
+            self.as_str(),
+            out,
+        )
+    }
+}
+
+#[derive(diesel::Insertable)]
+#[table_name = "jobs"]
+struct NewJob {
+    pub photo: i32,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub created_datetime: chrono::NaiveDateTime,
+    pub updated_datetime: chrono::NaiveDateTime,
+    pub reprocess: bool,
+    pub min_face_pixels: Option<i32>,
+    pub min_face_fraction: Option<f64>,
+    pub upsample: Option<i32>,
+    pub max_image_size: Option<i32>,
+}
+
+#[derive(diesel::Queryable)]
+pub struct Job {
+    pub photo: i32,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub created_datetime: chrono::NaiveDateTime,
+    pub updated_datetime: chrono::NaiveDateTime,
+    /// Whether the job runs detection over its photo again, rather than for the first time.
+    pub reprocess: bool,
+    /// The detection settings that the job overrides the server's own with, if any.
+    pub min_face_pixels: Option<i32>,
+    pub min_face_fraction: Option<f64>,
+    pub upsample: Option<i32>,
+    pub max_image_size: Option<i32>,
+}
+
+impl Job {
+    /// Whether a photo has been processed, not counting any reprocessing.
+    pub fn has_completed(
+        db_connection: &diesel::SqliteConnection,
+        photo_id: i32,
+    ) -> Result<bool, diesel::result::Error> {
+        use diesel::dsl::{exists, select};
+
+        select(exists(
+            jobs::table
+                .filter(jobs::photo.eq(photo_id))
+                .filter(jobs::reprocess.eq(false))
+                .filter(jobs::status.eq(JobStatus::Completed)),
+        ))
+        .get_result(db_connection)
+    }
+
+    /// Records a pending job to process a photo, yielding its id.
+    ///
+    /// Meant to be called while holding the jobs insertion guard.
+    pub fn insert(
+        db_connection: &diesel::SqliteConnection,
+        photo_id: i32,
+    ) -> Result<i32, diesel::result::Error> {
+        let now = chrono::Utc::now().naive_utc();
+
+        diesel::insert_into(jobs::table)
+            .values(&NewJob {
+                photo: photo_id,
+                status: JobStatus::Pending,
+                attempts: 0,
+                created_datetime: now,
+                updated_datetime: now,
+                reprocess: false,
+                min_face_pixels: None,
+                min_face_fraction: None,
+                upsample: None,
+                max_image_size: None,
+            })
+            .execute(db_connection)?;
+
+        Ok(*jobs::table
+            .select(jobs::id)
+            .order(jobs::id.desc())
+            .limit(1)
+            .load(db_connection)?
+            .get(0)
+            .unwrap())
+    }
+
+    /// Records a pending job to run detection over each of the given photos again, overriding
+    /// the server's detection settings with those given, and yields their ids in the same order.
+    pub async fn insert_reprocessing(
+        db: &crate::db::System,
+        photo_ids: Vec<i32>,
+        min_face_pixels: Option<u32>,
+        min_face_fraction: Option<f64>,
+        upsample: Option<u32>,
+        max_image_size: Option<u32>,
+    ) -> Result<Vec<i32>, crate::db::QueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+
+                let _guard = db.jobs_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let now = chrono::Utc::now().naive_utc();
+
+                    let mut job_ids = Vec::with_capacity(photo_ids.len());
+                    for photo_id in photo_ids.into_iter() {
+                        diesel::insert_into(jobs::table)
+                            .values(&NewJob {
+                                photo: photo_id,
+                                status: JobStatus::Pending,
+                                attempts: 0,
+                                created_datetime: now,
+                                updated_datetime: now,
+                                reprocess: true,
+                                min_face_pixels: min_face_pixels.map(|pixels| pixels as i32),
+                                min_face_fraction,
+                                upsample: upsample.map(|upsample| upsample as i32),
+                                max_image_size: max_image_size.map(|size| size as i32),
+                            })
+                            .execute(&db_connection)?;
+
+                        job_ids.push(
+                            *jobs::table
+                                .select(jobs::id)
+                                .order(jobs::id.desc())
+                                .limit(1)
+                                .load(&db_connection)?
+                                .get(0)
+                                .unwrap(),
+                        );
+                    }
+
+                    Ok(job_ids)
+                })
+            }
+        })
+        .await
+    }
+
+    pub async fn fetch(
+        db: &crate::db::System,
+        job_id: i32,
+    ) -> Result<Option<Self>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::jobs::dsl::*;
+            jobs.select((
+                photo,
+                status,
+                attempts,
+                error,
+                created_datetime,
+                updated_datetime,
+                reprocess,
+                min_face_pixels,
+                min_face_fraction,
+                upsample,
+                max_image_size,
+            ))
+            .filter(id.eq(job_id))
+            .load::<Self>(&db_connection)
+        })
+        .await
+        .map(|mut jobs| jobs.pop())
+    }
+
+    pub async fn fetch_all(
+        db: &crate::db::System,
+        job_status: Option<JobStatus>,
+    ) -> Result<Vec<(i32, Self)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::jobs::dsl::*;
+            let query = jobs
+                .select((
+                    id,
+                    (
+                        photo,
+                        status,
+                        attempts,
+                        error,
+                        created_datetime,
+                        updated_datetime,
+                        reprocess,
+                        min_face_pixels,
+                        min_face_fraction,
+                        upsample,
+                        max_image_size,
+                    ),
+                ))
+                .order_by(id.desc())
+                .into_boxed();
+
+            match job_status {
+                Some(job_status) => query.filter(status.eq(job_status)),
+                None => query,
+            }
+            .load::<(i32, Self)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Marks the oldest pending job as running and returns it, counting the attempt. Pending jobs
+    /// for photos in the trash are dropped, their files having been moved, and the photo is
+    /// queued again should it be restored.
+    pub async fn claim_next(
+        db: &crate::db::System,
+    ) -> Result<Option<(i32, Self)>, crate::db::QueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::db::schema::jobs::dsl::*;
+                use crate::db::schema::trashed_photos;
+                use crate::diesel::Connection;
+
+                let _guard = db.jobs_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    diesel::update(
+                        jobs.filter(status.eq(JobStatus::Pending)).filter(
+                            photo.eq_any(trashed_photos::table.select(trashed_photos::photo)),
+                        ),
+                    )
+                    .set((
+                        status.eq(JobStatus::Failed),
+                        error.eq("The photo was put in the trash"),
+                        updated_datetime.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .execute(&db_connection)?;
+
+                    let job = jobs
+                        .select((
+                            id,
+                            (
+                                photo,
+                                status,
+                                attempts,
+                                error,
+                                created_datetime,
+                                updated_datetime,
+                                reprocess,
+                                min_face_pixels,
+                                min_face_fraction,
+                                upsample,
+                                max_image_size,
+                            ),
+                        ))
+                        .filter(status.eq(JobStatus::Pending))
+                        .order_by(id)
+                        .limit(1)
+                        .load::<(i32, Self)>(&db_connection)?
+                        .pop();
+
+                    Ok(match job {
+                        Some((job_id, mut job)) => {
+                            job.status = JobStatus::Running;
+                            job.attempts += 1;
+                            job.updated_datetime = chrono::Utc::now().naive_utc();
+
+                            diesel::update(jobs.filter(id.eq(job_id)))
+                                .set((
+                                    status.eq(job.status),
+                                    attempts.eq(job.attempts),
+                                    updated_datetime.eq(job.updated_datetime),
+                                ))
+                                .execute(&db_connection)?;
+
+                            Some((job_id, job))
+                        }
+                        None => None,
+                    })
+                })
+            }
+        })
+        .await
+    }
+
+    /// Records a job as done, along with what it did to its photo's appearances if it was
+    /// reprocessing it.
+    pub async fn complete(
+        db: &crate::db::System,
+        job_id: i32,
+        changes: Vec<(i32, AppearanceChange)>,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        db.run_query(move |db_connection| {
+            use crate::diesel::Connection;
+
+            db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                let updated = diesel::update(jobs::table.filter(jobs::id.eq(job_id)))
+                    .set((
+                        jobs::status.eq(JobStatus::Completed),
+                        jobs::error.eq(None::<String>),
+                        jobs::updated_datetime.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .execute(&db_connection)?;
+
+                if updated == 0 {
+                    return Ok(0);
+                }
+
+                for (appearance_id, change) in changes.into_iter() {
+                    diesel::insert_into(job_changes::table)
+                        .values((
+                            job_changes::job.eq(job_id),
+                            job_changes::appearance.eq(appearance_id),
+                            job_changes::change.eq(change),
+                        ))
+                        .execute(&db_connection)?;
+                }
+
+                Ok(updated)
+            })
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+
+    /// Fetches what a reprocessing job did to its photo's appearances, in the order it did it.
+    pub async fn fetch_changes(
+        db: &crate::db::System,
+        job_id: i32,
+    ) -> Result<Vec<(i32, AppearanceChange)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            job_changes::table
+                .select((job_changes::appearance, job_changes::change))
+                .filter(job_changes::job.eq(job_id))
+                .order_by(job_changes::id)
+                .load::<(i32, AppearanceChange)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Records a failed attempt, leaving the job pending if it should be retried.
+    pub async fn fail(
+        db: &crate::db::System,
+        job_id: i32,
+        reason: String,
+        retry: bool,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        Self::update_status(
+            db,
+            job_id,
+            if retry {
+                JobStatus::Pending
+            } else {
+                JobStatus::Failed
+            },
+            Some(reason),
+        )
+        .await
+    }
+
+    /// Returns jobs left running by a previous instance of the server to the queue.
+    pub async fn requeue_running(db: &crate::db::System) -> Result<usize, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::jobs::dsl::*;
+            diesel::update(jobs.filter(status.eq(JobStatus::Running)))
+                .set((
+                    status.eq(JobStatus::Pending),
+                    updated_datetime.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(&db_connection)
+        })
+        .await
+    }
+
+    async fn update_status(
+        db: &crate::db::System,
+        job_id: i32,
+        job_status: JobStatus,
+        reason: Option<String>,
+    ) -> Result<(), crate::db::UpdateQueryError> {
+        db.run_query(move |db_connection| {
+            use crate::db::schema::jobs::dsl::*;
+            diesel::update(jobs.filter(id.eq(job_id)))
+                .set((
+                    status.eq(job_status),
+                    error.eq(reason),
+                    updated_datetime.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(&db_connection)
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|result| match result {
+            1 => Ok(()),
+            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
+            _ => unreachable!(),
+        })
+    }
+}
+
+impl std::convert::Into<photos_web_core::Job> for Job {
+    fn into(self) -> photos_web_core::Job {
+        photos_web_core::Job {
+            photo: self.photo,
+            status: self.status.into(),
+            attempts: self.attempts,
+            error: self.error,
+            created_datetime: self.created_datetime,
+            updated_datetime: self.updated_datetime,
+            reprocess: self.reprocess,
+            changes: None,
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::db::schema::{merge_appearances, merge_exclusions, merges};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+/// A person merged into another, with the details and avatar they had beforehand and the
+/// appearances that were moved over.
+pub struct Merge {
+    pub dst: i32,
+    pub src: i32,
+    pub person: crate::db::model::Person,
+    pub avatar: Option<i32>,
+    pub appearances: Vec<i32>,
+    pub merged_datetime: chrono::NaiveDateTime,
+    pub undone_datetime: Option<chrono::NaiveDateTime>,
+}
+
+impl std::convert::Into<photos_web_core::Merge> for Merge {
+    fn into(self) -> photos_web_core::Merge {
+        photos_web_core::Merge {
+            dst: self.dst,
+            src: self.src,
+            person: self.person.into(),
+            avatar: self.avatar,
+            appearances: self.appearances,
+            merged_datetime: self.merged_datetime,
+            undone_datetime: self.undone_datetime,
+        }
+    }
+}
+
+/// What undoing a merge put back.
+pub struct Undone {
+    pub dst: i32,
+    /// The restored person. This is the id they had before, unless it has since been reused.
+    pub person: i32,
+    /// The appearances moved back to the restored person. Any that have been reassigned or
+    /// deleted since the merge are left as they are.
+    pub appearances: Vec<i32>,
+    /// The appearances that the restored person is known not to be, along with whether the
+    /// person they were merged into is known not to be them too.
+    pub excluded_appearances: Vec<(i32, bool)>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UndoError {
+    #[error(transparent)]
+    QueryError(#[from] crate::db::QueryError),
+    #[error("No such merge")]
+    NoSuchMerge,
+    #[error("Merge has already been undone")]
+    AlreadyUndone,
+    #[error("The person merged into has since been merged into someone else")]
+    DstMerged,
+}
+
+impl Merge {
+    /// Records that `src_id` is about to be merged into `dst_id`, along with everything that's
+    /// needed to undo it. Yields `None` if `src_id` doesn't exist.
+    ///
+    /// Meant to be called within the merge's transaction, before anything has been changed.
+    pub fn record(
+        db_connection: &diesel::SqliteConnection,
+        dst_id: i32,
+        src_id: i32,
+    ) -> Result<Option<i32>, diesel::result::Error> {
+        use crate::db::schema::{
+            appearance_exclusions, appearances, avatars, people, person_exclusions,
+        };
+        use diesel::{BoolExpressionMethods, OptionalExtension};
+
+        let person = match people::table
+            .select((
+                people::first_name,
+                people::middle_names,
+                people::surname,
+                people::display_name,
+                people::dob,
+            ))
+            .filter(people::id.eq(src_id))
+            .first::<crate::db::model::Person>(db_connection)
+            .optional()?
+        {
+            Some(person) => person,
+            None => return Ok(None),
+        };
+
+        let avatar = avatars::table
+            .select(avatars::appearance)
+            .filter(avatars::person.eq(src_id))
+            .first::<i32>(db_connection)
+            .optional()?;
+
+        diesel::insert_into(merges::table)
+            .values((
+                merges::dst.eq(dst_id),
+                merges::src.eq(src_id),
+                merges::first_name.eq(person.first_name),
+                merges::middle_names.eq(person.middle_names),
+                merges::surname.eq(person.surname),
+                merges::display_name.eq(person.display_name),
+                merges::dob.eq(person.dob),
+                merges::avatar.eq(avatar),
+                merges::merged_datetime.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(db_connection)?;
+
+        // Nothing else can insert a merge until the transaction is over.
+        let merge_id = merges::table
+            .select(merges::id)
+            .order(merges::id.desc())
+            .first::<i32>(db_connection)?;
+
+        let appearance_ids = appearances::table
+            .select(appearances::id)
+            .filter(appearances::person.eq(src_id))
+            .load::<i32>(db_connection)?;
+
+        for appearance_id in appearance_ids.into_iter() {
+            diesel::insert_into(merge_appearances::table)
+                .values((
+                    merge_appearances::merge.eq(merge_id),
+                    merge_appearances::appearance.eq(appearance_id),
+                ))
+                .execute(db_connection)?;
+        }
+
+        let excluded_appearance_ids = appearance_exclusions::table
+            .select(appearance_exclusions::appearance)
+            .filter(appearance_exclusions::person.eq(src_id))
+            .load::<i32>(db_connection)?;
+
+        let shared_appearance_ids = appearance_exclusions::table
+            .select(appearance_exclusions::appearance)
+            .filter(appearance_exclusions::person.eq(dst_id))
+            .filter(appearance_exclusions::appearance.eq_any(&excluded_appearance_ids))
+            .load::<i32>(db_connection)?;
+
+        for appearance_id in excluded_appearance_ids.into_iter() {
+            diesel::insert_into(merge_exclusions::table)
+                .values((
+                    merge_exclusions::merge.eq(merge_id),
+                    merge_exclusions::appearance.eq(appearance_id),
+                    merge_exclusions::shared.eq(shared_appearance_ids.contains(&appearance_id)),
+                ))
+                .execute(db_connection)?;
+        }
+
+        let distinct_pairs = person_exclusions::table
+            .select((person_exclusions::person_a, person_exclusions::person_b))
+            .filter(
+                person_exclusions::person_a
+                    .eq_any(&[src_id, dst_id])
+                    .or(person_exclusions::person_b.eq_any(&[src_id, dst_id])),
+            )
+            .load::<(i32, i32)>(db_connection)?;
+
+        let distinct_from = |person_id: i32| {
+            distinct_pairs
+                .iter()
+                .filter_map(move |(person_a, person_b)| {
+                    if *person_a == person_id {
+                        Some(*person_b)
+                    } else if *person_b == person_id {
+                        Some(*person_a)
+                    } else {
+                        None
+                    }
+                })
+        };
+
+        let shared_person_ids: Vec<i32> = distinct_from(dst_id).collect();
+
+        for person_id in distinct_from(src_id) {
+            diesel::insert_into(merge_exclusions::table)
+                .values((
+                    merge_exclusions::merge.eq(merge_id),
+                    merge_exclusions::person.eq(person_id),
+                    merge_exclusions::shared.eq(shared_person_ids.contains(&person_id)),
+                ))
+                .execute(db_connection)?;
+        }
+
+        Ok(Some(merge_id))
+    }
+
+    /// Fetches the merges into a person, most recent first.
+    pub async fn fetch_for_person(
+        db: &crate::db::System,
+        person_id: i32,
+    ) -> Result<Vec<(i32, Self)>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            let found = merges::table
+                .select((
+                    merges::id,
+                    merges::src,
+                    (
+                        merges::first_name,
+                        merges::middle_names,
+                        merges::surname,
+                        merges::display_name,
+                        merges::dob,
+                    ),
+                    merges::avatar,
+                    merges::merged_datetime,
+                    merges::undone_datetime,
+                ))
+                .filter(merges::dst.eq(person_id))
+                .order(merges::id.desc())
+                .load::<(
+                    i32,
+                    i32,
+                    crate::db::model::Person,
+                    Option<i32>,
+                    chrono::NaiveDateTime,
+                    Option<chrono::NaiveDateTime>,
+                )>(&db_connection)?;
+
+            let merge_ids: Vec<i32> = found.iter().map(|(merge_id, ..)| *merge_id).collect();
+
+            let mut appearances = std::collections::HashMap::<i32, Vec<i32>>::new();
+            for (merge_id, appearance_id) in merge_appearances::table
+                .select((merge_appearances::merge, merge_appearances::appearance))
+                .filter(merge_appearances::merge.eq_any(&merge_ids))
+                .order(merge_appearances::appearance)
+                .load::<(i32, i32)>(&db_connection)?
+                .into_iter()
+            {
+                appearances
+                    .entry(merge_id)
+                    .or_insert_with(Vec::new)
+                    .push(appearance_id);
+            }
+
+            Ok(found
+                .into_iter()
+                .map(
+                    |(merge_id, src, person, avatar, merged_datetime, undone_datetime)| {
+                        (
+                            merge_id,
+                            Self {
+                                dst: person_id,
+                                src,
+                                person,
+                                avatar,
+                                appearances: appearances.remove(&merge_id).unwrap_or_default(),
+                                merged_datetime,
+                                undone_datetime,
+                            },
+                        )
+                    },
+                )
+                .collect())
+        })
+        .await
+    }
+
+    /// Restores a merged person along with their avatar, the appearances that were moved over
+    /// and still belong to the person they were merged into, and whatever they were known not
+    /// to be. Appearances matched to the person merged into since aren't moved.
+    pub async fn undo(db: &crate::db::System, merge_id: i32) -> Result<Undone, UndoError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::db::schema::{appearances, avatars, people};
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
+                let _people_guard = db.people_insertion_guard().lock();
+                let _avatars_guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let (dst_id, src_id, person, avatar, undone_datetime) = match merges::table
+                        .select((
+                            merges::dst,
+                            merges::src,
+                            (
+                                merges::first_name,
+                                merges::middle_names,
+                                merges::surname,
+                                merges::display_name,
+                                merges::dob,
+                            ),
+                            merges::avatar,
+                            merges::undone_datetime,
+                        ))
+                        .filter(merges::id.eq(merge_id))
+                        .first::<(
+                            i32,
+                            i32,
+                            crate::db::model::Person,
+                            Option<i32>,
+                            Option<chrono::NaiveDateTime>,
+                        )>(&db_connection)
+                        .optional()?
+                    {
+                        Some(merge) => merge,
+                        None => return Ok(Err(UndoError::NoSuchMerge)),
+                    };
+
+                    if undone_datetime.is_some() {
+                        return Ok(Err(UndoError::AlreadyUndone));
+                    }
+
+                    let person_exists = |person_id: i32| {
+                        people::table
+                            .select(people::id)
+                            .filter(people::id.eq(person_id))
+                            .first::<i32>(&db_connection)
+                            .optional()
+                            .map(|found| found.is_some())
+                    };
+
+                    if !person_exists(dst_id)? {
+                        return Ok(Err(UndoError::DstMerged));
+                    }
+
+                    let person_id = if person_exists(src_id)? {
+                        diesel::insert_into(people::table)
+                            .values(&person)
+                            .execute(&db_connection)?;
+
+                        let person_id = people::table
+                            .select(people::id)
+                            .order(people::id.desc())
+                            .first::<i32>(&db_connection)?;
+
+                        // Earlier merges into the person being restored now need to find them
+                        // under their new id.
+                        diesel::update(
+                            merges::table
+                                .filter(merges::dst.eq(src_id))
+                                .filter(merges::id.lt(merge_id))
+                                .filter(merges::undone_datetime.is_null()),
+                        )
+                        .set(merges::dst.eq(person_id))
+                        .execute(&db_connection)?;
+
+                        person_id
+                    } else {
+                        diesel::insert_into(people::table)
+                            .values((people::id.eq(src_id), &person))
+                            .execute(&db_connection)?;
+
+                        src_id
+                    };
+
+                    let appearance_ids = appearances::table
+                        .select(appearances::id)
+                        .filter(
+                            appearances::id.eq_any(
+                                merge_appearances::table
+                                    .select(merge_appearances::appearance)
+                                    .filter(merge_appearances::merge.eq(merge_id)),
+                            ),
+                        )
+                        .filter(appearances::person.eq(dst_id))
+                        .load::<i32>(&db_connection)?;
+
+                    diesel::update(
+                        appearances::table.filter(appearances::id.eq_any(&appearance_ids)),
+                    )
+                    .set(appearances::person.eq(person_id))
+                    .execute(&db_connection)?;
+
+                    let mut excluded_appearances = Vec::new();
+                    for (appearance_id, excluded_person_id, shared) in merge_exclusions::table
+                        .select((
+                            merge_exclusions::appearance,
+                            merge_exclusions::person,
+                            merge_exclusions::shared,
+                        ))
+                        .filter(merge_exclusions::merge.eq(merge_id))
+                        .load::<(Option<i32>, Option<i32>, bool)>(&db_connection)?
+                        .into_iter()
+                    {
+                        if let Some(appearance_id) = appearance_id {
+                            let appearance_exists = appearances::table
+                                .select(appearances::id)
+                                .filter(appearances::id.eq(appearance_id))
+                                .first::<i32>(&db_connection)
+                                .optional()?
+                                .is_some();
+
+                            if appearance_exists {
+                                crate::db::model::exclusion::exclude_appearance(
+                                    &db_connection,
+                                    appearance_id,
+                                    person_id,
+                                )?;
+
+                                if !shared {
+                                    crate::db::model::exclusion::include_appearance(
+                                        &db_connection,
+                                        appearance_id,
+                                        dst_id,
+                                    )?;
+                                }
+
+                                excluded_appearances.push((appearance_id, shared));
+                            }
+                        }
+
+                        if let Some(excluded_person_id) = excluded_person_id {
+                            if person_exists(excluded_person_id)? {
+                                crate::db::model::exclusion::exclude_person(
+                                    &db_connection,
+                                    person_id,
+                                    excluded_person_id,
+                                )?;
+
+                                if !shared && excluded_person_id != dst_id {
+                                    crate::db::model::exclusion::include_person(
+                                        &db_connection,
+                                        dst_id,
+                                        excluded_person_id,
+                                    )?;
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(avatar) = avatar.filter(|avatar| appearance_ids.contains(avatar)) {
+                        diesel::insert_into(avatars::table)
+                            .values((avatars::person.eq(person_id), avatars::appearance.eq(avatar)))
+                            .execute(&db_connection)?;
+                    }
+
+                    crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                    crate::db::model::Avatar::repair(&db_connection, dst_id)?;
+
+                    diesel::update(merges::table.filter(merges::id.eq(merge_id)))
+                        .set(merges::undone_datetime.eq(chrono::Utc::now().naive_utc()))
+                        .execute(&db_connection)?;
+
+                    Ok(Ok(Undone {
+                        dst: dst_id,
+                        person: person_id,
+                        appearances: appearance_ids,
+                        excluded_appearances,
+                    }))
+                })
+            }
+        })
+        .await?
+    }
+}
@@ -3,7 +3,11 @@ use diesel::sql_types::{Nullable, Timestamp};
 pub mod appearance;
 pub mod avatar;
 pub mod digest;
+pub mod exclusion;
 pub mod face_encoding;
+pub mod face_landmarks;
+pub mod job;
+pub mod merge;
 pub mod person;
 pub mod photo;
 
@@ -11,6 +15,9 @@ pub use appearance::Appearance;
 pub use avatar::Avatar;
 pub use digest::Digest;
 pub use face_encoding::FaceEncoding;
+pub use face_landmarks::FaceLandmarks;
+pub use job::Job;
+pub use merge::Merge;
 pub use person::Person;
 pub use photo::Photo;
 
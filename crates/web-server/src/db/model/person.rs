@@ -1,7 +1,17 @@
 use crate::db::schema::people;
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 
-#[derive(diesel::AsChangeset, diesel::Queryable)]
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error(transparent)]
+    QueryError(#[from] crate::db::QueryError),
+    #[error("No such person")]
+    NoSuchPerson,
+    #[error("A person can't be merged into themselves")]
+    SamePerson,
+}
+
+#[derive(diesel::AsChangeset, diesel::Insertable, diesel::Queryable)]
 #[table_name = "people"]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct Person {
@@ -12,39 +22,92 @@ pub struct Person {
     pub dob: Option<chrono::NaiveDate>,
 }
 
-#[derive(diesel::Insertable)]
-#[table_name = "people"]
-pub struct NewPerson {
-    pub first_name: String,
-    pub middle_names: String,
-    pub surname: String,
-}
-
 impl Person {
-    pub async fn insert(db: &crate::db::System) -> Result<i32, crate::db::QueryError> {
+    /// Creates a person from a group of appearances that currently belong to `owner_id`, or that
+    /// are unassigned if it's `None`. All of the appearances become confirmed references for the
+    /// new person, and the best of them is used as their avatar. The previous owner's avatar is
+    /// moved if it was one of the appearances taken, and the appearances are remembered as not
+    /// being of them.
+    ///
+    /// Fails with `NoSuchRecord` if any of the appearances don't exist or don't belong to
+    /// `owner_id`.
+    pub async fn insert_from_appearances(
+        self,
+        db: &crate::db::System,
+        owner_id: Option<i32>,
+        appearance_ids: Vec<i32>,
+    ) -> Result<i32, crate::db::UpdateQueryError> {
         db.run_query({
             let db = db.clone();
             move |db_connection| {
-                let _guard = db.people_insertion_guard().lock();
-
-                diesel::insert_into(people::table)
-                    .values(&NewPerson {
-                        first_name: String::from("Person"),
-                        middle_names: String::from("'Photo Bomber'"),
-                        surname: String::from("McPerson"),
-                    })
-                    .execute(&db_connection)?;
+                use crate::db::schema::appearances;
+                use crate::diesel::Connection;
+
+                let _people_guard = db.people_insertion_guard().lock();
+                let _avatars_guard = db.avatars_insertion_guard().lock();
 
-                Ok(*people::table
-                    .select(people::id)
-                    .order(people::id.desc())
-                    .limit(1)
-                    .load(&db_connection)?
-                    .get(0)
-                    .unwrap())
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let query = appearances::table
+                        .select(appearances::id)
+                        .filter(appearances::id.eq_any(&appearance_ids))
+                        .into_boxed();
+                    let found = match owner_id {
+                        Some(owner_id) => query.filter(appearances::person.eq(owner_id)),
+                        None => query.filter(appearances::person.is_null()),
+                    }
+                    .load::<i32>(&db_connection)?;
+
+                    if appearance_ids.is_empty() || found.len() != appearance_ids.len() {
+                        return Err(diesel::result::Error::RollbackTransaction);
+                    }
+
+                    diesel::insert_into(people::table)
+                        .values(&self)
+                        .execute(&db_connection)?;
+
+                    let person_id = *people::table
+                        .select(people::id)
+                        .order(people::id.desc())
+                        .limit(1)
+                        .load(&db_connection)?
+                        .get(0)
+                        .unwrap();
+
+                    diesel::update(appearances::table.filter(appearances::id.eq_any(&found)))
+                        .set((
+                            appearances::person.eq(person_id),
+                            appearances::reference.eq(true),
+                            appearances::match_distance.eq(None::<f64>),
+                            appearances::match_appearance.eq(None::<i32>),
+                            appearances::confirmed.eq(true),
+                        ))
+                        .execute(&db_connection)?;
+
+                    crate::db::model::Avatar::repair(&db_connection, person_id)?;
+
+                    if let Some(owner_id) = owner_id {
+                        for appearance_id in found.iter() {
+                            crate::db::model::exclusion::exclude_appearance(
+                                &db_connection,
+                                *appearance_id,
+                                owner_id,
+                            )?;
+                        }
+
+                        crate::db::model::Avatar::repair(&db_connection, owner_id)?;
+                    }
+
+                    Ok(person_id)
+                })
             }
         })
         .await
+        .map_err(|err| match err {
+            crate::db::QueryError::QueryError(diesel::result::Error::RollbackTransaction) => {
+                crate::db::UpdateQueryError::NoSuchRecord
+            }
+            err => crate::db::UpdateQueryError::QueryError(err),
+        })
     }
 
     pub async fn fetch(
@@ -97,38 +160,57 @@ impl Person {
         })
     }
 
+    /// Merges `src_id` into `dst_id`, moving over their appearances and whatever they're known
+    /// not to be before deleting them, and giving `dst_id` one of them as an avatar if they had
+    /// none. The merge is recorded so that it can be undone, and its id is yielded.
     pub async fn merge(
         db: &crate::db::System,
         dst_id: i32,
         src_id: i32,
-    ) -> Result<(), crate::db::UpdateQueryError> {
-        db.run_query(move |db_connection| {
-            use crate::diesel::Connection;
+    ) -> Result<i32, MergeError> {
+        if dst_id == src_id {
+            return Err(MergeError::SamePerson);
+        }
 
-            db_connection.transaction::<_, diesel::result::Error, _>(|| {
-                diesel::delete(crate::db::schema::avatars::table)
-                    .filter(crate::db::schema::avatars::person.eq(src_id))
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+
+                let _avatars_guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let merge_id =
+                        match crate::db::model::Merge::record(&db_connection, dst_id, src_id)? {
+                            Some(merge_id) => merge_id,
+                            None => return Ok(None),
+                        };
+
+                    diesel::delete(crate::db::schema::avatars::table)
+                        .filter(crate::db::schema::avatars::person.eq(src_id))
+                        .execute(&db_connection)?;
+
+                    diesel::update(
+                        crate::db::schema::appearances::table
+                            .filter(crate::db::schema::appearances::person.eq(src_id)),
+                    )
+                    .set(crate::db::schema::appearances::person.eq(dst_id))
                     .execute(&db_connection)?;
 
-                diesel::update(
-                    crate::db::schema::appearances::table
-                        .filter(crate::db::schema::appearances::person.eq(src_id)),
-                )
-                .set(crate::db::schema::appearances::person.eq(dst_id))
-                .execute(&db_connection)?;
-
-                diesel::delete(people::table)
-                    .filter(people::id.eq(src_id))
-                    .execute(&db_connection)
-            })
-        })
-        .await
-        .map_err(crate::db::UpdateQueryError::QueryError)
-        .and_then(|result| match result {
-            1 => Ok(()),
-            0 => Err(crate::db::UpdateQueryError::NoSuchRecord),
-            _ => unreachable!(),
+                    crate::db::model::exclusion::merge(&db_connection, dst_id, src_id)?;
+
+                    diesel::delete(people::table)
+                        .filter(people::id.eq(src_id))
+                        .execute(&db_connection)?;
+
+                    crate::db::model::Avatar::repair(&db_connection, dst_id)?;
+
+                    Ok(Some(merge_id))
+                })
+            }
         })
+        .await?
+        .ok_or(MergeError::NoSuchPerson)
     }
 }
 
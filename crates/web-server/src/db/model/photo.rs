@@ -1,4 +1,4 @@
-use crate::db::schema::{appearances, photos};
+use crate::db::schema::{appearances, photos, trashed_photos};
 use diesel::{
     sql_types::{Nullable, Timestamp},
     ExpressionMethods, QueryDsl, RunQueryDsl,
@@ -6,6 +6,19 @@ use diesel::{
 
 sql_function!(fn ifnull(x: Nullable<Timestamp>, y: Timestamp) -> Timestamp);
 
+/// How far apart the clocks of any two time zones can be.
+const MAX_CLOCK_DIFFERENCE_HOURS: i64 = 28;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedateError {
+    #[error(transparent)]
+    QueryError(#[from] crate::db::QueryError),
+    #[error("No such photo")]
+    NoSuchPhoto,
+    #[error("Failed to move the photo's files into its new month")]
+    MoveFailed(#[source] std::io::Error),
+}
+
 #[derive(diesel::Insertable)]
 #[table_name = "photos"]
 struct NewPhoto {
@@ -17,6 +30,7 @@ struct NewPhoto {
     pub thumb_height: i32,
     pub original_datetime: Option<chrono::NaiveDateTime>,
     pub upload_datetime: chrono::NaiveDateTime,
+    pub original_offset: Option<i32>,
 }
 
 #[derive(diesel::Queryable)]
@@ -29,9 +43,33 @@ pub struct Photo {
     pub thumb_height: i32,
     pub original_datetime: Option<chrono::NaiveDateTime>,
     pub upload_datetime: chrono::NaiveDateTime,
+    /// Seconds ahead of UTC, where the photo's camera recorded it.
+    pub original_offset: Option<i32>,
+    /// The EXIF orientation the photo has been turned or flipped into since it was uploaded, if it
+    /// has been, which stands in for that of its file.
+    pub orientation: Option<i32>,
+}
+
+/// What deleting a photo removed.
+pub struct Deleted {
+    /// The photo's file, which is left for the caller to remove along with its thumbnail.
+    pub file_name: String,
+    pub appearances: Vec<i32>,
+    /// The people deleted for having no appearances left.
+    pub people: Vec<i32>,
+}
+
+/// A photo that has been moved into or out of the trash.
+pub struct Trashed {
+    /// The photo's file, which is left for the caller to move along with its thumbnail.
+    pub file_name: String,
+    /// The photo's faces that were encoded by the current model, for matching to stop or start
+    /// taking them into account.
+    pub faces: Vec<(i32, crate::db::model::FaceEncoding, crate::recognition::Face)>,
 }
 
 impl Photo {
+    /// Records a photo along with a pending job to look for faces in it, yielding the ids of both.
     pub async fn insert(
         db: &crate::db::System,
         digest: crate::db::model::Digest,
@@ -41,69 +79,610 @@ impl Photo {
         thumb_width: u32,
         thumb_height: u32,
         original_datetime: Option<chrono::NaiveDateTime>,
+        original_offset: Option<chrono::FixedOffset>,
         upload_datetime: chrono::NaiveDateTime,
-    ) -> Result<i32, crate::db::QueryError> {
+    ) -> Result<(i32, i32), crate::db::QueryError> {
         db.run_query({
             let db = db.clone();
             move |db_connection| {
+                use crate::diesel::Connection;
+
                 let _guard = db.photos_insertion_guard().lock();
+                let _jobs_guard = db.jobs_insertion_guard().lock();
 
-                diesel::insert_into(crate::db::schema::photos::table)
-                    .values(&NewPhoto {
-                        digest,
-                        file_name,
-                        image_width: image_width as i32,
-                        image_height: image_height as i32,
-                        thumb_width: thumb_width as i32,
-                        thumb_height: thumb_height as i32,
-                        original_datetime,
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    diesel::insert_into(crate::db::schema::photos::table)
+                        .values(&NewPhoto {
+                            digest,
+                            file_name,
+                            image_width: image_width as i32,
+                            image_height: image_height as i32,
+                            thumb_width: thumb_width as i32,
+                            thumb_height: thumb_height as i32,
+                            original_datetime,
+                            upload_datetime,
+                            original_offset: original_offset
+                                .map(|original_offset| original_offset.local_minus_utc()),
+                        })
+                        .execute(&db_connection)?;
+
+                    let photo_id = *photos::table
+                        .select(photos::id)
+                        .order(photos::id.desc())
+                        .limit(1)
+                        .load::<i32>(&db_connection)?
+                        .get(0)
+                        .unwrap();
+
+                    let job_id = crate::db::model::Job::insert(&db_connection, photo_id)?;
+
+                    Ok((photo_id, job_id))
+                })
+            }
+        })
+        .await
+    }
+
+    /// Sets when a photo was taken, and the offset from UTC that it was taken at if that's known,
+    /// or clears it so that it's placed by when it was uploaded, moving its files into the month
+    /// it now belongs in. Nothing is changed if they can't be moved.
+    pub async fn set_original_datetime(
+        db: &crate::db::System,
+        photo_dir_path: &std::path::Path,
+        thumb_dir_path: &std::path::Path,
+        photo_id: i32,
+        original_datetime: Option<chrono::NaiveDateTime>,
+        original_offset: Option<chrono::FixedOffset>,
+    ) -> Result<(), RedateError> {
+        // An offset is no use without the time it's an offset of.
+        let original_offset = original_datetime
+            .and(original_offset)
+            .map(|original_offset| original_offset.local_minus_utc());
+
+        let photo_dir_path = photo_dir_path.to_owned();
+        let thumb_dir_path = thumb_dir_path.to_owned();
+
+        db.run_query(move |db_connection| {
+            use crate::diesel::Connection;
+            use diesel::OptionalExtension;
+
+            let mut move_error = None;
+            let found = db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                let (file_name, upload_datetime) = match photos::table
+                    .select((photos::file_name, photos::upload_datetime))
+                    .filter(photos::id.eq(photo_id))
+                    .filter(photos::id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                    .first::<(String, chrono::NaiveDateTime)>(&db_connection)
+                    .optional()?
+                {
+                    Some(found) => found,
+                    None => return Ok(false),
+                };
+
+                let new_file_name = redate(
+                    &db_connection,
+                    photo_id,
+                    &file_name,
+                    original_datetime,
+                    upload_datetime,
+                )?;
+
+                diesel::update(photos::table.filter(photos::id.eq(photo_id)))
+                    .set(photos::original_offset.eq(original_offset))
+                    .execute(&db_connection)?;
+
+                if let Err(err) = crate::files::rename_photos(
+                    &photo_dir_path,
+                    &thumb_dir_path,
+                    &[(file_name, new_file_name)],
+                ) {
+                    move_error = Some(err);
+                    return Err(diesel::result::Error::RollbackTransaction);
+                }
+
+                Ok(true)
+            });
+
+            Ok(match move_error {
+                Some(err) => Err(err),
+                None => Ok(found?),
+            })
+        })
+        .await?
+        .map_err(RedateError::MoveFailed)
+        .and_then(|found| if found { Ok(()) } else { Err(RedateError::NoSuchPhoto) })
+    }
+
+    /// Moves when each of the given photos was taken by `shift`, as for a camera whose clock was
+    /// wrong, moving their files into the months they now belong in. Photos that aren't known to
+    /// have been taken at any particular time are left as they are, and none are changed if their
+    /// files can't all be moved. Yields the ids of the photos that were changed.
+    pub async fn shift_original_datetimes(
+        db: &crate::db::System,
+        photo_dir_path: &std::path::Path,
+        thumb_dir_path: &std::path::Path,
+        photo_ids: Vec<i32>,
+        shift: chrono::Duration,
+    ) -> Result<Vec<i32>, RedateError> {
+        let photo_dir_path = photo_dir_path.to_owned();
+        let thumb_dir_path = thumb_dir_path.to_owned();
+
+        db.run_query(move |db_connection| {
+            use crate::diesel::Connection;
+
+            let mut move_error = None;
+            let shifted = db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                let found = photos::table
+                    .select((
+                        photos::id,
+                        photos::file_name,
+                        photos::original_datetime,
+                        photos::upload_datetime,
+                    ))
+                    .filter(photos::id.eq_any(&photo_ids))
+                    .filter(photos::id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                    .filter(photos::original_datetime.is_not_null())
+                    .order_by(photos::id)
+                    .load::<(
+                        i32,
+                        String,
+                        Option<chrono::NaiveDateTime>,
+                        chrono::NaiveDateTime,
+                    )>(&db_connection)?;
+
+                let mut shifted = Vec::with_capacity(found.len());
+                let mut file_names = Vec::with_capacity(found.len());
+                for (photo_id, file_name, original_datetime, upload_datetime) in found.into_iter() {
+                    let new_file_name = redate(
+                        &db_connection,
+                        photo_id,
+                        &file_name,
+                        original_datetime.map(|original_datetime| original_datetime + shift),
                         upload_datetime,
-                    })
+                    )?;
+
+                    shifted.push(photo_id);
+                    file_names.push((file_name, new_file_name));
+                }
+
+                if let Err(err) =
+                    crate::files::rename_photos(&photo_dir_path, &thumb_dir_path, &file_names)
+                {
+                    move_error = Some(err);
+                    return Err(diesel::result::Error::RollbackTransaction);
+                }
+
+                Ok(shifted)
+            });
+
+            Ok(match move_error {
+                Some(err) => Err(err),
+                None => Ok(shifted?),
+            })
+        })
+        .await?
+        .map_err(RedateError::MoveFailed)
+    }
+
+    /// Records that a photo has been turned or flipped as `rotation` would turn or flip an upright
+    /// image, leaving it in `orientation` as of its file, and moves the boxes and landmarks of the
+    /// faces found in it along too. Yields the ids of its appearances, whose avatars are then out
+    /// of date.
+    pub async fn rotate(
+        db: &crate::db::System,
+        photo_id: i32,
+        rotation: crate::image_ext::Orientation,
+        orientation: crate::image_ext::Orientation,
+        thumb_width: u32,
+        thumb_height: u32,
+    ) -> Result<Vec<i32>, crate::db::UpdateQueryError> {
+        db.run_query(move |db_connection| {
+            use crate::diesel::Connection;
+            use diesel::OptionalExtension;
+
+            db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                let dimensions = match photos::table
+                    .select((photos::image_width, photos::image_height))
+                    .filter(photos::id.eq(photo_id))
+                    .filter(photos::id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                    .first::<(i32, i32)>(&db_connection)
+                    .optional()?
+                {
+                    Some((image_width, image_height)) => (image_width as u32, image_height as u32),
+                    None => return Ok(None),
+                };
+
+                let found = appearances::table
+                    .select((
+                        appearances::id,
+                        appearances::top,
+                        appearances::left,
+                        appearances::bottom,
+                        appearances::right,
+                        appearances::landmarks,
+                    ))
+                    .filter(appearances::photo.eq(photo_id))
+                    .order_by(appearances::id)
+                    .load::<(
+                        i32,
+                        i32,
+                        i32,
+                        i32,
+                        i32,
+                        Option<crate::db::model::FaceLandmarks>,
+                    )>(&db_connection)?;
+
+                let mut appearance_ids = Vec::with_capacity(found.len());
+                for (appearance_id, top, left, bottom, right, landmarks) in found.into_iter() {
+                    let (x0, y0) = rotation.map_corner((left, top), dimensions);
+                    let (x1, y1) = rotation.map_corner((right, bottom), dimensions);
+
+                    let landmarks = landmarks.map(|landmarks| {
+                        crate::db::model::FaceLandmarks::new(
+                            landmarks
+                                .iter()
+                                .map(|point| rotation.map_pixel(*point, dimensions))
+                                .collect(),
+                        )
+                    });
+
+                    diesel::update(appearances::table.filter(appearances::id.eq(appearance_id)))
+                        .set((
+                            appearances::top.eq(std::cmp::min(y0, y1)),
+                            appearances::left.eq(std::cmp::min(x0, x1)),
+                            appearances::bottom.eq(std::cmp::max(y0, y1)),
+                            appearances::right.eq(std::cmp::max(x0, x1)),
+                            appearances::landmarks.eq(landmarks),
+                        ))
+                        .execute(&db_connection)?;
+
+                    appearance_ids.push(appearance_id);
+                }
+
+                let (image_width, image_height) = rotation.dimensions(dimensions);
+
+                diesel::update(photos::table.filter(photos::id.eq(photo_id)))
+                    .set((
+                        photos::orientation.eq(orientation.exif() as i32),
+                        photos::image_width.eq(image_width as i32),
+                        photos::image_height.eq(image_height as i32),
+                        photos::thumb_width.eq(thumb_width as i32),
+                        photos::thumb_height.eq(thumb_height as i32),
+                    ))
+                    .execute(&db_connection)?;
+
+                Ok(Some(appearance_ids))
+            })
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|appearance_ids| appearance_ids.ok_or(crate::db::UpdateQueryError::NoSuchRecord))
+    }
+
+    /// Moves a photo to the trash, yielding its file name so that its files can be moved too,
+    /// and its faces encoded by `current_encoder`. People whose avatar was taken from it are given
+    /// another.
+    pub async fn trash(
+        db: &crate::db::System,
+        photo_id: i32,
+        current_encoder: String,
+    ) -> Result<Trashed, crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let file_name = match photos::table
+                        .left_join(trashed_photos::table)
+                        .select(photos::file_name)
+                        .filter(photos::id.eq(photo_id))
+                        .filter(trashed_photos::photo.is_null())
+                        .first::<String>(&db_connection)
+                        .optional()?
+                    {
+                        Some(file_name) => file_name,
+                        None => return Ok(None),
+                    };
+
+                    diesel::insert_into(trashed_photos::table)
+                        .values((
+                            trashed_photos::photo.eq(photo_id),
+                            trashed_photos::trashed_datetime.eq(chrono::Utc::now().naive_utc()),
+                        ))
+                        .execute(&db_connection)?;
+
+                    repair_avatars(&db_connection, photo_id)?;
+
+                    Ok(Some(Trashed {
+                        file_name,
+                        faces: load_faces(&db_connection, photo_id, &current_encoder)?,
+                    }))
+                })
+            }
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|trashed| trashed.ok_or(crate::db::UpdateQueryError::NoSuchRecord))
+    }
+
+    /// Takes a photo back out of the trash, yielding its file name so that its files can be moved
+    /// back too, and its faces encoded by `current_encoder`. The photo is queued to be processed
+    /// again if it never was, as jobs for photos in the trash are dropped.
+    pub async fn restore(
+        db: &crate::db::System,
+        photo_id: i32,
+        current_encoder: String,
+    ) -> Result<Trashed, crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::diesel::Connection;
+                use diesel::OptionalExtension;
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let file_name = match photos::table
+                        .inner_join(trashed_photos::table)
+                        .select(photos::file_name)
+                        .filter(photos::id.eq(photo_id))
+                        .first::<String>(&db_connection)
+                        .optional()?
+                    {
+                        Some(file_name) => file_name,
+                        None => return Ok(None),
+                    };
+
+                    diesel::delete(trashed_photos::table.filter(trashed_photos::photo.eq(photo_id)))
+                        .execute(&db_connection)?;
+
+                    repair_avatars(&db_connection, photo_id)?;
+
+                    if !crate::db::model::Job::has_completed(&db_connection, photo_id)? {
+                        let _jobs_guard = db.jobs_insertion_guard().lock();
+                        crate::db::model::Job::insert(&db_connection, photo_id)?;
+                    }
+
+                    Ok(Some(Trashed {
+                        file_name,
+                        faces: load_faces(&db_connection, photo_id, &current_encoder)?,
+                    }))
+                })
+            }
+        })
+        .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|trashed| trashed.ok_or(crate::db::UpdateQueryError::NoSuchRecord))
+    }
+
+    /// Fetches the photos in the trash along with when each was put there, most recent first.
+    pub async fn fetch_trashed(
+        db: &crate::db::System,
+    ) -> Result<Vec<(i32, Self, chrono::NaiveDateTime)>, crate::db::QueryError> {
+        db.run_query(|db_connection| {
+            photos::table
+                .inner_join(trashed_photos::table)
+                .select((
+                    photos::id,
+                    (
+                        photos::digest,
+                        photos::file_name,
+                        photos::image_width,
+                        photos::image_height,
+                        photos::thumb_width,
+                        photos::thumb_height,
+                        photos::original_datetime,
+                        photos::upload_datetime,
+                        photos::original_offset,
+                        photos::orientation,
+                    ),
+                    trashed_photos::trashed_datetime,
+                ))
+                .order_by(trashed_photos::trashed_datetime.desc())
+                .then_order_by(photos::id)
+                .load::<(i32, Self, chrono::NaiveDateTime)>(&db_connection)
+        })
+        .await
+    }
+
+    /// Fetches the ids of the photos put in the trash before `before`.
+    pub async fn fetch_ids_trashed_before(
+        db: &crate::db::System,
+        before: chrono::NaiveDateTime,
+    ) -> Result<Vec<i32>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            trashed_photos::table
+                .select(trashed_photos::photo)
+                .filter(trashed_photos::trashed_datetime.lt(before))
+                .order_by(trashed_photos::photo)
+                .load::<i32>(&db_connection)
+        })
+        .await
+    }
+
+    /// Deletes a photo in the trash for good, along with its appearances and jobs. The avatars of
+    /// the people who appeared in it are picked again, and any of them left with no appearances at
+    /// all are deleted too if `delete_empty_people` is set.
+    pub async fn delete(
+        db: &crate::db::System,
+        photo_id: i32,
+        delete_empty_people: bool,
+    ) -> Result<Deleted, crate::db::UpdateQueryError> {
+        db.run_query({
+            let db = db.clone();
+            move |db_connection| {
+                use crate::db::schema::{
+                    appearance_exclusions, avatars, job_changes, jobs, people, person_exclusions,
+                };
+                use crate::diesel::Connection;
+                use diesel::{BoolExpressionMethods, OptionalExtension};
+
+                let _guard = db.avatars_insertion_guard().lock();
+
+                db_connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let file_name = match photos::table
+                        .inner_join(trashed_photos::table)
+                        .select(photos::file_name)
+                        .filter(photos::id.eq(photo_id))
+                        .first::<String>(&db_connection)
+                        .optional()?
+                    {
+                        Some(file_name) => file_name,
+                        None => return Ok(None),
+                    };
+
+                    let found = appearances::table
+                        .select((appearances::id, appearances::person))
+                        .filter(appearances::photo.eq(photo_id))
+                        .order(appearances::id)
+                        .load::<(i32, Option<i32>)>(&db_connection)?;
+
+                    let appearance_ids: Vec<i32> =
+                        found.iter().map(|(appearance_id, _)| *appearance_id).collect();
+
+                    let mut person_ids: Vec<i32> =
+                        found.into_iter().filter_map(|(_, person_id)| person_id).collect();
+                    person_ids.sort_unstable();
+                    person_ids.dedup();
+
+                    diesel::delete(
+                        avatars::table.filter(avatars::appearance.eq_any(&appearance_ids)),
+                    )
+                    .execute(&db_connection)?;
+
+                    diesel::delete(
+                        appearance_exclusions::table
+                            .filter(appearance_exclusions::appearance.eq_any(&appearance_ids)),
+                    )
                     .execute(&db_connection)?;
 
-                Ok(*photos::table
-                    .select(photos::id)
-                    .order(photos::id.desc())
-                    .limit(1)
-                    .load::<i32>(&db_connection)?
-                    .get(0)
-                    .unwrap())
+                    diesel::update(
+                        appearances::table
+                            .filter(appearances::match_appearance.eq_any(&appearance_ids)),
+                    )
+                    .set(appearances::match_appearance.eq(None::<i32>))
+                    .execute(&db_connection)?;
+
+                    diesel::delete(appearances::table.filter(appearances::photo.eq(photo_id)))
+                        .execute(&db_connection)?;
+
+                    diesel::delete(job_changes::table.filter(job_changes::job.eq_any(
+                        jobs::table.select(jobs::id).filter(jobs::photo.eq(photo_id)),
+                    )))
+                    .execute(&db_connection)?;
+
+                    diesel::delete(jobs::table.filter(jobs::photo.eq(photo_id)))
+                        .execute(&db_connection)?;
+
+                    diesel::delete(trashed_photos::table.filter(trashed_photos::photo.eq(photo_id)))
+                        .execute(&db_connection)?;
+
+                    diesel::delete(photos::table.filter(photos::id.eq(photo_id)))
+                        .execute(&db_connection)?;
+
+                    let mut deleted_person_ids = Vec::new();
+                    for person_id in person_ids.into_iter() {
+                        let appearance_count = appearances::table
+                            .filter(appearances::person.eq(person_id))
+                            .count()
+                            .get_result::<i64>(&db_connection)?;
+
+                        if delete_empty_people && appearance_count == 0 {
+                            diesel::delete(
+                                appearance_exclusions::table
+                                    .filter(appearance_exclusions::person.eq(person_id)),
+                            )
+                            .execute(&db_connection)?;
+
+                            diesel::delete(
+                                person_exclusions::table.filter(
+                                    person_exclusions::person_a
+                                        .eq(person_id)
+                                        .or(person_exclusions::person_b.eq(person_id)),
+                                ),
+                            )
+                            .execute(&db_connection)?;
+
+                            diesel::delete(people::table.filter(people::id.eq(person_id)))
+                                .execute(&db_connection)?;
+
+                            deleted_person_ids.push(person_id);
+                        } else {
+                            crate::db::model::Avatar::repair(&db_connection, person_id)?;
+                        }
+                    }
+
+                    Ok(Some(Deleted {
+                        file_name,
+                        appearances: appearance_ids,
+                        people: deleted_person_ids,
+                    }))
+                })
             }
         })
         .await
+        .map_err(crate::db::UpdateQueryError::QueryError)
+        .and_then(|deleted| deleted.ok_or(crate::db::UpdateQueryError::NoSuchRecord))
     }
 
+    /// Counts the photos taken, or else uploaded, on each day in `time_zone`, most recent first,
+    /// out of those that everyone in `people` appears in. Photos taken without an offset from UTC
+    /// are taken to be in `default_time_zone`.
     pub async fn count_per_day(
         db: &crate::db::System,
         people: Vec<i32>,
+        default_time_zone: chrono_tz::Tz,
+        time_zone: chrono_tz::Tz,
     ) -> Result<Vec<(chrono::NaiveDate, usize)>, crate::db::QueryError> {
         db.run_query(|db_connection| {
-            let datetime = ifnull(photos::original_datetime, photos::upload_datetime);
             people
                 .into_iter()
                 .fold(
                     photos::table
-                        .left_join(appearances::table)
-                        .select(datetime)
-                        .distinct()
-                        .order(datetime.desc())
+                        .select((
+                            photos::original_datetime,
+                            photos::original_offset,
+                            photos::upload_datetime,
+                        ))
+                        .filter(
+                            photos::id
+                                .ne_all(trashed_photos::table.select(trashed_photos::photo)),
+                        )
                         .into_boxed(),
-                    |query, person| query.filter(appearances::person.eq(person)),
+                    |query, person| query.filter(photos::id.eq_any(appearing(person))),
+                )
+                .load::<(Option<chrono::NaiveDateTime>, Option<i32>, chrono::NaiveDateTime)>(
+                    &db_connection,
                 )
-                .load::<chrono::NaiveDateTime>(&db_connection)
         })
         .await
-        .map(|datetimes| {
+        .map(|photos| {
             use itertools::Itertools;
-            datetimes
+            photos
                 .into_iter()
-                .map(|datetime| datetime.date())
+                .map(|(original_datetime, original_offset, upload_datetime)| {
+                    day_taken(
+                        taken_at(
+                            original_datetime,
+                            original_offset,
+                            upload_datetime,
+                            default_time_zone,
+                        ),
+                        time_zone,
+                    )
+                })
+                .sorted_by(|a, b| b.cmp(a))
                 .dedup_with_count()
                 .map(|(count, date)| (date, count))
                 .collect()
         })
     }
 
+    /// Fetches a photo, unless it's in the trash, as its files are too.
     pub async fn fetch(
         db: &crate::db::System,
         photo_id: i32,
@@ -120,19 +699,76 @@ impl Photo {
                     thumb_height,
                     original_datetime,
                     upload_datetime,
+                    original_offset,
+                    orientation,
                 ))
                 .filter(id.eq(photo_id))
+                .filter(id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
                 .load::<Self>(&db_connection)
         })
         .await
         .map(|mut photos| photos.pop())
     }
 
+    /// Fetches the ids of the photos taken, or else uploaded, between `from` and `to` inclusive,
+    /// restricted to `photo_ids` if any are given and to those without any appearances if
+    /// `without_appearances` is set. Photos in the trash are left out, as their files are too.
+    pub async fn fetch_ids(
+        db: &crate::db::System,
+        photo_ids: Vec<i32>,
+        from: Option<chrono::NaiveDate>,
+        to: Option<chrono::NaiveDate>,
+        without_appearances: bool,
+    ) -> Result<Vec<i32>, crate::db::QueryError> {
+        db.run_query(move |db_connection| {
+            let datetime = ifnull(photos::original_datetime, photos::upload_datetime);
+
+            let mut query = photos::table
+                .select(photos::id)
+                .filter(photos::id.ne_all(trashed_photos::table.select(trashed_photos::photo)))
+                .order_by(photos::id)
+                .into_boxed();
+
+            if !photo_ids.is_empty() {
+                query = query.filter(photos::id.eq_any(photo_ids));
+            }
+
+            if let Some(from) = from {
+                query = query.filter(datetime.ge(from.and_hms(0, 0, 0)));
+            }
+
+            if let Some(to) = to {
+                query = query.filter(datetime.lt(to.succ().and_hms(0, 0, 0)));
+            }
+
+            if without_appearances {
+                query = query.filter(diesel::dsl::not(
+                    photos::id.eq_any(appearances::table.select(appearances::photo)),
+                ));
+            }
+
+            query.load::<i32>(&db_connection)
+        })
+        .await
+    }
+
+    /// Fetches the photos taken, or else uploaded, on `date` in `time_zone` that everyone in
+    /// `people` appears in, in the order they were taken. Photos taken without an offset from UTC
+    /// are taken to be in `default_time_zone`.
     pub async fn fetch_all_for_day(
         db: &crate::db::System,
         date: chrono::NaiveDate,
         people: Vec<i32>,
+        default_time_zone: chrono_tz::Tz,
+        time_zone: chrono_tz::Tz,
     ) -> Result<Vec<(i32, Self)>, crate::db::QueryError> {
+        // Times are recorded on the clocks of wherever photos were taken, or in UTC when they
+        // were uploaded, so the photos that might be from the day are narrowed down by what's
+        // recorded before those that are are picked out.
+        let slack = chrono::Duration::hours(MAX_CLOCK_DIFFERENCE_HOURS);
+        let from = date.and_hms(0, 0, 0) - slack;
+        let to = date.succ().and_hms(0, 0, 0) + slack;
+
         db.run_query(move |db_connection| {
             let datetime = ifnull(photos::original_datetime, photos::upload_datetime);
 
@@ -140,7 +776,6 @@ impl Photo {
                 .into_iter()
                 .fold(
                     photos::table
-                        .left_join(appearances::table)
                         .select((
                             photos::id,
                             (
@@ -152,20 +787,182 @@ impl Photo {
                                 photos::thumb_height,
                                 photos::original_datetime,
                                 photos::upload_datetime,
+                                photos::original_offset,
+                                photos::orientation,
                             ),
                         ))
-                        .distinct()
-                        .filter(datetime.ge(date.and_hms(0, 0, 0)))
-                        .filter(datetime.lt(date.succ().and_hms(0, 0, 0)))
-                        .order_by(datetime)
-                        .then_order_by(photos::id)
+                        .filter(
+                            photos::id
+                                .ne_all(trashed_photos::table.select(trashed_photos::photo)),
+                        )
+                        .filter(datetime.ge(from))
+                        .filter(datetime.lt(to))
                         .into_boxed(),
-                    |query, person| query.filter(appearances::person.eq(person)),
+                    |query, person| query.filter(photos::id.eq_any(appearing(person))),
                 )
                 .load::<(i32, Self)>(&db_connection)
         })
         .await
+        .map(|photos| {
+            use itertools::Itertools;
+            photos
+                .into_iter()
+                .map(|(photo_id, photo)| (photo.taken_at(default_time_zone), photo_id, photo))
+                .filter(|(taken_at, _, _)| day_taken(*taken_at, time_zone) == date)
+                .sorted_by_key(|(taken_at, photo_id, _)| (*taken_at, *photo_id))
+                .map(|(_, photo_id, photo)| (photo_id, photo))
+                .collect()
+        })
     }
+
+    fn taken_at(&self, default_time_zone: chrono_tz::Tz) -> chrono::DateTime<chrono::Utc> {
+        taken_at(
+            self.original_datetime,
+            self.original_offset,
+            self.upload_datetime,
+            default_time_zone,
+        )
+    }
+}
+
+/// When a photo was taken, or else uploaded. Times taken without an offset from UTC are read as
+/// being in `default_time_zone`, and as the earlier of the two where its clocks went back.
+fn taken_at(
+    original_datetime: Option<chrono::NaiveDateTime>,
+    original_offset: Option<i32>,
+    upload_datetime: chrono::NaiveDateTime,
+    default_time_zone: chrono_tz::Tz,
+) -> chrono::DateTime<chrono::Utc> {
+    use chrono::{Offset, TimeZone};
+
+    let original_datetime = match original_datetime {
+        Some(original_datetime) => original_datetime,
+        None => return chrono::Utc.from_utc_datetime(&upload_datetime),
+    };
+
+    let offset = original_offset
+        .and_then(chrono::FixedOffset::east_opt)
+        .or_else(|| {
+            default_time_zone
+                .from_local_datetime(&original_datetime)
+                .earliest()
+                .map(|datetime| datetime.offset().fix())
+        })
+        // Skipped over where the clocks went forward, so the offset either side will do.
+        .unwrap_or_else(|| default_time_zone.offset_from_utc_datetime(&original_datetime).fix());
+
+    chrono::Utc.from_utc_datetime(&(original_datetime - offset))
+}
+
+/// The day on which a photo taken at `taken_at` was taken, in `time_zone`.
+fn day_taken(
+    taken_at: chrono::DateTime<chrono::Utc>,
+    time_zone: chrono_tz::Tz,
+) -> chrono::NaiveDate {
+    taken_at.with_timezone(&time_zone).date().naive_local()
+}
+
+/// Records when a photo was taken, moving it into the month that it now belongs in, and yields its
+/// new file name.
+///
+/// Meant to be called within a transaction.
+fn redate(
+    db_connection: &diesel::SqliteConnection,
+    photo_id: i32,
+    file_name: &str,
+    original_datetime: Option<chrono::NaiveDateTime>,
+    upload_datetime: chrono::NaiveDateTime,
+) -> Result<String, diesel::result::Error> {
+    let month_sub_dir = std::path::PathBuf::from(format!(
+        "{}",
+        original_datetime.unwrap_or(upload_datetime).format("%Y-%m")
+    ));
+
+    let new_file_name = match std::path::Path::new(file_name).file_name() {
+        Some(photo_file_name) => month_sub_dir
+            .join(photo_file_name)
+            .to_string_lossy()
+            .into_owned(),
+        None => file_name.to_owned(),
+    };
+
+    diesel::update(photos::table.filter(photos::id.eq(photo_id)))
+        .set((
+            photos::original_datetime.eq(original_datetime),
+            photos::file_name.eq(&new_file_name),
+        ))
+        .execute(db_connection)?;
+
+    Ok(new_file_name)
+}
+
+/// The photos that a person appears in, for narrowing photos down to those everyone asked for
+/// appears in.
+fn appearing(
+    person_id: i32,
+) -> diesel::dsl::Filter<
+    diesel::dsl::Select<appearances::table, appearances::photo>,
+    diesel::dsl::Eq<appearances::person, i32>,
+> {
+    appearances::table
+        .select(appearances::photo)
+        .filter(appearances::person.eq(person_id))
+}
+
+/// Loads a photo's faces that were encoded by `current_encoder`, as matching needs to know them.
+fn load_faces(
+    db_connection: &diesel::SqliteConnection,
+    photo_id: i32,
+    current_encoder: &str,
+) -> Result<
+    Vec<(i32, crate::db::model::FaceEncoding, crate::recognition::Face)>,
+    diesel::result::Error,
+> {
+    Ok(appearances::table
+        .select((
+            appearances::id,
+            appearances::face_encoding,
+            appearances::person,
+            appearances::reference,
+            appearances::confirmed,
+        ))
+        .filter(appearances::photo.eq(photo_id))
+        .filter(appearances::encoder.eq(current_encoder))
+        .load::<(i32, crate::db::model::FaceEncoding, Option<i32>, bool, bool)>(db_connection)?
+        .into_iter()
+        .map(|(appearance_id, face_encoding, person, reference, confirmed)| {
+            (
+                appearance_id,
+                face_encoding,
+                crate::recognition::Face {
+                    person,
+                    reference,
+                    confirmed,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Makes sure that the people who appear in a photo have avatars, as after it has been moved into
+/// or out of the trash.
+///
+/// Meant to be called within a transaction while holding the avatars insertion guard.
+fn repair_avatars(
+    db_connection: &diesel::SqliteConnection,
+    photo_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let person_ids = appearances::table
+        .select(appearances::person)
+        .distinct()
+        .filter(appearances::photo.eq(photo_id))
+        .load::<Option<i32>>(db_connection)?;
+
+    for person_id in person_ids.into_iter().flatten() {
+        crate::db::model::Avatar::repair(db_connection, person_id)?;
+    }
+
+    Ok(())
 }
 
 impl std::convert::Into<photos_web_core::Photo> for Photo {
@@ -177,7 +974,81 @@ impl std::convert::Into<photos_web_core::Photo> for Photo {
             thumb_width: self.thumb_width,
             thumb_height: self.thumb_height,
             original_datetime: self.original_datetime,
+            original_offset: self.original_offset,
             upload_datetime: self.upload_datetime,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{day_taken, taken_at};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Asia::Tokyo, Europe::London, UTC};
+
+    #[test]
+    fn places_photos_by_their_recorded_offset() {
+        let taken = taken_at(
+            Some(NaiveDate::from_ymd(2026, 3, 1).and_hms(23, 30, 0)),
+            Some(3600),
+            NaiveDate::from_ymd(2026, 3, 10).and_hms(12, 0, 0),
+            Tokyo,
+        );
+
+        assert_eq!(taken, Utc.ymd(2026, 3, 1).and_hms(22, 30, 0));
+        assert_eq!(day_taken(taken, London), NaiveDate::from_ymd(2026, 3, 1));
+        assert_eq!(day_taken(taken, New_York), NaiveDate::from_ymd(2026, 3, 1));
+        assert_eq!(day_taken(taken, Tokyo), NaiveDate::from_ymd(2026, 3, 2));
+    }
+
+    #[test]
+    fn places_photos_without_an_offset_in_the_default_time_zone() {
+        let taken = taken_at(
+            Some(NaiveDate::from_ymd(2026, 7, 1).and_hms(0, 30, 0)),
+            None,
+            NaiveDate::from_ymd(2026, 7, 10).and_hms(12, 0, 0),
+            London,
+        );
+
+        assert_eq!(taken, Utc.ymd(2026, 6, 30).and_hms(23, 30, 0));
+        assert_eq!(day_taken(taken, London), NaiveDate::from_ymd(2026, 7, 1));
+        assert_eq!(day_taken(taken, UTC), NaiveDate::from_ymd(2026, 6, 30));
+    }
+
+    #[test]
+    fn places_photos_not_known_to_have_been_taken_by_their_upload() {
+        let taken = taken_at(
+            None,
+            Some(3600),
+            NaiveDate::from_ymd(2026, 7, 10).and_hms(23, 30, 0),
+            Tokyo,
+        );
+
+        assert_eq!(taken, Utc.ymd(2026, 7, 10).and_hms(23, 30, 0));
+        assert_eq!(day_taken(taken, UTC), NaiveDate::from_ymd(2026, 7, 10));
+        assert_eq!(day_taken(taken, Tokyo), NaiveDate::from_ymd(2026, 7, 11));
+    }
+
+    #[test]
+    fn places_photos_taken_as_the_clocks_changed() {
+        let upload_datetime = NaiveDate::from_ymd(2026, 11, 1).and_hms(12, 0, 0);
+
+        // Half past one happened twice when the clocks went back, and is read as the first.
+        let taken = taken_at(
+            Some(NaiveDate::from_ymd(2026, 10, 25).and_hms(1, 30, 0)),
+            None,
+            upload_datetime,
+            London,
+        );
+        assert_eq!(taken, Utc.ymd(2026, 10, 25).and_hms(0, 30, 0));
+
+        // And never happened when they went forward.
+        let taken = taken_at(
+            Some(NaiveDate::from_ymd(2026, 3, 29).and_hms(1, 30, 0)),
+            None,
+            upload_datetime,
+            London,
+        );
+        assert_eq!(day_taken(taken, London), NaiveDate::from_ymd(2026, 3, 29));
+    }
+}
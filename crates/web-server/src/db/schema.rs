@@ -1,7 +1,15 @@
 table! {
-    appearances (id) {
+    appearance_exclusions (id) {
         id -> Integer,
+        appearance -> Integer,
         person -> Integer,
+    }
+}
+
+table! {
+    appearances (id) {
+        id -> Integer,
+        person -> Nullable<Integer>,
         photo -> Integer,
         reference -> Bool,
         top -> Integer,
@@ -9,6 +17,14 @@ table! {
         bottom -> Integer,
         right -> Integer,
         face_encoding -> Binary,
+        match_distance -> Nullable<Double>,
+        match_appearance -> Nullable<Integer>,
+        confirmed -> Bool,
+        encoder -> Nullable<Text>,
+        sharpness -> Nullable<Double>,
+        frontalness -> Nullable<Double>,
+        landmarks -> Nullable<Binary>,
+        manual -> Bool,
     }
 }
 
@@ -20,6 +36,66 @@ table! {
     }
 }
 
+table! {
+    job_changes (id) {
+        id -> Integer,
+        job -> Integer,
+        appearance -> Integer,
+        change -> Text,
+    }
+}
+
+table! {
+    jobs (id) {
+        id -> Integer,
+        photo -> Integer,
+        status -> Text,
+        attempts -> Integer,
+        error -> Nullable<Text>,
+        created_datetime -> Timestamp,
+        updated_datetime -> Timestamp,
+        reprocess -> Bool,
+        min_face_pixels -> Nullable<Integer>,
+        min_face_fraction -> Nullable<Double>,
+        upsample -> Nullable<Integer>,
+        max_image_size -> Nullable<Integer>,
+    }
+}
+
+table! {
+    merge_appearances (id) {
+        id -> Integer,
+        merge -> Integer,
+        appearance -> Integer,
+    }
+}
+
+table! {
+    merge_exclusions (id) {
+        id -> Integer,
+        merge -> Integer,
+        appearance -> Nullable<Integer>,
+        person -> Nullable<Integer>,
+        shared -> Bool,
+    }
+}
+
+table! {
+    merges (id) {
+        id -> Integer,
+        dst -> Integer,
+        src -> Integer,
+        first_name -> Text,
+        middle_names -> Nullable<Text>,
+        surname -> Text,
+        display_name -> Nullable<Text>,
+        dob -> Nullable<Date>,
+        avatar -> Nullable<Integer>,
+        merged_datetime -> Timestamp,
+        undone_datetime -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     people (id) {
         id -> Integer,
@@ -31,6 +107,14 @@ table! {
     }
 }
 
+table! {
+    person_exclusions (id) {
+        id -> Integer,
+        person_a -> Integer,
+        person_b -> Integer,
+    }
+}
+
 table! {
     photos (id) {
         id -> Integer,
@@ -42,12 +126,41 @@ table! {
         thumb_height -> Integer,
         original_datetime -> Nullable<Timestamp>,
         upload_datetime -> Timestamp,
+        original_offset -> Nullable<Integer>,
+        orientation -> Nullable<Integer>,
+    }
+}
+
+table! {
+    trashed_photos (photo) {
+        photo -> Integer,
+        trashed_datetime -> Timestamp,
     }
 }
 
+joinable!(appearance_exclusions -> appearances (appearance));
+joinable!(appearance_exclusions -> people (person));
 joinable!(appearances -> people (person));
 joinable!(appearances -> photos (photo));
 joinable!(avatars -> appearances (appearance));
 joinable!(avatars -> people (person));
+joinable!(job_changes -> jobs (job));
+joinable!(jobs -> photos (photo));
+joinable!(merge_appearances -> merges (merge));
+joinable!(merge_exclusions -> merges (merge));
+joinable!(trashed_photos -> photos (photo));
 
-allow_tables_to_appear_in_same_query!(appearances, avatars, people, photos,);
+allow_tables_to_appear_in_same_query!(
+    appearance_exclusions,
+    appearances,
+    avatars,
+    job_changes,
+    jobs,
+    merge_appearances,
+    merge_exclusions,
+    merges,
+    people,
+    person_exclusions,
+    photos,
+    trashed_photos,
+);
@@ -0,0 +1,106 @@
+//! Moves and removes the files kept for photos. This is mostly done once the database has been
+//! updated, so a file that can't be moved or removed is logged and left where it is rather than
+//! failing the change it follows from. Photos that now belong in another month are the exception,
+//! as the database would otherwise name files that aren't there, so they're moved before the new
+//! file names are recorded.
+
+/// Moves each file from the first path to the second, creating directories as needed. Files that
+/// aren't there are skipped.
+pub async fn move_all(moves: Vec<(std::path::PathBuf, std::path::PathBuf)>) {
+    run_blocking(move || {
+        for (from, to) in moves.iter() {
+            log_failure(from, move_file(from, to));
+        }
+    })
+    .await
+}
+
+/// Moves the photos and thumbnails whose file names have changed, given as they were before and
+/// after. Files that aren't there are skipped. Should one fail to move, those already moved are
+/// moved back and the failure is returned, so that the new file names needn't be recorded.
+pub fn rename_photos(
+    photo_dir_path: &std::path::Path,
+    thumb_dir_path: &std::path::Path,
+    file_names: &[(String, String)],
+) -> std::io::Result<()> {
+    let moves: Vec<_> = file_names
+        .iter()
+        .filter(|(file_name, new_file_name)| file_name != new_file_name)
+        .flat_map(|(file_name, new_file_name)| {
+            vec![
+                (
+                    photo_dir_path.join(file_name),
+                    photo_dir_path.join(new_file_name),
+                ),
+                (
+                    thumb_dir_path.join(file_name),
+                    thumb_dir_path.join(new_file_name),
+                ),
+            ]
+        })
+        .collect();
+
+    for (index, (from, to)) in moves.iter().enumerate() {
+        match move_file(from, to) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                for (from, to) in moves[..index].iter().rev() {
+                    log_failure(to, move_file(to, from));
+                }
+                return Err(err);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes each of the files and directories, along with everything in the directories.
+pub async fn remove_all(file_paths: Vec<std::path::PathBuf>, dir_paths: Vec<std::path::PathBuf>) {
+    run_blocking(move || {
+        for file_path in file_paths.iter() {
+            log_failure(file_path, std::fs::remove_file(file_path));
+        }
+        for dir_path in dir_paths.iter() {
+            log_failure(dir_path, std::fs::remove_dir_all(dir_path));
+        }
+    })
+    .await
+}
+
+/// Moves a file, copying it where it can't simply be renamed, as when the trash directory is on
+/// another file system.
+fn move_file(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+    if let Some(dir_path) = to.parent() {
+        std::fs::create_dir_all(dir_path)?;
+    }
+
+    match std::fs::rename(from, to) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Logs a failure to move or remove a file, other than its not being there.
+fn log_failure(path: &std::path::Path, result: std::io::Result<()>) {
+    match result {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            log::error!("Failed to move or remove {:?}: {}", path, err)
+        }
+        _ => {}
+    }
+}
+
+async fn run_blocking<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let _ = actix_web::web::block(move || {
+        f();
+        Ok::<_, ()>(())
+    })
+    .await;
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Orientation {
     Identity,
     FlipHorz,
@@ -27,6 +27,228 @@ impl Orientation {
             Self::Rotate270Cw => Cow::Owned(image.rotate270()),
         }
     }
+
+    /// Reads an orientation as it's numbered in EXIF meta-data.
+    pub fn from_exif(orientation: i64) -> Option<Self> {
+        match orientation {
+            1 => Some(Self::Identity),
+            2 => Some(Self::FlipHorz),
+            3 => Some(Self::Rotate180),
+            4 => Some(Self::FlipVert),
+            5 => Some(Self::Transpose),
+            6 => Some(Self::Rotate90Cw),
+            7 => Some(Self::Transverse),
+            8 => Some(Self::Rotate270Cw),
+            _ => None,
+        }
+    }
+
+    pub fn exif(self) -> u16 {
+        match self {
+            Self::Identity => 1,
+            Self::FlipHorz => 2,
+            Self::Rotate180 => 3,
+            Self::FlipVert => 4,
+            Self::Transpose => 5,
+            Self::Rotate90Cw => 6,
+            Self::Transverse => 7,
+            Self::Rotate270Cw => 8,
+        }
+    }
+
+    /// How many quarter turns clockwise an image is given to be put in this orientation, after
+    /// being flipped from left to right if it is.
+    fn turns_and_flip(self) -> (u32, bool) {
+        match self {
+            Self::Identity => (0, false),
+            Self::FlipHorz => (0, true),
+            Self::Rotate180 => (2, false),
+            Self::FlipVert => (2, true),
+            Self::Transpose => (3, true),
+            Self::Rotate90Cw => (1, false),
+            Self::Transverse => (1, true),
+            Self::Rotate270Cw => (3, false),
+        }
+    }
+
+    fn from_turns_and_flip(turns: u32, flip: bool) -> Self {
+        match (turns % 4, flip) {
+            (0, false) => Self::Identity,
+            (0, true) => Self::FlipHorz,
+            (1, false) => Self::Rotate90Cw,
+            (1, true) => Self::Transverse,
+            (2, false) => Self::Rotate180,
+            (2, true) => Self::FlipVert,
+            (3, false) => Self::Rotate270Cw,
+            _ => Self::Transpose,
+        }
+    }
+
+    /// The orientation that an image in this one is put in by then being turned or flipped as
+    /// `then` would turn or flip an upright image.
+    pub fn then(self, then: Self) -> Self {
+        let (turns, flip) = self.turns_and_flip();
+        let (then_turns, then_flip) = then.turns_and_flip();
+
+        // Flipping an image that has been turned one way leaves it turned the other way.
+        let turns = if then_flip { 4 - turns } else { turns };
+
+        Self::from_turns_and_flip(turns + then_turns, flip != then_flip)
+    }
+
+    /// The dimensions of an image of `(width, height)` once it's been put in this orientation.
+    pub fn dimensions(self, (width, height): (u32, u32)) -> (u32, u32) {
+        let (turns, _) = self.turns_and_flip();
+        if turns % 2 == 0 {
+            (width, height)
+        } else {
+            (height, width)
+        }
+    }
+
+    /// Where a pixel of an image of `dimensions` ends up once it's been put in this orientation.
+    pub fn map_pixel(self, pixel: (i32, i32), dimensions: (u32, u32)) -> (i32, i32) {
+        self.map(pixel, dimensions, 1)
+    }
+
+    /// Where a corner between the pixels of an image of `dimensions`, such as that of a box drawn
+    /// around some of them, ends up once it's been put in this orientation.
+    pub fn map_corner(self, corner: (i32, i32), dimensions: (u32, u32)) -> (i32, i32) {
+        self.map(corner, dimensions, 0)
+    }
+
+    fn map(self, (mut x, mut y): (i32, i32), (width, height): (u32, u32), size: i32) -> (i32, i32) {
+        let (turns, flip) = self.turns_and_flip();
+        let (mut width, mut height) = (width as i32, height as i32);
+
+        if flip {
+            x = width - size - x;
+        }
+
+        for _ in 0..turns {
+            let turned = (height - size - y, x);
+            x = turned.0;
+            y = turned.1;
+            std::mem::swap(&mut width, &mut height);
+        }
+
+        (x, y)
+    }
+}
+
+/// Puts a JPEG in `orientation` without decoding it, by recording the orientation in its EXIF
+/// meta-data or adding meta-data that records it where there is none. Yields nothing for JPEGs
+/// whose meta-data doesn't record an orientation, as there's no room to add one.
+pub fn orient_jpeg(contents: &[u8], orientation: Orientation) -> Option<Vec<u8>> {
+    const START_OF_IMAGE: u8 = 0xd8;
+    const START_OF_SCAN: u8 = 0xda;
+    const APP0: u8 = 0xe0;
+    const APP1: u8 = 0xe1;
+    const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+    if contents.get(0..2)? != [0xff, START_OF_IMAGE] {
+        return None;
+    }
+
+    // Any meta-data is added after the JFIF segment, which has to come first.
+    let mut insert_at = 2;
+
+    let mut offset = 2;
+    while contents.get(offset) == Some(&0xff) {
+        let marker = *contents.get(offset + 1)?;
+        if marker == START_OF_SCAN {
+            break;
+        }
+
+        let length = u16::from_be_bytes([*contents.get(offset + 2)?, *contents.get(offset + 3)?]);
+        let segment_end = offset + 2 + length as usize;
+        let segment = contents.get(offset + 4..segment_end)?;
+
+        if marker == APP1 && segment.starts_with(EXIF_HEADER) {
+            let mut contents = contents.to_vec();
+            let tiff_start = offset + 4 + EXIF_HEADER.len();
+            set_tiff_orientation(&mut contents[tiff_start..segment_end], orientation)?;
+            return Some(contents);
+        }
+
+        if marker == APP0 && offset == 2 {
+            insert_at = segment_end;
+        }
+
+        offset = segment_end;
+    }
+
+    // A big-endian TIFF header followed by a directory of just the orientation.
+    let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+    tiff.extend_from_slice(&orientation.exif().to_be_bytes());
+    tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+    let length = (2 + EXIF_HEADER.len() + tiff.len()) as u16;
+
+    let mut oriented = Vec::with_capacity(contents.len() + 2 + length as usize);
+    oriented.extend_from_slice(&contents[..insert_at]);
+    oriented.extend_from_slice(&[0xff, APP1]);
+    oriented.extend_from_slice(&length.to_be_bytes());
+    oriented.extend_from_slice(EXIF_HEADER);
+    oriented.extend_from_slice(&tiff);
+    oriented.extend_from_slice(&contents[insert_at..]);
+    Some(oriented)
+}
+
+/// Rewrites the orientation recorded in the first directory of some TIFF meta-data.
+fn set_tiff_orientation(tiff: &mut [u8], orientation: Orientation) -> Option<()> {
+    const ORIENTATION_TAG: u16 = 0x0112;
+    const SHORT_FORMAT: u16 = 3;
+    const ENTRY_SIZE: usize = 12;
+
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+
+    let read_u16 = |tiff: &[u8], at: usize| {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+
+    let read_u32 = |tiff: &[u8], at: usize| {
+        let bytes = [
+            *tiff.get(at)?,
+            *tiff.get(at + 1)?,
+            *tiff.get(at + 2)?,
+            *tiff.get(at + 3)?,
+        ];
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let directory = read_u32(tiff, 4)? as usize;
+    let entry_count = read_u16(tiff, directory)? as usize;
+
+    let entry = (0..entry_count)
+        .map(|index| directory + 2 + index * ENTRY_SIZE)
+        .find(|entry| read_u16(tiff, *entry) == Some(ORIENTATION_TAG))?;
+
+    if read_u16(tiff, entry + 2)? != SHORT_FORMAT {
+        return None;
+    }
+
+    let value = if little_endian {
+        orientation.exif().to_le_bytes()
+    } else {
+        orientation.exif().to_be_bytes()
+    };
+    tiff.get_mut(entry + 8..entry + 10)?.copy_from_slice(&value);
+
+    Some(())
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -45,17 +267,7 @@ impl MetaData {
     pub fn orientation(&self) -> Option<Orientation> {
         self.get_tag_value(rexif::ExifTag::Orientation)
             .and_then(|orientation| orientation.to_i64(0))
-            .and_then(|orientation| match orientation {
-                1 => Some(Orientation::Identity),
-                2 => Some(Orientation::FlipHorz),
-                3 => Some(Orientation::Rotate180),
-                4 => Some(Orientation::FlipVert),
-                5 => Some(Orientation::Transpose),
-                6 => Some(Orientation::Rotate90Cw),
-                7 => Some(Orientation::Transverse),
-                8 => Some(Orientation::Rotate270Cw),
-                _ => None,
-            })
+            .and_then(Orientation::from_exif)
     }
 
     pub fn original_datetime(&self) -> Option<chrono::NaiveDateTime> {
@@ -70,6 +282,69 @@ impl MetaData {
             })
     }
 
+    /// How far ahead of UTC the camera's clock was set when the photo was taken, as recorded
+    /// alongside the original datetime or else as worked out from the GPS time.
+    pub fn original_offset(&self) -> Option<chrono::FixedOffset> {
+        self.recorded_original_offset()
+            .or_else(|| self.gps_original_offset())
+    }
+
+    fn recorded_original_offset(&self) -> Option<chrono::FixedOffset> {
+        // OffsetTimeOriginal, which rexif doesn't know of.
+        const OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+        let offset_string = self
+            .0
+            .entries
+            .iter()
+            .find(|entry| {
+                entry.tag == rexif::ExifTag::UnknownToMe && entry.ifd.tag == OFFSET_TIME_ORIGINAL
+            })
+            .and_then(|entry| match &entry.value {
+                rexif::TagValue::Ascii(ascii_string) => Some(ascii_string.trim()),
+                _ => None,
+            })?;
+
+        // Written as "+HH:MM" or "-HH:MM".
+        let (sign, offset_string) = match offset_string.strip_prefix('+') {
+            Some(offset_string) => (1, offset_string),
+            None => (-1, offset_string.strip_prefix('-')?),
+        };
+        let mut parts = offset_string.splitn(2, ':');
+        let hours = parts.next()?.parse::<i32>().ok()?;
+        let minutes = parts.next()?.parse::<i32>().ok()?;
+
+        offset_from_seconds(sign * (hours * 60 + minutes) * 60)
+    }
+
+    /// Works out the offset from the GPS time, which is UTC, to the nearest quarter of an hour, as
+    /// the GPS may have fixed the time a little before the photo was taken.
+    fn gps_original_offset(&self) -> Option<chrono::FixedOffset> {
+        const QUARTER_HOUR_SECONDS: f64 = 15.0 * 60.0;
+
+        let date = match self.get_tag_value(rexif::ExifTag::GPSDateStamp)? {
+            rexif::TagValue::Ascii(ascii_string) => {
+                chrono::NaiveDate::parse_from_str(ascii_string.trim(), "%Y:%m:%d").ok()?
+            }
+            _ => return None,
+        };
+
+        let seconds = match self.get_tag_value(rexif::ExifTag::GPSTimeStamp)? {
+            rexif::TagValue::URational(parts) if parts.len() == 3 => {
+                parts[0].value() * 3600.0 + parts[1].value() * 60.0 + parts[2].value()
+            }
+            _ => return None,
+        };
+        if !seconds.is_finite() {
+            return None;
+        }
+
+        let gps_datetime = date.and_hms(0, 0, 0) + chrono::Duration::seconds(seconds as i64);
+        let offset = (self.original_datetime()? - gps_datetime).num_seconds() as f64;
+
+        offset_from_seconds(((offset / QUARTER_HOUR_SECONDS).round() * QUARTER_HOUR_SECONDS) as i32)
+    }
+
     fn get_tag_value(&self, tag: rexif::ExifTag) -> Option<&rexif::TagValue> {
         self.0
             .entries
@@ -79,6 +354,17 @@ impl MetaData {
     }
 }
 
+/// Makes an offset from UTC of as many seconds, provided that it's one that clocks are set to.
+fn offset_from_seconds(seconds: i32) -> Option<chrono::FixedOffset> {
+    const MAX_OFFSET_SECONDS: i32 = 14 * 60 * 60;
+
+    if seconds.abs() <= MAX_OFFSET_SECONDS {
+        chrono::FixedOffset::east_opt(seconds)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum NewImageExtError {
     #[error("Failed to read the image")]
@@ -156,9 +442,24 @@ impl ImageExt {
             .and_then(|meta_data| meta_data.original_datetime())
     }
 
+    pub fn original_offset(&self) -> Option<chrono::FixedOffset> {
+        self.meta_data
+            .as_ref()
+            .and_then(|meta_data| meta_data.original_offset())
+    }
+
     pub fn reorient(self) -> image::DynamicImage {
+        self.reorient_as(None)
+    }
+
+    /// Puts the image in `orientation`, where it has been turned or flipped since it was
+    /// uploaded, or else in the orientation that its meta-data gives.
+    pub fn reorient_as(self, orientation: Option<Orientation>) -> image::DynamicImage {
         // TODO fix the unecessary copy
-        self.orientation().reorient(&self.image).into_owned()
+        orientation
+            .unwrap_or_else(|| self.orientation())
+            .reorient(&self.image)
+            .into_owned()
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -166,6 +467,21 @@ impl ImageExt {
     }
 }
 
+/// Loads the image at `path` for faces to be looked for in, put in `orientation` as with
+/// `ImageExt::reorient_as`.
+pub fn load_rgb(
+    path: &std::path::Path,
+    orientation: Option<Orientation>,
+) -> Result<image::RgbImage, NewImageExtError> {
+    Ok(ImageExt::new(std::io::BufReader::new(
+        std::fs::File::open(path).map_err(NewImageExtError::ImageReadError)?,
+    ))?
+    .reorient_as(orientation)
+    .into_rgb8())
+}
+
+pub const THUMB_SIZE: u32 = 256;
+
 pub fn thumbnail(image: &image::DynamicImage, size: u32) -> image::DynamicImage {
     use image::GenericImageView;
 
@@ -189,14 +505,149 @@ pub fn thumbnail(image: &image::DynamicImage, size: u32) -> image::DynamicImage
     )
 }
 
-pub fn encode_image(
-    image: &image::DynamicImage,
-) -> Result<actix_web::web::HttpResponse, image::ImageError> {
-    let mut cursor = std::io::Cursor::new(Vec::with_capacity(1024 * 1024));
+#[cfg(test)]
+mod tests {
+    use super::Orientation;
+    use image::GenericImageView;
 
-    image.write_to(&mut cursor, image::ImageOutputFormat::Png)?;
+    const ORIENTATIONS: [Orientation; 8] = [
+        Orientation::Identity,
+        Orientation::FlipHorz,
+        Orientation::Rotate180,
+        Orientation::FlipVert,
+        Orientation::Transpose,
+        Orientation::Rotate90Cw,
+        Orientation::Transverse,
+        Orientation::Rotate270Cw,
+    ];
 
-    Ok(actix_web::web::HttpResponse::Ok()
-        .header(actix_web::http::header::CONTENT_TYPE, "image/png")
-        .body(cursor.into_inner()))
+    /// A small image that isn't square, each of whose pixels records where it started out.
+    fn image() -> image::DynamicImage {
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(4, 3, |x, y| {
+            image::Rgb([x as u8, y as u8, 0])
+        }))
+    }
+
+    #[test]
+    fn follows_one_orientation_with_another() {
+        let image = image();
+        for orientation in ORIENTATIONS.iter() {
+            for then in ORIENTATIONS.iter() {
+                let expected = then.reorient(&orientation.reorient(&image)).into_owned();
+                let actual = orientation.then(*then).reorient(&image).into_owned();
+                assert_eq!(
+                    actual.as_bytes(),
+                    expected.as_bytes(),
+                    "{:?} then {:?}",
+                    orientation,
+                    then
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn follows_a_turn_with_others() {
+        assert_eq!(
+            Orientation::Rotate90Cw.then(Orientation::Rotate90Cw),
+            Orientation::Rotate180
+        );
+        assert_eq!(
+            Orientation::Rotate270Cw.then(Orientation::Rotate90Cw),
+            Orientation::Identity
+        );
+        assert_eq!(
+            Orientation::FlipHorz.then(Orientation::FlipHorz),
+            Orientation::Identity
+        );
+        assert_eq!(
+            Orientation::Rotate90Cw.then(Orientation::FlipHorz),
+            Orientation::Transpose
+        );
+    }
+
+    #[test]
+    fn maps_pixels_to_where_they_end_up() {
+        let image = image();
+        for orientation in ORIENTATIONS.iter() {
+            let reoriented = orientation.reorient(&image);
+            assert_eq!(
+                reoriented.dimensions(),
+                orientation.dimensions(image.dimensions())
+            );
+
+            for (x, y, pixel) in image.pixels() {
+                let (mapped_x, mapped_y) =
+                    orientation.map_pixel((x as i32, y as i32), image.dimensions());
+                assert_eq!(
+                    reoriented.get_pixel(mapped_x as u32, mapped_y as u32),
+                    pixel,
+                    "{:?} ({}, {})",
+                    orientation,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn maps_boxes_around_the_pixels_they_enclosed() {
+        let image = image();
+        let (left, top, right, bottom) = (1, 0, 3, 2);
+
+        for orientation in ORIENTATIONS.iter() {
+            let (x1, y1) = orientation.map_corner((left, top), image.dimensions());
+            let (x2, y2) = orientation.map_corner((right, bottom), image.dimensions());
+            let (mapped_left, mapped_right) = (x1.min(x2), x1.max(x2));
+            let (mapped_top, mapped_bottom) = (y1.min(y2), y1.max(y2));
+
+            let mut enclosed: Vec<(i32, i32)> = (left..right)
+                .flat_map(|x| (top..bottom).map(move |y| (x, y)))
+                .map(|pixel| orientation.map_pixel(pixel, image.dimensions()))
+                .collect();
+            enclosed.sort_unstable();
+
+            let mut mapped: Vec<(i32, i32)> = (mapped_left..mapped_right)
+                .flat_map(|x| (mapped_top..mapped_bottom).map(move |y| (x, y)))
+                .collect();
+            mapped.sort_unstable();
+
+            assert_eq!(mapped, enclosed, "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn records_the_orientation_of_a_jpeg() {
+        let mut contents = Vec::new();
+        image()
+            .write_to(&mut contents, image::ImageFormat::Jpeg)
+            .unwrap();
+        let orientation = |contents: &[u8]| {
+            super::ImageExt::new(std::io::Cursor::new(contents))
+                .unwrap()
+                .orientation()
+        };
+
+        // Meta-data is added to a JPEG without any, and rewritten in one that has some.
+        let oriented = super::orient_jpeg(&contents, Orientation::Rotate90Cw).unwrap();
+        assert_eq!(orientation(&oriented), Orientation::Rotate90Cw);
+        let reoriented = super::orient_jpeg(&oriented, Orientation::Rotate180).unwrap();
+        assert_eq!(reoriented.len(), oriented.len());
+        assert_eq!(orientation(&reoriented), Orientation::Rotate180);
+
+        let mut contents = Vec::new();
+        image()
+            .write_to(&mut contents, image::ImageFormat::Png)
+            .unwrap();
+        assert!(super::orient_jpeg(&contents, Orientation::Rotate90Cw).is_none());
+    }
+
+    #[test]
+    fn maps_the_corners_of_a_turned_image() {
+        assert_eq!(Orientation::Rotate90Cw.map_corner((0, 0), (4, 3)), (3, 0));
+        assert_eq!(Orientation::Rotate90Cw.map_corner((4, 3), (4, 3)), (0, 4));
+        assert_eq!(Orientation::Rotate180.map_corner((1, 0), (4, 3)), (3, 3));
+        assert_eq!(Orientation::FlipHorz.map_corner((1, 2), (4, 3)), (3, 2));
+    }
 }
@@ -0,0 +1,556 @@
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessPhotoError {
+    #[error("Failed to fetch the photo's record")]
+    FetchPhotoFailed(#[source] crate::db::QueryError),
+    #[error("No such photo {0}")]
+    NoSuchPhoto(i32),
+    #[error("Failed to load the photo")]
+    PhotoLoadFailed(#[source] crate::image_ext::NewImageExtError),
+    #[error("Operation cancelled")]
+    OperationCancelled,
+    #[error("Failed to detect faces")]
+    FaceDetectionFailed(#[source] crate::recognition::ServiceError),
+    #[error("Failed to encode faces")]
+    FaceEncodingFailed(#[source] crate::recognition::ServiceError),
+    #[error("Failed to record appearance in database")]
+    RecordAppearanceFailed(#[source] crate::db::QueryError),
+    #[error("Failed to fetch the photo's appearances")]
+    FetchAppearancesFailed(#[source] crate::db::QueryError),
+    #[error("Failed to update appearance in database")]
+    UpdateAppearanceFailed(#[source] crate::db::UpdateQueryError),
+}
+
+/// The appearances that reprocessing a photo added, removed and changed.
+#[derive(Default)]
+pub struct FaceChanges {
+    pub added: Vec<i32>,
+    pub removed: Vec<i32>,
+    pub changed: Vec<i32>,
+}
+
+impl std::convert::Into<Vec<(i32, crate::db::model::job::AppearanceChange)>> for FaceChanges {
+    fn into(self) -> Vec<(i32, crate::db::model::job::AppearanceChange)> {
+        use crate::db::model::job::AppearanceChange;
+
+        let added = self
+            .added
+            .into_iter()
+            .map(|appearance_id| (appearance_id, AppearanceChange::Added));
+        let removed = self
+            .removed
+            .into_iter()
+            .map(|appearance_id| (appearance_id, AppearanceChange::Removed));
+        let changed = self
+            .changed
+            .into_iter()
+            .map(|appearance_id| (appearance_id, AppearanceChange::Changed));
+
+        added.chain(removed).chain(changed).collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct Context {
+    pub db: crate::db::System,
+    pub photo_dir_path: std::path::PathBuf,
+    pub avatar_dir_path: std::path::PathBuf,
+    pub recognition: crate::recognition::Service,
+    pub max_attempts: i32,
+}
+
+/// Starts `worker_count` workers on the current arbiter, each of which claims pending jobs from
+/// the database and processes them one at a time.
+pub fn spawn(context: Context, worker_count: usize) {
+    for _ in 0..worker_count {
+        actix_web::rt::spawn(run_worker(context.clone()));
+    }
+}
+
+async fn run_worker(context: Context) {
+    use actix_web::rt::time::delay_for;
+
+    loop {
+        match crate::db::model::Job::claim_next(&context.db).await {
+            Ok(Some((job_id, job))) => {
+                log::debug!("JOB {} PHOTO {} ATTEMPT {}", job_id, job.photo, job.attempts);
+
+                let outcome = if job.reprocess {
+                    reprocess_photo(
+                        &context.db,
+                        &context.photo_dir_path,
+                        &context.avatar_dir_path,
+                        &context.recognition,
+                        detector(&context, &job),
+                        job.photo,
+                    )
+                    .await
+                    .map(|changes| changes.into())
+                } else {
+                    process_photo(&context, job.photo)
+                        .await
+                        .map(|()| Vec::new())
+                };
+
+                let result = match outcome {
+                    Ok(changes) => {
+                        crate::db::model::Job::complete(&context.db, job_id, changes).await
+                    }
+                    Err(err) => {
+                        let reason = describe_error(&err);
+                        log::error!("Job {} failed: {}", job_id, reason);
+                        // A photo that's gone, or gone into the trash, won't come back by trying
+                        // again.
+                        let retry = job.attempts < context.max_attempts
+                            && !matches!(err, ProcessPhotoError::NoSuchPhoto(_));
+                        crate::db::model::Job::fail(&context.db, job_id, reason, retry).await
+                    }
+                };
+
+                if let Err(err) = result {
+                    log::error!("Failed to record the outcome of job {}: {}", job_id, err);
+                }
+            }
+            Ok(None) => delay_for(POLL_INTERVAL).await,
+            Err(err) => {
+                log::error!("Failed to claim a job: {}", err);
+                delay_for(POLL_INTERVAL).await
+            }
+        }
+    }
+}
+
+/// How faces are looked for by a job, which is as the server was started to except where the job
+/// overrides it.
+fn detector(context: &Context, job: &crate::db::model::Job) -> crate::recognition::Detector {
+    let detector = context.recognition.detector();
+    crate::recognition::Detector {
+        min_face_pixels: job
+            .min_face_pixels
+            .map(|pixels| pixels as u32)
+            .or(detector.min_face_pixels),
+        min_face_fraction: job.min_face_fraction.or(detector.min_face_fraction),
+        upsample: job
+            .upsample
+            .map(|upsample| upsample as u32)
+            .unwrap_or(detector.upsample),
+        max_image_size: job
+            .max_image_size
+            .map(|size| size as u32)
+            .or(detector.max_image_size),
+    }
+}
+
+pub fn describe_error(error: &dyn std::error::Error) -> String {
+    let mut description = format!("{}", error);
+    let mut current = error.source();
+    while let Some(error) = current {
+        description.push_str(&format!(": {}", error));
+        current = error.source();
+    }
+    description
+}
+
+async fn load_photo(
+    db: &crate::db::System,
+    photo_dir_path: &std::path::Path,
+    photo_id: i32,
+) -> Result<image::RgbImage, ProcessPhotoError> {
+    use actix_web::{error::BlockingError, web};
+
+    let photo = crate::db::model::Photo::fetch(db, photo_id)
+        .await
+        .map_err(ProcessPhotoError::FetchPhotoFailed)?
+        .ok_or(ProcessPhotoError::NoSuchPhoto(photo_id))?;
+
+    web::block({
+        let photo_file_path = photo_dir_path.join(&photo.file_name);
+        let orientation = photo
+            .orientation
+            .and_then(|orientation| crate::image_ext::Orientation::from_exif(orientation.into()));
+        move || {
+            crate::image_ext::load_rgb(&photo_file_path, orientation)
+                .map_err(ProcessPhotoError::PhotoLoadFailed)
+        }
+    })
+    .await
+    .map_err(|err| match err {
+        BlockingError::Error(err) => err,
+        BlockingError::Canceled => ProcessPhotoError::OperationCancelled,
+    })
+}
+
+async fn detect_faces(
+    db: &crate::db::System,
+    photo_dir_path: &std::path::Path,
+    recognition: &crate::recognition::Service,
+    detector: crate::recognition::Detector,
+    photo_id: i32,
+) -> Result<Vec<crate::recognition::DetectedFace>, ProcessPhotoError> {
+    let image = load_photo(db, photo_dir_path, photo_id).await?;
+
+    recognition
+        .detect_faces_with(image, detector)
+        .await
+        .map_err(ProcessPhotoError::FaceDetectionFailed)
+}
+
+async fn process_photo(context: &Context, photo_id: i32) -> Result<(), ProcessPhotoError> {
+    let faces = detect_faces(
+        &context.db,
+        &context.photo_dir_path,
+        &context.recognition,
+        context.recognition.detector(),
+        photo_id,
+    )
+    .await?;
+
+    // Faces that don't match anyone are left unassigned, to be grouped with each other by
+    // clustering and promoted to a person by hand.
+    let faces: Vec<_> = faces
+        .into_iter()
+        .map(|face| {
+            let matched = context.recognition.identify(&face.face_encoding);
+
+            if let Some(matched) = &matched {
+                log::debug!("  FOUND PERSON {} ({})", matched.person, matched.distance);
+            }
+
+            (face, matched)
+        })
+        .collect();
+
+    let known: Vec<_> = faces
+        .iter()
+        .map(|(face, matched)| known_face(face, matched))
+        .collect();
+
+    // Recorded all at once, in place of any recorded by an attempt that failed after recording
+    // them, so that trying again doesn't record them twice.
+    let (removed_ids, added_ids) = crate::db::model::Appearance::replace_detected(
+        &context.db,
+        photo_id,
+        context.recognition.encoder().to_owned(),
+        faces,
+    )
+    .await
+    .map_err(ProcessPhotoError::RecordAppearanceFailed)?;
+
+    {
+        let mut known_faces = context.recognition.known_faces_mut();
+        for appearance_id in removed_ids.iter() {
+            known_faces.remove(*appearance_id);
+        }
+        for (appearance_id, (face, face_encoding)) in added_ids.into_iter().zip(known) {
+            known_faces.insert(appearance_id, face, face_encoding);
+        }
+    }
+
+    crate::files::remove_all(
+        Vec::new(),
+        removed_ids
+            .iter()
+            .map(|appearance_id| context.avatar_dir_path.join(appearance_id.to_string()))
+            .collect(),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// What matching needs to know about a face that detection found, along with its encoding.
+fn known_face(
+    face: &crate::recognition::DetectedFace,
+    matched: &Option<crate::recognition::Match>,
+) -> (crate::recognition::Face, crate::db::model::FaceEncoding) {
+    (
+        crate::recognition::Face {
+            person: matched.as_ref().map(|matched| matched.person),
+            reference: false,
+            confirmed: false,
+        },
+        face.face_encoding.clone(),
+    )
+}
+
+/// Reprocesses each of the given photos in turn, looking for faces with `detector` and carrying on
+/// past any that fail.
+pub async fn reprocess(
+    db: &crate::db::System,
+    photo_dir_path: &std::path::Path,
+    avatar_dir_path: &std::path::Path,
+    recognition: &crate::recognition::Service,
+    detector: crate::recognition::Detector,
+    photo_ids: Vec<i32>,
+) -> Vec<(i32, Result<FaceChanges, ProcessPhotoError>)> {
+    let mut outcomes = Vec::with_capacity(photo_ids.len());
+    for photo_id in photo_ids.into_iter() {
+        log::debug!("REPROCESS PHOTO {}", photo_id);
+
+        outcomes.push((
+            photo_id,
+            reprocess_photo(
+                db,
+                photo_dir_path,
+                avatar_dir_path,
+                recognition,
+                detector,
+                photo_id,
+            )
+            .await,
+        ));
+    }
+    outcomes
+}
+
+/// Runs face detection over a photo again. Appearances that have been confirmed or made
+/// references, or that were drawn around by hand, are kept as they are, and faces that coincide
+/// with them are ignored. Every other appearance is updated from the face that coincides with it,
+/// or removed if none does, and any faces left over are added.
+async fn reprocess_photo(
+    db: &crate::db::System,
+    photo_dir_path: &std::path::Path,
+    avatar_dir_path: &std::path::Path,
+    recognition: &crate::recognition::Service,
+    detector: crate::recognition::Detector,
+    photo_id: i32,
+) -> Result<FaceChanges, ProcessPhotoError> {
+    let faces = detect_faces(db, photo_dir_path, recognition, detector, photo_id).await?;
+
+    let (kept, mut redetectable): (Vec<_>, Vec<_>) =
+        crate::db::model::Appearance::fetch_all_for_photo(db, photo_id)
+            .await
+            .map_err(ProcessPhotoError::FetchAppearancesFailed)?
+            .into_iter()
+            .map(|(appearance_id, appearance)| {
+                let face_box = crate::recognition::FaceBox {
+                    top: appearance.top,
+                    left: appearance.left,
+                    bottom: appearance.bottom,
+                    right: appearance.right,
+                };
+                (appearance_id, face_box, appearance)
+            })
+            .partition(|(_, _, appearance)| {
+                appearance.confirmed || appearance.reference || appearance.manual
+            });
+
+    let mut changed = Vec::new();
+    let mut added = Vec::new();
+
+    for face in faces.into_iter() {
+        if kept
+            .iter()
+            .any(|(_, face_box, _)| face_box.coincides(&face.face_box))
+        {
+            continue;
+        }
+
+        let matched = recognition.identify(&face.face_encoding);
+        let person_id = matched.as_ref().map(|matched| matched.person);
+
+        let previous = redetectable
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, face_box, _))| face_box.coincides(&face.face_box))
+            .max_by(|(_, (_, a, _)), (_, (_, b, _))| {
+                a.similarity(&face.face_box)
+                    .partial_cmp(&b.similarity(&face.face_box))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(index, _)| index);
+
+        match previous {
+            Some(index) => {
+                let (appearance_id, face_box, appearance) = redetectable.swap_remove(index);
+                if face_box == face.face_box
+                    && appearance.face_encoding == face.face_encoding
+                    && appearance.encoder.as_deref() == Some(recognition.encoder())
+                    && appearance.sharpness.is_some()
+                    && appearance.landmarks.as_deref().unwrap_or(&[]) == &face.landmarks[..]
+                    && appearance.person == person_id
+                {
+                    continue;
+                }
+
+                changed.push((appearance_id, face, matched));
+            }
+            None => added.push((face, matched)),
+        }
+    }
+
+    let removed: Vec<i32> = redetectable
+        .into_iter()
+        .map(|(appearance_id, _, _)| appearance_id)
+        .collect();
+
+    let changed_ids: Vec<i32> = changed
+        .iter()
+        .map(|(appearance_id, _, _)| *appearance_id)
+        .collect();
+    let known: Vec<_> = changed
+        .iter()
+        .map(|(_, face, matched)| known_face(face, matched))
+        .chain(added.iter().map(|(face, matched)| known_face(face, matched)))
+        .collect();
+
+    // Recorded all at once, so that a failure part way through doesn't leave the photo with only
+    // some of what was found.
+    let added_ids = crate::db::model::Appearance::apply_reprocessed(
+        db,
+        photo_id,
+        recognition.encoder().to_owned(),
+        changed,
+        added,
+        removed.clone(),
+    )
+    .await
+    .map_err(ProcessPhotoError::UpdateAppearanceFailed)?;
+
+    {
+        let mut known_faces = recognition.known_faces_mut();
+        for appearance_id in removed.iter() {
+            known_faces.remove(*appearance_id);
+        }
+        for (appearance_id, (face, face_encoding)) in
+            changed_ids.iter().chain(added_ids.iter()).zip(known)
+        {
+            known_faces.insert(*appearance_id, face, face_encoding);
+        }
+    }
+
+    crate::files::remove_all(
+        Vec::new(),
+        removed
+            .iter()
+            .map(|appearance_id| avatar_dir_path.join(appearance_id.to_string()))
+            .collect(),
+    )
+    .await;
+
+    Ok(FaceChanges {
+        added: added_ids,
+        removed,
+        changed: changed_ids,
+    })
+}
+
+/// Starts a task on the current arbiter that re-encodes, from their recorded regions, the faces
+/// of every appearance that wasn't encoded by the model now in use. Those faces are left out of
+/// matching until they have been.
+pub fn spawn_reencoding(context: Context) {
+    actix_web::rt::spawn(reencode(context));
+}
+
+async fn reencode(context: Context) {
+    let photo_ids = match crate::db::model::Appearance::fetch_stale_photo_ids(
+        &context.db,
+        context.recognition.encoder().to_owned(),
+    )
+    .await
+    {
+        Ok(photo_ids) => photo_ids,
+        Err(err) => {
+            log::error!("Failed to fetch the photos to re-encode: {}", err);
+            return;
+        }
+    };
+
+    if !photo_ids.is_empty() {
+        log::info!("Re-encoding the faces in {} photos", photo_ids.len());
+    }
+
+    for photo_id in photo_ids.into_iter() {
+        log::debug!("REENCODE PHOTO {}", photo_id);
+
+        if let Err(err) = reencode_photo(&context, photo_id).await {
+            log::error!(
+                "Failed to re-encode the faces in photo {}: {}",
+                photo_id,
+                describe_error(&err)
+            );
+        }
+    }
+}
+
+/// Re-encodes the faces of a photo's appearances that weren't encoded by the model now in use,
+/// adding them to those that new faces are matched against. Any in which the model can't make
+/// out a face are left as they are.
+async fn reencode_photo(context: &Context, photo_id: i32) -> Result<(), ProcessPhotoError> {
+    let encoder = context.recognition.encoder();
+
+    let appearances: Vec<_> =
+        crate::db::model::Appearance::fetch_all_for_photo(&context.db, photo_id)
+            .await
+            .map_err(ProcessPhotoError::FetchAppearancesFailed)?
+            .into_iter()
+            .filter(|(_, appearance)| appearance.encoder.as_deref() != Some(encoder))
+            .collect();
+
+    if appearances.is_empty() {
+        return Ok(());
+    }
+
+    let image = load_photo(&context.db, &context.photo_dir_path, photo_id).await?;
+
+    let faces = context
+        .recognition
+        .encode_faces(
+            image,
+            appearances
+                .iter()
+                .map(|(_, appearance)| crate::recognition::FaceBox {
+                    top: appearance.top,
+                    left: appearance.left,
+                    bottom: appearance.bottom,
+                    right: appearance.right,
+                })
+                .collect(),
+        )
+        .await
+        .map_err(ProcessPhotoError::FaceEncodingFailed)?;
+
+    for ((appearance_id, _), face) in appearances.into_iter().zip(faces) {
+        let face = match face {
+            Some(face) => face,
+            None => {
+                log::warn!("No face found for appearance {} to re-encode", appearance_id);
+                continue;
+            }
+        };
+
+        match crate::db::model::Appearance::reencode(
+            &context.db,
+            appearance_id,
+            face.face_encoding.clone(),
+            encoder.to_owned(),
+            face.landmarks,
+            face.quality,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(crate::db::UpdateQueryError::NoSuchRecord) => continue,
+            Err(err) => return Err(ProcessPhotoError::UpdateAppearanceFailed(err)),
+        }
+
+        // Fetched afresh, as it may have been reassigned while its face was being encoded.
+        if let Some(appearance) = crate::db::model::Appearance::fetch(&context.db, appearance_id)
+            .await
+            .map_err(ProcessPhotoError::FetchAppearancesFailed)?
+        {
+            context.recognition.known_faces_mut().insert(
+                appearance_id,
+                crate::recognition::Face {
+                    person: appearance.person,
+                    reference: appearance.reference,
+                    confirmed: appearance.confirmed,
+                },
+                face.face_encoding,
+            );
+        }
+    }
+
+    Ok(())
+}
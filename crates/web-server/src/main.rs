@@ -6,8 +6,13 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 mod api;
+mod avatar;
 mod db;
+mod files;
 mod image_ext;
+mod jobs;
+mod recognition;
+mod trash;
 
 embed_migrations!();
 
@@ -15,6 +20,12 @@ embed_migrations!();
 enum ServerError {
     #[error("Failed to connect to database")]
     DatabaseInitError(#[from] db::NewSystemError),
+    #[error("Failed to start the face recognition service")]
+    RecognitionInitError(#[from] recognition::NewServiceError),
+    #[error("Failed to requeue interrupted jobs")]
+    JobQueueInitError(#[source] db::QueryError),
+    #[error("Failed to find photos to reprocess")]
+    ReprocessFetchError(#[source] db::QueryError),
     #[error("Failed to bind listen socket")]
     BindError(#[source] std::io::Error),
     #[error("Failed to run server")]
@@ -65,33 +76,84 @@ async fn get_thumb(
     )?)
 }
 
+#[actix_web::get("/static/trash/thumbs/{month}/{file_name}")]
+async fn get_trashed_thumb(
+    actix_web::web::Path((month, file_name)): actix_web::web::Path<(String, String)>,
+    trash_dir_path: actix_web::web::Data<api::TrashDirPath>,
+) -> actix_web::Result<actix_files::NamedFile> {
+    Ok(actix_files::NamedFile::open(
+        trash_dir_path.join("thumbs").join(month).join(file_name),
+    )?)
+}
+
 async fn run(
     db_file_path: std::path::PathBuf,
     photo_file_path: std::path::PathBuf,
     thumb_file_path: std::path::PathBuf,
+    avatar_file_path: std::path::PathBuf,
+    trash_file_path: std::path::PathBuf,
+    trash_retention: chrono::Duration,
+    default_time_zone: chrono_tz::Tz,
     static_dir_path: std::path::PathBuf,
-    face_landmark_predictor_model_file_path: std::path::PathBuf,
-    face_encoder_model_file_path: std::path::PathBuf,
+    recognition_backend: recognition::Backend,
+    face_recognition_thread_count: usize,
+    face_matcher: recognition::Matcher,
+    face_detector: recognition::Detector,
+    job_worker_count: usize,
+    job_max_attempts: i32,
     host: &str,
     port: u16,
 ) -> Result<(), ServerError> {
     let db = db::System::new(&db_file_path)?;
 
+    let recognition = recognition::Service::new(
+        &db,
+        recognition_backend,
+        face_recognition_thread_count,
+        face_matcher,
+        face_detector,
+    )
+    .await?;
+
+    db::model::Job::requeue_running(&db)
+        .await
+        .map_err(ServerError::JobQueueInitError)?;
+
+    let job_context = jobs::Context {
+        db: db.clone(),
+        photo_dir_path: photo_file_path.clone(),
+        avatar_dir_path: avatar_file_path.clone(),
+        recognition: recognition.clone(),
+        max_attempts: job_max_attempts,
+    };
+
+    jobs::spawn_reencoding(job_context.clone());
+    jobs::spawn(job_context, job_worker_count);
+
+    trash::spawn_sweeper(trash::Context {
+        db: db.clone(),
+        recognition: recognition.clone(),
+        photo_dir_path: photo_file_path.clone(),
+        thumb_dir_path: thumb_file_path.clone(),
+        avatar_dir_path: avatar_file_path.clone(),
+        trash_dir_path: trash_file_path.clone(),
+        retention: trash_retention,
+    });
+
     actix_web::HttpServer::new(move || {
         actix_web::App::new()
             .data(db.clone())
+            .data(recognition.clone())
             .data(StaticDirPath::from(static_dir_path.clone()))
             .data(api::PhotoDirPath::from(photo_file_path.clone()))
             .data(api::ThumbDirPath::from(thumb_file_path.clone()))
-            .data(api::FaceLandmarkPredictorModelFilePath::from(
-                face_landmark_predictor_model_file_path.clone(),
-            ))
-            .data(api::FaceEncoderModelFilePath::from(
-                face_encoder_model_file_path.clone(),
-            ))
+            .data(api::AvatarDirPath::from(avatar_file_path.clone()))
+            .data(api::TrashDirPath::from(trash_file_path.clone()))
+            .data(api::DefaultTimeZone::from(default_time_zone))
             .service(actix_web::web::scope("/api").configure(api::configure))
             .service(get_photo)
             .service(get_thumb)
+            .service(get_trashed_thumb)
             .service(actix_files::Files::new("/static", static_dir_path.clone()))
             .default_service(actix_web::web::to(index))
     })
@@ -102,6 +164,111 @@ async fn run(
     .map_err(|err| ServerError::RunError(err))
 }
 
+async fn reprocess(
+    db_file_path: std::path::PathBuf,
+    photo_file_path: std::path::PathBuf,
+    avatar_file_path: std::path::PathBuf,
+    recognition_backend: recognition::Backend,
+    face_recognition_thread_count: usize,
+    face_matcher: recognition::Matcher,
+    face_detector: recognition::Detector,
+    photo_ids: Vec<i32>,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    without_appearances: bool,
+) -> Result<(), ServerError> {
+    let db = db::System::new(&db_file_path)?;
+
+    let recognition = recognition::Service::new(
+        &db,
+        recognition_backend,
+        face_recognition_thread_count,
+        face_matcher,
+        face_detector,
+    )
+    .await?;
+
+    let photo_ids = db::model::Photo::fetch_ids(&db, photo_ids, from, to, without_appearances)
+        .await
+        .map_err(ServerError::ReprocessFetchError)?;
+
+    let outcomes = jobs::reprocess(
+        &db,
+        &photo_file_path,
+        &avatar_file_path,
+        &recognition,
+        face_detector,
+        photo_ids,
+    )
+    .await;
+
+    for (photo_id, outcome) in outcomes.into_iter() {
+        match outcome {
+            Ok(changes) => println!(
+                "Photo {}: added {:?}, removed {:?}, changed {:?}",
+                photo_id, changes.added, changes.removed, changes.changed
+            ),
+            Err(err) => println!("Photo {}: failed: {}", photo_id, jobs::describe_error(&err)),
+        }
+    }
+
+    Ok(())
+}
+
+enum FaceRecognizer {
+    Dlib,
+    Fake,
+}
+
+impl std::str::FromStr for FaceRecognizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dlib" => Ok(Self::Dlib),
+            "fake" => Ok(Self::Fake),
+            _ => Err(format!("Unknown face recognizer '{}'", s)),
+        }
+    }
+}
+
+enum FaceMatchStrategy {
+    Nearest,
+    Vote,
+    Centroid,
+}
+
+impl std::str::FromStr for FaceMatchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "vote" => Ok(Self::Vote),
+            "centroid" => Ok(Self::Centroid),
+            _ => Err(format!("Unknown face match strategy '{}'", s)),
+        }
+    }
+}
+
+#[derive(structopt::StructOpt)]
+enum Command {
+    /// Runs face detection over existing photos again and reports the appearances that were
+    /// added, removed and changed, rather than starting the server. Every photo is reprocessed
+    /// unless some are picked. The server keeps its own copy of the faces, so it should be stopped
+    /// while this runs.
+    Reprocess {
+        #[structopt(long = "photo")]
+        photos: Vec<i32>,
+        #[structopt(long)]
+        from: Option<chrono::NaiveDate>,
+        #[structopt(long)]
+        to: Option<chrono::NaiveDate>,
+        #[structopt(long)]
+        without_appearances: bool,
+    },
+}
+
 #[derive(structopt::StructOpt)]
 struct CliOptions {
     #[structopt(long, default_value = "/var/lib/photos/photos.db")]
@@ -112,6 +279,18 @@ struct CliOptions {
     photo_file_path: std::path::PathBuf,
     #[structopt(long, default_value = "/var/lib/photos/thumbs")]
     thumb_file_path: std::path::PathBuf,
+    #[structopt(long, default_value = "/var/lib/photos/avatars")]
+    avatar_file_path: std::path::PathBuf,
+    #[structopt(long, default_value = "/var/lib/photos/trash")]
+    trash_file_path: std::path::PathBuf,
+    /// How many days photos are kept in the trash before they're purged for good.
+    #[structopt(long, default_value = "30")]
+    trash_retention_days: i64,
+    /// The IANA name of the time zone that photos are taken to be in when their cameras didn't
+    /// record an offset from UTC, and that they're grouped into days in for viewers who don't say
+    /// which they're in.
+    #[structopt(long, default_value = "UTC")]
+    time_zone: chrono_tz::Tz,
     #[structopt(
         long,
         default_value = "/usr/local/share/photos/shape_predictor_68_face_landmarks.dat"
@@ -122,10 +301,45 @@ struct CliOptions {
         default_value = "/usr/local/share/photos/dlib_face_recognition_resnet_model_v1.dat"
     )]
     face_encoder_model_file_path: std::path::PathBuf,
+    #[structopt(long, default_value = "dlib", possible_values = &["dlib", "fake"])]
+    face_recognizer: FaceRecognizer,
+    #[structopt(long, required_if("face-recognizer", "fake"))]
+    fake_face_fixture_file_path: Option<std::path::PathBuf>,
+    #[structopt(long, default_value = "1")]
+    face_recognition_thread_count: usize,
+    #[structopt(long, default_value = "0.6")]
+    face_match_tolerance: f64,
+    #[structopt(
+        long,
+        default_value = "nearest",
+        possible_values = &["nearest", "vote", "centroid"]
+    )]
+    face_match_strategy: FaceMatchStrategy,
+    #[structopt(long, default_value = "5")]
+    face_match_neighbour_count: usize,
+    /// Passes over faces whose boxes are fewer pixels across than this.
+    #[structopt(long)]
+    min_face_pixels: Option<u32>,
+    /// Passes over faces whose boxes are less than this fraction of the photo across.
+    #[structopt(long)]
+    min_face_fraction: Option<f64>,
+    /// How many times photos are doubled in size for face detection, to find smaller faces.
+    #[structopt(long, default_value = "0")]
+    face_detection_upsample: u32,
+    /// Detects faces in a copy of each photo downscaled to at most this many pixels along its
+    /// longest side, which is quicker but may miss smaller faces.
+    #[structopt(long)]
+    face_detection_max_image_size: Option<u32>,
+    #[structopt(long, default_value = "1")]
+    job_worker_count: usize,
+    #[structopt(long, default_value = "3")]
+    job_max_attempts: i32,
     #[structopt(short, long, default_value = "0.0.0.0")]
     host: String,
     #[structopt(short, long, default_value = "80")]
     port: u16,
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
 #[actix_web::main]
@@ -136,18 +350,81 @@ async fn main() {
 
     let cli_options = CliOptions::from_args();
 
-    if let Err(error) = run(
-        cli_options.db_file_path,
-        cli_options.photo_file_path,
-        cli_options.thumb_file_path,
-        cli_options.static_dir_path,
-        cli_options.face_landmark_predictor_model_file_path,
-        cli_options.face_encoder_model_file_path,
-        &cli_options.host,
-        cli_options.port,
-    )
-    .await
-    {
+    let recognition_backend = match cli_options.face_recognizer {
+        FaceRecognizer::Dlib => recognition::Backend::Dlib {
+            face_landmark_predictor_model_file_path: cli_options
+                .face_landmark_predictor_model_file_path,
+            face_encoder_model_file_path: cli_options.face_encoder_model_file_path,
+        },
+        FaceRecognizer::Fake => recognition::Backend::Fake {
+            fixture_file_path: cli_options.fake_face_fixture_file_path.unwrap(),
+        },
+    };
+
+    let face_matcher = recognition::Matcher {
+        tolerance: cli_options.face_match_tolerance,
+        strategy: match cli_options.face_match_strategy {
+            FaceMatchStrategy::Nearest => recognition::Strategy::Nearest,
+            FaceMatchStrategy::Vote => recognition::Strategy::Vote {
+                neighbour_count: cli_options.face_match_neighbour_count,
+            },
+            FaceMatchStrategy::Centroid => recognition::Strategy::Centroid,
+        },
+    };
+
+    let face_detector = recognition::Detector {
+        min_face_pixels: cli_options.min_face_pixels,
+        min_face_fraction: cli_options.min_face_fraction,
+        upsample: cli_options.face_detection_upsample,
+        max_image_size: cli_options.face_detection_max_image_size,
+    };
+
+    let result = match cli_options.command {
+        None => {
+            run(
+                cli_options.db_file_path,
+                cli_options.photo_file_path,
+                cli_options.thumb_file_path,
+                cli_options.avatar_file_path,
+                cli_options.trash_file_path,
+                chrono::Duration::days(cli_options.trash_retention_days),
+                cli_options.time_zone,
+                cli_options.static_dir_path,
+                recognition_backend,
+                cli_options.face_recognition_thread_count,
+                face_matcher,
+                face_detector,
+                cli_options.job_worker_count,
+                cli_options.job_max_attempts,
+                &cli_options.host,
+                cli_options.port,
+            )
+            .await
+        }
+        Some(Command::Reprocess {
+            photos,
+            from,
+            to,
+            without_appearances,
+        }) => {
+            reprocess(
+                cli_options.db_file_path,
+                cli_options.photo_file_path,
+                cli_options.avatar_file_path,
+                recognition_backend,
+                cli_options.face_recognition_thread_count,
+                face_matcher,
+                face_detector,
+                photos,
+                from,
+                to,
+                without_appearances,
+            )
+            .await
+        }
+    };
+
+    if let Err(error) = result {
         use std::error::Error;
 
         println!("Error: {}", error);
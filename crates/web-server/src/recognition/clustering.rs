@@ -0,0 +1,72 @@
+//! Groups faces that likely belong to the same person using Chinese Whispers: every face starts
+//! in its own cluster, and each pass moves every face into the cluster most common amongst the
+//! faces within tolerance of it, until nothing moves.
+
+const MAX_ITERATIONS: usize = 100;
+
+/// Clusters the given `(appearance, encoding)` pairs, returning the appearance ids in each
+/// cluster. Clusters are ordered largest first, and the ids within each are in ascending order.
+pub fn cluster(
+    faces: &[(i32, crate::db::model::FaceEncoding)],
+    tolerance: f64,
+) -> Vec<Vec<i32>> {
+    let neighbours: Vec<Vec<usize>> = faces
+        .iter()
+        .enumerate()
+        .map(|(i, (_, face_encoding))| {
+            faces
+                .iter()
+                .enumerate()
+                .filter(|(j, (_, other))| *j != i && face_encoding.distance(other) < tolerance)
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+
+    let mut labels: Vec<usize> = (0..faces.len()).collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+
+        for (i, neighbours) in neighbours.iter().enumerate() {
+            let mut counts = std::collections::BTreeMap::new();
+            for j in neighbours.iter() {
+                *counts.entry(labels[*j]).or_insert(0usize) += 1;
+            }
+
+            // Ties go to the lowest label so the result doesn't depend on hash ordering.
+            let best = counts
+                .into_iter()
+                .fold(None, |best: Option<(usize, usize)>, (label, count)| match best {
+                    Some((_, best_count)) if best_count >= count => best,
+                    _ => Some((label, count)),
+                });
+
+            if let Some((label, _)) = best {
+                if labels[i] != label {
+                    labels[i] = label;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut clusters = std::collections::BTreeMap::<usize, Vec<i32>>::new();
+    for (label, (appearance, _)) in labels.into_iter().zip(faces.iter()) {
+        clusters.entry(label).or_default().push(*appearance);
+    }
+
+    let mut clusters: Vec<Vec<i32>> = clusters
+        .into_iter()
+        .map(|(_, mut appearances)| {
+            appearances.sort_unstable();
+            appearances
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+    clusters
+}
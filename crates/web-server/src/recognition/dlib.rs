@@ -0,0 +1,103 @@
+pub struct DlibFaceRecognizer {
+    face_detector: dlib_face_recognition::FaceDetector,
+    face_landmark_predictor: dlib_face_recognition::LandmarkPredictor,
+    face_encoder: dlib_face_recognition::FaceEncoderNetwork,
+}
+
+impl DlibFaceRecognizer {
+    pub fn new(
+        face_landmark_predictor_model_file_path: &std::path::Path,
+        face_encoder_model_file_path: &std::path::Path,
+    ) -> Result<Self, super::NewServiceError> {
+        Ok(Self {
+            face_detector: dlib_face_recognition::FaceDetector::new(),
+            face_landmark_predictor: dlib_face_recognition::LandmarkPredictor::new(
+                face_landmark_predictor_model_file_path,
+            )
+            .map_err(super::NewServiceError::FaceLandmarkPredictorInitFailed)?,
+            face_encoder: dlib_face_recognition::FaceEncoderNetwork::new(
+                face_encoder_model_file_path,
+            )
+            .map_err(super::NewServiceError::FaceEncoderInitFailed)?,
+        })
+    }
+}
+
+impl super::FaceRecognizer for DlibFaceRecognizer {
+    fn detect_faces(&mut self, image: &image::RgbImage, scale: f64) -> Vec<super::FaceBox> {
+        use dlib_face_recognition::FaceDetectorTrait;
+
+        let image_matrix = if scale == 1.0 {
+            dlib_face_recognition::ImageMatrix::from_image(image)
+        } else {
+            let scaled_dimension = |dimension: u32| {
+                std::cmp::max((dimension as f64 * scale).round() as u32, 1)
+            };
+            dlib_face_recognition::ImageMatrix::from_image(&image::imageops::resize(
+                image,
+                scaled_dimension(image.width()),
+                scaled_dimension(image.height()),
+                image::imageops::FilterType::Triangle,
+            ))
+        };
+
+        self.face_detector
+            .face_locations(&image_matrix)
+            .iter()
+            .map(|face_location| {
+                super::FaceBox {
+                    top: face_location.top as i32,
+                    left: face_location.left as i32,
+                    bottom: face_location.bottom as i32,
+                    right: face_location.right as i32,
+                }
+                .unscaled(scale)
+            })
+            .collect()
+    }
+
+    fn encode_faces(
+        &mut self,
+        image: &image::RgbImage,
+        face_boxes: &[super::FaceBox],
+    ) -> Vec<Option<super::EncodedFace>> {
+        use dlib_face_recognition::{FaceEncoderTrait, LandmarkPredictorTrait};
+
+        let image_matrix = dlib_face_recognition::ImageMatrix::from_image(image);
+
+        face_boxes
+            .iter()
+            .map(|face_box| {
+                let landmarks = self.face_landmark_predictor.face_landmarks(
+                    &image_matrix,
+                    &dlib_face_recognition::Rectangle {
+                        top: face_box.top as i64,
+                        left: face_box.left as i64,
+                        bottom: face_box.bottom as i64,
+                        right: face_box.right as i64,
+                    },
+                );
+                if landmarks.is_empty() {
+                    return None;
+                }
+
+                self.face_encoder
+                    .get_face_encodings(&image_matrix, std::slice::from_ref(&landmarks), 0)
+                    .get(0)
+                    .map(|face_encoding| {
+                        let mut elements = [0f64; crate::db::model::face_encoding::ELEMENT_COUNT];
+                        elements.copy_from_slice(&face_encoding.to_elements());
+                        super::EncodedFace {
+                            face_encoding: crate::db::model::FaceEncoding::new(elements),
+                            landmarks: crate::db::model::FaceLandmarks::new(
+                                landmarks
+                                    .iter()
+                                    .map(|point| (point.x() as i32, point.y() as i32))
+                                    .collect(),
+                            ),
+                        }
+                    })
+            })
+            .collect()
+    }
+}
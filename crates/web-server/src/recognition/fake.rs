@@ -0,0 +1,251 @@
+//! A deterministic stand-in for the dlib backend that reports the faces described by a fixture
+//! file rather than looking at the image, so ingestion can be exercised without the models.
+//!
+//! The fixture is a JSON object keyed by the digest of the image's (reoriented) RGB pixels, in the
+//! same form used for photo file names. Each entry lists the faces in that image:
+//!
+//! ```json
+//! {
+//!   "<digest>": [
+//!     { "top": 10, "left": 20, "bottom": 110, "right": 120, "identity": "alice" },
+//!     { "top": 15, "left": 200, "bottom": 95, "right": 280, "encoding": [0.1, ...] }
+//!   ]
+//! }
+//! ```
+//!
+//! Faces given an `identity` are encoded from a hash of it, so faces sharing an identity are
+//! always an exact match and faces with different identities never match. A face may also list
+//! its `landmarks` as `[x, y]` pairs in the 68 point scheme.
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum FixtureEncoding {
+    Identity(String),
+    Encoding(Vec<f64>),
+}
+
+#[derive(serde::Deserialize)]
+struct FixtureFace {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    #[serde(flatten)]
+    encoding: FixtureEncoding,
+    #[serde(default)]
+    landmarks: Vec<(i32, i32)>,
+}
+
+impl FixtureFace {
+    fn face_box(&self) -> super::FaceBox {
+        super::FaceBox {
+            top: self.top,
+            left: self.left,
+            bottom: self.bottom,
+            right: self.right,
+        }
+    }
+
+    fn face_encoding(&self) -> crate::db::model::FaceEncoding {
+        use crate::db::model::face_encoding::ELEMENT_COUNT;
+
+        let mut elements = [0f64; ELEMENT_COUNT];
+        match &self.encoding {
+            FixtureEncoding::Identity(identity) => {
+                const CHUNK_SIZE: usize = 16;
+                for (c, chunk) in elements.chunks_mut(CHUNK_SIZE).enumerate() {
+                    let digest = md5::compute(format!("{}:{}", identity, c));
+                    for (element, byte) in chunk.iter_mut().zip(digest.iter()) {
+                        *element = *byte as f64 / 255.0;
+                    }
+                }
+            }
+            FixtureEncoding::Encoding(encoding) => {
+                for (element, value) in elements.iter_mut().zip(encoding.iter()) {
+                    *element = *value;
+                }
+            }
+        }
+
+        crate::db::model::FaceEncoding::new(elements)
+    }
+}
+
+pub struct FakeFaceRecognizer {
+    fixture: std::collections::HashMap<String, Vec<FixtureFace>>,
+}
+
+impl FakeFaceRecognizer {
+    pub fn load(fixture_file_path: &std::path::Path) -> Result<Self, super::NewServiceError> {
+        let fixture = serde_json::from_reader(std::io::BufReader::new(
+            std::fs::File::open(fixture_file_path)
+                .map_err(super::NewServiceError::FakeFixtureOpenFailed)?,
+        ))
+        .map_err(super::NewServiceError::FakeFixtureParseFailed)?;
+
+        Ok(Self { fixture })
+    }
+
+    fn faces(&self, image: &image::RgbImage) -> &[FixtureFace] {
+        let digest = crate::db::model::Digest::compute(image.as_raw()).to_string();
+        self.fixture
+            .get(&digest)
+            .map(|faces| faces.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+impl super::FaceRecognizer for FakeFaceRecognizer {
+    /// The fixture's boxes are already in the original image, so the scale is ignored.
+    fn detect_faces(&mut self, image: &image::RgbImage, _scale: f64) -> Vec<super::FaceBox> {
+        self.faces(image)
+            .iter()
+            .map(|face| face.face_box())
+            .collect()
+    }
+
+    fn encode_faces(
+        &mut self,
+        image: &image::RgbImage,
+        face_boxes: &[super::FaceBox],
+    ) -> Vec<Option<super::EncodedFace>> {
+        let faces = self.faces(image);
+        face_boxes
+            .iter()
+            .map(|face_box| {
+                // Pick the fixture face that overlaps the requested box the most, so that boxes
+                // which don't exactly match a detection still encode consistently.
+                faces
+                    .iter()
+                    .map(|face| (face.face_box().overlap(face_box), face))
+                    .filter(|(overlap, _)| *overlap > 0)
+                    .max_by_key(|(overlap, _)| *overlap)
+                    .map(|(_, face)| super::EncodedFace {
+                        face_encoding: face.face_encoding(),
+                        landmarks: crate::db::model::FaceLandmarks::new(face.landmarks.clone()),
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::recognition::{FaceBox, FaceRecognizer};
+    use super::FakeFaceRecognizer;
+
+    fn image(seed: u8) -> image::RgbImage {
+        image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8, y as u8, seed]))
+    }
+
+    fn recognizer(fixture: serde_json::Value) -> FakeFaceRecognizer {
+        FakeFaceRecognizer {
+            fixture: serde_json::from_value(fixture).unwrap(),
+        }
+    }
+
+    fn digest(image: &image::RgbImage) -> String {
+        crate::db::model::Digest::compute(image.as_raw()).to_string()
+    }
+
+    #[test]
+    fn reports_the_faces_listed_for_an_image() {
+        let (image, other_image) = (image(0), image(1));
+        let mut recognizer = recognizer(serde_json::json!({
+            digest(&image): [
+                { "top": 1, "left": 2, "bottom": 11, "right": 12, "identity": "alice" },
+                { "top": 20, "left": 30, "bottom": 40, "right": 50, "identity": "bob" },
+            ],
+        }));
+
+        assert_eq!(
+            recognizer.detect_faces(&image, 1.0),
+            vec![
+                FaceBox {
+                    top: 1,
+                    left: 2,
+                    bottom: 11,
+                    right: 12
+                },
+                FaceBox {
+                    top: 20,
+                    left: 30,
+                    bottom: 40,
+                    right: 50
+                },
+            ]
+        );
+        assert!(recognizer.detect_faces(&other_image, 1.0).is_empty());
+    }
+
+    #[test]
+    fn encodes_faces_by_identity() {
+        let (image, other_image) = (image(0), image(1));
+        let mut recognizer = recognizer(serde_json::json!({
+            digest(&image): [
+                { "top": 0, "left": 0, "bottom": 10, "right": 10, "identity": "alice" },
+                { "top": 0, "left": 20, "bottom": 10, "right": 30, "identity": "bob" },
+            ],
+            digest(&other_image): [
+                { "top": 30, "left": 30, "bottom": 40, "right": 40, "identity": "alice" },
+            ],
+        }));
+
+        let face_boxes = recognizer.detect_faces(&image, 1.0);
+        let encodings = recognizer.encode_faces(&image, &face_boxes);
+        let other_face_boxes = recognizer.detect_faces(&other_image, 1.0);
+        let other_encodings = recognizer.encode_faces(&other_image, &other_face_boxes);
+
+        let (alice, bob) = (
+            &encodings[0].as_ref().unwrap().face_encoding,
+            &encodings[1].as_ref().unwrap().face_encoding,
+        );
+        assert_eq!(
+            other_encodings[0]
+                .as_ref()
+                .unwrap()
+                .face_encoding
+                .distance(alice),
+            0.0
+        );
+        assert!(alice.distance(bob) > 0.6);
+    }
+
+    #[test]
+    fn encodes_the_face_that_a_box_overlaps_most() {
+        let image = image(0);
+        let mut recognizer = recognizer(serde_json::json!({
+            digest(&image): [
+                { "top": 0, "left": 0, "bottom": 10, "right": 10, "identity": "alice" },
+                { "top": 0, "left": 20, "bottom": 10, "right": 30, "identity": "bob" },
+            ],
+        }));
+
+        let face_boxes = recognizer.detect_faces(&image, 1.0);
+        let bob = recognizer.encode_faces(&image, &face_boxes[1..])[0]
+            .as_ref()
+            .unwrap()
+            .face_encoding
+            .clone();
+        let encodings = recognizer.encode_faces(
+            &image,
+            &[
+                FaceBox {
+                    top: 2,
+                    left: 8,
+                    bottom: 12,
+                    right: 28,
+                },
+                FaceBox {
+                    top: 20,
+                    left: 0,
+                    bottom: 30,
+                    right: 10,
+                },
+            ],
+        );
+
+        assert_eq!(encodings[0].as_ref().unwrap().face_encoding.distance(&bob), 0.0);
+        assert!(encodings[1].is_none());
+    }
+}
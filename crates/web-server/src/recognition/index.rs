@@ -0,0 +1,501 @@
+//! A vantage-point tree over face encodings. Each node splits the faces beneath it by whether
+//! they're closer to its own face than the median distance, which lets a search skip any subtree
+//! that the triangle inequality says can't hold anything close enough.
+//!
+//! Faces added since the tree was last built are kept in a list that's searched linearly, and
+//! removed faces are left in place to divide the tree but are otherwise skipped, until either is
+//! large enough to be worth rebuilding the tree for.
+
+const MIN_REBUILD_SIZE: usize = 64;
+
+struct Slot<T> {
+    key: i32,
+    face_encoding: crate::db::model::FaceEncoding,
+    value: Option<T>,
+}
+
+struct Node {
+    slot: usize,
+    radius: f64,
+    inside: Option<usize>,
+    outside: Option<usize>,
+}
+
+pub struct Neighbour<'a, T> {
+    pub key: i32,
+    pub value: &'a T,
+    pub distance: f64,
+}
+
+pub struct FaceIndex<T> {
+    slots: Vec<Slot<T>>,
+    positions: std::collections::HashMap<i32, usize>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+    indexed: usize,
+    removed: usize,
+}
+
+impl<T> FaceIndex<T> {
+    pub fn new<I>(faces: I) -> Self
+    where
+        I: IntoIterator<Item = (i32, crate::db::model::FaceEncoding, T)>,
+    {
+        let mut index = Self {
+            slots: Vec::new(),
+            positions: std::collections::HashMap::new(),
+            nodes: Vec::new(),
+            root: None,
+            indexed: 0,
+            removed: 0,
+        };
+
+        for (key, face_encoding, value) in faces.into_iter() {
+            index.push(key, face_encoding, value);
+        }
+        index.rebuild();
+
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Adds a face, replacing any that was already recorded under `key`.
+    pub fn insert(&mut self, key: i32, face_encoding: crate::db::model::FaceEncoding, value: T) {
+        self.take(key);
+        self.push(key, face_encoding, value);
+        self.rebuild_if_stale();
+    }
+
+    pub fn remove(&mut self, key: i32) -> Option<(crate::db::model::FaceEncoding, T)> {
+        let removed = self.take(key);
+        self.rebuild_if_stale();
+        removed
+    }
+
+    pub fn get(&self, key: i32) -> Option<(&crate::db::model::FaceEncoding, &T)> {
+        let slot = &self.slots[*self.positions.get(&key)?];
+        slot.value
+            .as_ref()
+            .map(|value| (&slot.face_encoding, value))
+    }
+
+    pub fn get_mut(&mut self, key: i32) -> Option<&mut T> {
+        let position = *self.positions.get(&key)?;
+        self.slots[position].value.as_mut()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &crate::db::model::FaceEncoding, &T)> {
+        self.slots.iter().filter_map(|slot| {
+            slot.value
+                .as_ref()
+                .map(|value| (slot.key, &slot.face_encoding, value))
+        })
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+
+    /// Finds the faces closest to `face_encoding` that are nearer than `max_distance`, closest
+    /// first. `group` picks which group a face counts towards, or `None` to skip it, and only the
+    /// closest face of each group is kept, up to `count` groups.
+    pub fn nearest<G>(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        count: usize,
+        max_distance: f64,
+        group: G,
+    ) -> Vec<Neighbour<T>>
+    where
+        G: Fn(i32, &T) -> Option<i32>,
+    {
+        let mut search = Search {
+            face_encoding,
+            count,
+            max_distance,
+            group,
+            found: Vec::new(),
+        };
+
+        if count > 0 {
+            if let Some(root) = self.root {
+                self.search(root, &mut search);
+            }
+
+            for slot in self.slots[self.indexed..].iter() {
+                search.consider(slot);
+            }
+        }
+
+        search
+            .found
+            .into_iter()
+            .map(|(_, neighbour)| neighbour)
+            .collect()
+    }
+
+    fn search<'a, G>(&'a self, node: usize, search: &mut Search<'_, 'a, T, G>)
+    where
+        G: Fn(i32, &T) -> Option<i32>,
+    {
+        let node = &self.nodes[node];
+        let vantage = &self.slots[node.slot];
+        search.consider(vantage);
+
+        let distance = vantage.face_encoding.distance(search.face_encoding);
+
+        // Look on the query's own side first, as that's where the closest faces likely are, so
+        // the bound is as tight as possible by the time the other side is checked.
+        let children = if distance < node.radius {
+            [(node.inside, true), (node.outside, false)]
+        } else {
+            [(node.outside, false), (node.inside, true)]
+        };
+
+        for (child, inside) in children.iter() {
+            if let Some(child) = child {
+                let reachable = if *inside {
+                    distance - node.radius < search.bound()
+                } else {
+                    node.radius - distance < search.bound()
+                };
+
+                if reachable {
+                    self.search(*child, search);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, key: i32, face_encoding: crate::db::model::FaceEncoding, value: T) {
+        self.positions.insert(key, self.slots.len());
+        self.slots.push(Slot {
+            key,
+            face_encoding,
+            value: Some(value),
+        });
+    }
+
+    fn take(&mut self, key: i32) -> Option<(crate::db::model::FaceEncoding, T)> {
+        let position = self.positions.remove(&key)?;
+        let slot = &mut self.slots[position];
+        let value = slot.value.take()?;
+        if position < self.indexed {
+            self.removed += 1;
+        }
+        Some((slot.face_encoding.clone(), value))
+    }
+
+    fn rebuild_if_stale(&mut self) {
+        let unindexed = self.slots.len() - self.indexed;
+        if unindexed > std::cmp::max(MIN_REBUILD_SIZE, self.indexed / 4)
+            || self.removed > std::cmp::max(MIN_REBUILD_SIZE, self.indexed / 4)
+        {
+            self.rebuild();
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.slots.retain(|slot| slot.value.is_some());
+        self.positions = self
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(position, slot)| (slot.key, position))
+            .collect();
+
+        let mut positions: Vec<usize> = (0..self.slots.len()).collect();
+        self.nodes = Vec::with_capacity(self.slots.len());
+        self.root = build(&self.slots, &mut self.nodes, &mut positions);
+        self.indexed = self.slots.len();
+        self.removed = 0;
+    }
+}
+
+struct Search<'q, 'a, T, G> {
+    face_encoding: &'q crate::db::model::FaceEncoding,
+    count: usize,
+    max_distance: f64,
+    group: G,
+    found: Vec<(i32, Neighbour<'a, T>)>,
+}
+
+impl<'q, 'a, T, G> Search<'q, 'a, T, G>
+where
+    G: Fn(i32, &T) -> Option<i32>,
+{
+    /// Only faces closer than this can still make a difference to the result.
+    fn bound(&self) -> f64 {
+        match self.found.last() {
+            Some((_, neighbour)) if self.found.len() >= self.count => neighbour.distance,
+            _ => self.max_distance,
+        }
+    }
+
+    fn consider(&mut self, slot: &'a Slot<T>) {
+        let value = match &slot.value {
+            Some(value) => value,
+            None => return,
+        };
+
+        let group = match (self.group)(slot.key, value) {
+            Some(group) => group,
+            None => return,
+        };
+
+        let distance = slot.face_encoding.distance(self.face_encoding);
+        if distance >= self.bound() {
+            return;
+        }
+
+        if let Some(position) = self.found.iter().position(|(found, _)| *found == group) {
+            if self.found[position].1.distance <= distance {
+                return;
+            }
+            self.found.remove(position);
+        }
+
+        let position = self
+            .found
+            .iter()
+            .position(|(_, neighbour)| neighbour.distance > distance)
+            .unwrap_or(self.found.len());
+        self.found.insert(
+            position,
+            (
+                group,
+                Neighbour {
+                    key: slot.key,
+                    value,
+                    distance,
+                },
+            ),
+        );
+        self.found.truncate(self.count);
+    }
+}
+
+/// Builds a subtree over the slots at `positions`, returning its root.
+fn build<T>(
+    slots: &[Slot<T>],
+    nodes: &mut Vec<Node>,
+    positions: &mut [usize],
+) -> Option<usize> {
+    let (vantage, rest) = positions.split_first_mut()?;
+    let vantage_encoding = &slots[*vantage].face_encoding;
+
+    let node = nodes.len();
+    nodes.push(Node {
+        slot: *vantage,
+        radius: 0.0,
+        inside: None,
+        outside: None,
+    });
+
+    if rest.is_empty() {
+        return Some(node);
+    }
+
+    let mut distances: Vec<(usize, f64)> = rest
+        .iter()
+        .map(|position| {
+            (
+                *position,
+                vantage_encoding.distance(&slots[*position].face_encoding),
+            )
+        })
+        .collect();
+
+    // Everything before the median is no further than it, and everything after no closer.
+    let median = distances.len() / 2;
+    distances.select_nth_unstable_by(median, |(_, a), (_, b)| {
+        a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let radius = distances[median].1;
+
+    for (position, (distance_position, _)) in rest.iter_mut().zip(distances.into_iter()) {
+        *position = distance_position;
+    }
+
+    let (inside, outside) = rest.split_at_mut(median);
+    let inside = build(slots, nodes, inside);
+    let outside = build(slots, nodes, outside);
+
+    nodes[node].radius = radius;
+    nodes[node].inside = inside;
+    nodes[node].outside = outside;
+
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaceIndex;
+    use crate::db::model::{face_encoding::ELEMENT_COUNT, FaceEncoding};
+
+    /// Encodings scattered by a fixed seed, so that any failure can be reproduced.
+    fn encodings(count: usize, seed: u64) -> Vec<FaceEncoding> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                let mut elements = [0f64; ELEMENT_COUNT];
+                for element in elements.iter_mut() {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    *element = (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5;
+                }
+                FaceEncoding::new(elements)
+            })
+            .collect()
+    }
+
+    /// The keys of the `count` faces closest to `face_encoding`, found the slow way.
+    fn nearest_by_scanning(
+        faces: &[(i32, FaceEncoding)],
+        face_encoding: &FaceEncoding,
+        count: usize,
+        max_distance: f64,
+    ) -> Vec<i32> {
+        let mut found: Vec<(f64, i32)> = faces
+            .iter()
+            .map(|(key, other)| (other.distance(face_encoding), *key))
+            .filter(|(distance, _)| *distance < max_distance)
+            .collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        found.into_iter().take(count).map(|(_, key)| key).collect()
+    }
+
+    fn nearest_keys(
+        index: &FaceIndex<()>,
+        face_encoding: &FaceEncoding,
+        count: usize,
+        max_distance: f64,
+    ) -> Vec<i32> {
+        index
+            .nearest(face_encoding, count, max_distance, |key, _| Some(key))
+            .into_iter()
+            .map(|neighbour| neighbour.key)
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_same_faces_as_a_scan() {
+        let faces: Vec<(i32, FaceEncoding)> =
+            (0..).zip(encodings(500, 1).into_iter()).collect();
+        let index = FaceIndex::new(
+            faces
+                .iter()
+                .map(|(key, face_encoding)| (*key, face_encoding.clone(), ())),
+        );
+
+        for query in encodings(20, 2).iter() {
+            for &(count, max_distance) in [(1, 10.0), (5, 10.0), (10, 4.2)].iter() {
+                assert_eq!(
+                    nearest_keys(&index, query, count, max_distance),
+                    nearest_by_scanning(&faces, query, count, max_distance)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_same_faces_as_a_scan_after_changes() {
+        let mut faces: Vec<(i32, FaceEncoding)> =
+            (0..).zip(encodings(100, 3).into_iter()).collect();
+        let mut index = FaceIndex::new(
+            faces
+                .iter()
+                .map(|(key, face_encoding)| (*key, face_encoding.clone(), ())),
+        );
+
+        // Enough of each to leave some unindexed or removed, and to force a rebuild or two.
+        for (key, face_encoding) in (100..).zip(encodings(200, 4).into_iter()) {
+            index.insert(key, face_encoding.clone(), ());
+            faces.push((key, face_encoding));
+        }
+        for key in (0..300).step_by(3) {
+            assert!(index.remove(key).is_some());
+        }
+        faces.retain(|(key, _)| key % 3 != 0);
+
+        assert_eq!(index.len(), faces.len());
+
+        for query in encodings(20, 5).iter() {
+            assert_eq!(
+                nearest_keys(&index, query, 5, 10.0),
+                nearest_by_scanning(&faces, query, 5, 10.0)
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_closest_face_of_each_group() {
+        let faces = encodings(64, 6);
+        let index = FaceIndex::new(
+            (0..)
+                .zip(faces.iter())
+                .map(|(key, face_encoding)| (key, face_encoding.clone(), key % 4)),
+        );
+
+        let found = index.nearest(&faces[0], 10, 10.0, |_, group| {
+            if *group == 3 {
+                None
+            } else {
+                Some(*group)
+            }
+        });
+
+        let mut groups: Vec<i32> = found.iter().map(|neighbour| *neighbour.value).collect();
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].key, 0);
+        assert!(found
+            .windows(2)
+            .all(|pair| pair[0].distance <= pair[1].distance));
+        groups.sort_unstable();
+        assert_eq!(groups, vec![0, 1, 2]);
+
+        for neighbour in found.iter() {
+            let closest = (0..)
+                .zip(faces.iter())
+                .filter(|(key, _)| key % 4 == *neighbour.value)
+                .map(|(_, face_encoding)| face_encoding.distance(&faces[0]))
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(neighbour.distance, closest);
+        }
+    }
+
+    #[test]
+    fn replaces_and_removes_faces_by_key() {
+        let faces = encodings(3, 7);
+        let mut index =
+            FaceIndex::new(vec![(1, faces[0].clone(), "a"), (2, faces[1].clone(), "b")]);
+
+        index.insert(1, faces[2].clone(), "c");
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get(1), Some((&faces[2], &"c")));
+
+        assert_eq!(index.remove(2), Some((faces[1].clone(), "b")));
+        assert_eq!(index.remove(2), None);
+        assert!(index.get(2).is_none());
+        assert_eq!(index.len(), 1);
+
+        let found = index.nearest(&faces[1], 5, 10.0, |key, _| Some(key));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].key, 1);
+    }
+
+    #[test]
+    fn finds_nothing_beyond_the_maximum_distance() {
+        let faces = encodings(2, 8);
+        let index = FaceIndex::new(vec![(1, faces[0].clone(), ())]);
+        let distance = faces[0].distance(&faces[1]);
+
+        assert!(nearest_keys(&index, &faces[1], 1, distance).is_empty());
+        assert_eq!(nearest_keys(&index, &faces[1], 1, distance + 0.01), vec![1]);
+        assert!(nearest_keys(&index, &faces[1], 0, 10.0).is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+/// What matching needs to know about a face besides its encoding.
+#[derive(Clone, Copy)]
+pub struct Face {
+    pub person: Option<i32>,
+    pub reference: bool,
+    pub confirmed: bool,
+}
+
+impl Face {
+    /// Whether the face can be trusted to show what its person looks like.
+    fn is_known(&self) -> bool {
+        self.person.is_some() && (self.reference || self.confirmed)
+    }
+}
+
+/// The known face closest to a new one, and how far apart they are.
+pub struct Match {
+    pub person: i32,
+    pub appearance: i32,
+    pub distance: f64,
+}
+
+/// Two people whose known faces look alike, where `dst` has at least as many of them as `src`.
+pub struct SimilarPeople {
+    pub dst: i32,
+    pub src: i32,
+    pub distance: f64,
+}
+
+/// An appearance that has been corrected away from `person`. A face that looks at least as much
+/// like it as like `person` isn't matched to them.
+struct Exclusion {
+    appearance: i32,
+    person: i32,
+}
+
+/// In-memory copy of every face encoded by the current model, indexed by encoding, that new faces
+/// are matched against, leaving out those in photos in the trash. Only confirmed faces and
+/// references are known well enough to be matched to.
+pub struct KnownFaces {
+    faces: super::index::FaceIndex<Face>,
+    exclusions: Vec<Exclusion>,
+}
+
+impl KnownFaces {
+    pub async fn fetch(
+        db: &crate::db::System,
+        encoder: String,
+    ) -> Result<Self, crate::db::QueryError> {
+        let faces = db
+            .run_query(move |db_connection| {
+                use crate::db::schema::{appearances, trashed_photos};
+                use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+                appearances::table
+                    .select((
+                        appearances::id,
+                        appearances::person,
+                        appearances::reference,
+                        appearances::confirmed,
+                        appearances::face_encoding,
+                    ))
+                    .filter(appearances::encoder.eq(encoder))
+                    .filter(
+                        appearances::photo
+                            .ne_all(trashed_photos::table.select(trashed_photos::photo)),
+                    )
+                    .load::<(i32, Option<i32>, bool, bool, crate::db::model::FaceEncoding)>(
+                        &db_connection,
+                    )
+            })
+            .await?;
+
+        let exclusions = crate::db::model::exclusion::fetch_all_for_appearances(db)
+            .await?
+            .into_iter()
+            .map(|(appearance, person)| Exclusion { appearance, person })
+            .collect();
+
+        Ok(Self {
+            faces: super::index::FaceIndex::new(faces.into_iter().map(
+                |(appearance, person, reference, confirmed, face_encoding)| {
+                    (
+                        appearance,
+                        face_encoding,
+                        Face {
+                            person,
+                            reference,
+                            confirmed,
+                        },
+                    )
+                },
+            )),
+            exclusions,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Finds the person that `face_encoding` belongs to according to `matcher`, provided the
+    /// face doesn't look at least as much like one of the appearances excluded from them.
+    pub fn closest(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        matcher: &super::Matcher,
+    ) -> Option<Match> {
+        let candidates = match matcher.strategy {
+            super::Strategy::Nearest => self.nearest(face_encoding, matcher.tolerance),
+            super::Strategy::Vote { neighbour_count } => {
+                self.vote(face_encoding, matcher.tolerance, neighbour_count)
+            }
+            super::Strategy::Centroid => self.centroid(face_encoding, matcher.tolerance),
+        };
+
+        candidates.into_iter().find(|candidate| {
+            !self.exclusions.iter().any(|exclusion| {
+                exclusion.person == candidate.person
+                    && self
+                        .faces
+                        .get(exclusion.appearance)
+                        .map(|(excluded_encoding, _)| {
+                            excluded_encoding.distance(face_encoding) <= candidate.distance
+                        })
+                        .unwrap_or(false)
+            })
+        })
+    }
+
+    /// Every person with a reference face within `tolerance`, by the distance to the closest of
+    /// them, closest first.
+    fn nearest(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        tolerance: f64,
+    ) -> Vec<Match> {
+        self.faces
+            .nearest(face_encoding, usize::MAX, tolerance, |_, face| {
+                if face.reference {
+                    face.person
+                } else {
+                    None
+                }
+            })
+            .into_iter()
+            .filter_map(|neighbour| {
+                neighbour.value.person.map(|person| Match {
+                    person,
+                    appearance: neighbour.key,
+                    distance: neighbour.distance,
+                })
+            })
+            .collect()
+    }
+
+    /// Every person with a face amongst the `neighbour_count` closest known ones within
+    /// `tolerance`, most faces first, and then by the distance to the closest of them.
+    fn vote(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        tolerance: f64,
+        neighbour_count: usize,
+    ) -> Vec<Match> {
+        let neighbours = self.faces.nearest(
+            face_encoding,
+            neighbour_count,
+            tolerance,
+            |appearance, face| {
+                if face.is_known() {
+                    Some(appearance)
+                } else {
+                    None
+                }
+            },
+        );
+
+        let mut votes = std::collections::HashMap::<i32, usize>::new();
+        let mut candidates = Vec::<Match>::new();
+        for neighbour in neighbours.into_iter() {
+            if let Some(person) = neighbour.value.person {
+                let count = votes.entry(person).or_insert(0);
+                // Neighbours come closest first, so the first of each person is their closest.
+                if *count == 0 {
+                    candidates.push(Match {
+                        person,
+                        appearance: neighbour.key,
+                        distance: neighbour.distance,
+                    });
+                }
+                *count += 1;
+            }
+        }
+
+        // The sort is stable, so people with as many votes stay closest first.
+        candidates.sort_by(|a, b| votes[&b.person].cmp(&votes[&a.person]));
+        candidates
+    }
+
+    /// Every person whose mean known face is within `tolerance`, closest first. Each is matched
+    /// through whichever of their faces is closest. Unlike the other strategies, this has to look
+    /// at every known face.
+    fn centroid(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        tolerance: f64,
+    ) -> Vec<Match> {
+        let mut faces = std::collections::HashMap::<
+            i32,
+            Vec<(i32, &crate::db::model::FaceEncoding)>,
+        >::new();
+        for (appearance, known_encoding, face) in self.faces.iter() {
+            if let (Some(person), true) = (face.person, face.is_known()) {
+                faces
+                    .entry(person)
+                    .or_insert_with(Vec::new)
+                    .push((appearance, known_encoding));
+            }
+        }
+
+        let mut candidates: Vec<Match> = faces
+            .into_iter()
+            .filter_map(|(person, known_faces)| {
+                let centroid = crate::db::model::FaceEncoding::centroid(
+                    known_faces.iter().map(|(_, known_encoding)| *known_encoding),
+                )?;
+                let distance = centroid.distance(face_encoding);
+                if distance >= tolerance {
+                    return None;
+                }
+
+                let appearance = known_faces
+                    .iter()
+                    .map(|(appearance, known_encoding)| {
+                        (*appearance, known_encoding.distance(face_encoding))
+                    })
+                    .fold(None, |closest: Option<(i32, f64)>, (appearance, distance)| {
+                        match closest {
+                            Some(closest) if closest.1 <= distance => Some(closest),
+                            _ => Some((appearance, distance)),
+                        }
+                    })?
+                    .0;
+
+                Some(Match {
+                    person,
+                    appearance,
+                    distance,
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.person.cmp(&b.person))
+        });
+        candidates
+    }
+
+    /// Ranks people by how close their nearest assigned face is to that of `appearance`,
+    /// returning at most `count` `(person, distance)` pairs, closest first. The face of
+    /// `appearance` itself and the faces of `excluded_people` aren't considered. Nobody is ranked
+    /// for an appearance that isn't known, as when it's yet to be encoded by the current model.
+    pub fn rank(&self, appearance: i32, excluded_people: &[i32], count: usize) -> Vec<(i32, f64)> {
+        let face_encoding = match self.faces.get(appearance) {
+            Some((face_encoding, _)) => face_encoding,
+            None => return Vec::new(),
+        };
+
+        self.rank_people(face_encoding, count, |other_appearance, person| {
+            other_appearance != appearance && !excluded_people.contains(&person)
+        })
+    }
+
+    /// Ranks people by how close their nearest assigned face is to `face_encoding`, returning at
+    /// most `count` `(person, distance)` pairs, closest first.
+    pub fn search(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        count: usize,
+    ) -> Vec<(i32, f64)> {
+        self.rank_people(face_encoding, count, |_, _| true)
+    }
+
+    /// Ranks people as `search` does, only considering the assigned faces that `consider`
+    /// accepts, given their appearance and person.
+    fn rank_people<F>(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        count: usize,
+        consider: F,
+    ) -> Vec<(i32, f64)>
+    where
+        F: Fn(i32, i32) -> bool,
+    {
+        self.faces
+            .nearest(
+                face_encoding,
+                count,
+                std::f64::INFINITY,
+                |appearance, face| {
+                    face.person
+                        .filter(|person| consider(appearance, *person))
+                },
+            )
+            .into_iter()
+            .filter_map(|neighbour| {
+                neighbour
+                    .value
+                    .person
+                    .map(|person| (person, neighbour.distance))
+            })
+            .collect()
+    }
+
+    /// Pairs up the people whose known faces are closer than `max_distance` by `linkage`,
+    /// closest first, as candidates for being the same person.
+    pub fn similar_people(
+        &self,
+        linkage: super::Linkage,
+        max_distance: f64,
+    ) -> Vec<SimilarPeople> {
+        let mut faces =
+            std::collections::HashMap::<i32, Vec<&crate::db::model::FaceEncoding>>::new();
+        for (_, known_encoding, face) in self.faces.iter() {
+            if let (Some(person), true) = (face.person, face.is_known()) {
+                faces
+                    .entry(person)
+                    .or_insert_with(Vec::new)
+                    .push(known_encoding);
+            }
+        }
+
+        // Keyed by each pair of people, lowest first.
+        let mut distances = std::collections::HashMap::<(i32, i32), f64>::new();
+        match linkage {
+            super::Linkage::Centroid => {
+                let centroids: Vec<(i32, crate::db::model::FaceEncoding)> = faces
+                    .iter()
+                    .filter_map(|(person, known_encodings)| {
+                        crate::db::model::FaceEncoding::centroid(known_encodings.iter().copied())
+                            .map(|centroid| (*person, centroid))
+                    })
+                    .collect();
+
+                for (index, (a, a_centroid)) in centroids.iter().enumerate() {
+                    for (b, b_centroid) in centroids[index + 1..].iter() {
+                        let distance = a_centroid.distance(b_centroid);
+                        if distance < max_distance {
+                            distances.insert(
+                                (*std::cmp::min(a, b), *std::cmp::max(a, b)),
+                                distance,
+                            );
+                        }
+                    }
+                }
+            }
+            super::Linkage::Min => {
+                for (person, known_encodings) in faces.iter() {
+                    for known_encoding in known_encodings.iter() {
+                        let neighbours = self.faces.nearest(
+                            known_encoding,
+                            usize::MAX,
+                            max_distance,
+                            |_, face| {
+                                face.person
+                                    .filter(|other| other > person && face.is_known())
+                            },
+                        );
+
+                        for neighbour in neighbours.into_iter() {
+                            if let Some(other) = neighbour.value.person {
+                                let distance = distances
+                                    .entry((*person, other))
+                                    .or_insert(neighbour.distance);
+                                if neighbour.distance < *distance {
+                                    *distance = neighbour.distance;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut similar: Vec<SimilarPeople> = distances
+            .into_iter()
+            .map(|((a, b), distance)| {
+                let (dst, src) = if faces[&b].len() > faces[&a].len() {
+                    (b, a)
+                } else {
+                    (a, b)
+                };
+                SimilarPeople { dst, src, distance }
+            })
+            .collect();
+        similar.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (a.dst, a.src).cmp(&(b.dst, b.src)))
+        });
+        similar
+    }
+
+    pub fn insert(
+        &mut self,
+        appearance: i32,
+        face: Face,
+        face_encoding: crate::db::model::FaceEncoding,
+    ) {
+        self.faces.insert(appearance, face_encoding, face);
+    }
+
+    /// Forgets an appearance, along with anything that was remembered about who it isn't.
+    pub fn remove(&mut self, appearance: i32) {
+        self.faces.remove(appearance);
+        self.exclusions
+            .retain(|exclusion| exclusion.appearance != appearance);
+    }
+
+    pub fn merge(&mut self, dst_person: i32, src_person: i32) {
+        for face in self.faces.values_mut() {
+            if face.person == Some(src_person) {
+                face.person = Some(dst_person);
+            }
+        }
+
+        for exclusion in self.exclusions.iter_mut() {
+            if exclusion.person == src_person {
+                exclusion.person = dst_person;
+            }
+        }
+    }
+
+    /// Undoes a merge of `src_person` into `dst_person`, moving `appearances` back and restoring
+    /// what `src_person` was known not to be. Excluded appearances come along with whether
+    /// `dst_person` is still known not to be them.
+    pub fn unmerge(
+        &mut self,
+        dst_person: i32,
+        src_person: i32,
+        appearances: &[i32],
+        excluded_appearances: &[(i32, bool)],
+    ) {
+        for appearance in appearances.iter() {
+            if let Some(face) = self.faces.get_mut(*appearance) {
+                face.person = Some(src_person);
+            }
+        }
+
+        for (appearance, shared) in excluded_appearances.iter() {
+            if !shared {
+                self.exclusions.retain(|exclusion| {
+                    exclusion.appearance != *appearance || exclusion.person != dst_person
+                });
+            }
+
+            let excluded = self.exclusions.iter().any(|exclusion| {
+                exclusion.appearance == *appearance && exclusion.person == src_person
+            });
+            if !excluded {
+                self.exclusions.push(Exclusion {
+                    appearance: *appearance,
+                    person: src_person,
+                });
+            }
+        }
+    }
+
+    pub fn confirm(&mut self, appearance: i32) {
+        if let Some(face) = self.faces.get_mut(appearance) {
+            if face.person.is_some() {
+                face.confirmed = true;
+            }
+        }
+    }
+
+    /// Moves an appearance from `previous_person` to `person`, remembering that it isn't of its
+    /// previous person. An appearance assigned by hand counts as confirmed.
+    pub fn reassign(
+        &mut self,
+        appearance: i32,
+        previous_person: Option<i32>,
+        person: Option<i32>,
+        reference: bool,
+    ) {
+        match self.faces.get_mut(appearance) {
+            Some(face) => {
+                *face = Face {
+                    person,
+                    reference,
+                    confirmed: person.is_some(),
+                }
+            }
+            None => return,
+        }
+
+        if let Some(person) = person {
+            self.exclusions.retain(|exclusion| {
+                exclusion.appearance != appearance || exclusion.person != person
+            });
+        }
+
+        if let Some(previous_person) = previous_person {
+            let excluded = self.exclusions.iter().any(|exclusion| {
+                exclusion.appearance == appearance && exclusion.person == previous_person
+            });
+            if Some(previous_person) != person && !excluded {
+                self.exclusions.push(Exclusion {
+                    appearance,
+                    person: previous_person,
+                });
+            }
+        }
+    }
+
+    /// Reassigns an appearance that was left to automatic matching, as long as it still is.
+    pub fn rematch(&mut self, appearance: i32, person: Option<i32>) {
+        if let Some(face) = self.faces.get_mut(appearance) {
+            if !face.reference && !face.confirmed {
+                face.person = person;
+            }
+        }
+    }
+}
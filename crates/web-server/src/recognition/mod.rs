@@ -0,0 +1,514 @@
+pub mod clustering;
+mod dlib;
+mod fake;
+mod index;
+mod known_faces;
+pub mod quality;
+
+pub use known_faces::{Face, KnownFaces, Match};
+pub use quality::FaceQuality;
+
+/// How a new face is matched against the known ones.
+#[derive(Clone, Copy, Debug)]
+pub enum Strategy {
+    /// The person with the closest reference face.
+    Nearest,
+    /// The person with the most faces amongst the `neighbour_count` closest confirmed ones.
+    Vote { neighbour_count: usize },
+    /// The person whose mean confirmed face is closest.
+    Centroid,
+}
+
+/// How the distance between two people is measured from their known faces.
+#[derive(Clone, Copy, Debug)]
+pub enum Linkage {
+    /// The distance between their mean faces.
+    Centroid,
+    /// The distance between their closest two faces.
+    Min,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Matcher {
+    /// The furthest apart two encodings can be while still being considered the same person.
+    pub tolerance: f64,
+    pub strategy: Strategy,
+}
+
+/// How faces are looked for in an image.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Detector {
+    /// The fewest pixels along the shorter side of a face's box for it to be kept, so that tiny
+    /// faces in the background are passed over.
+    pub min_face_pixels: Option<u32>,
+    /// As `min_face_pixels`, as a fraction of the shorter side of the image.
+    pub min_face_fraction: Option<f64>,
+    /// How many times the image is doubled in size for detection, so that smaller faces are found.
+    pub upsample: u32,
+    /// The longest side of the image that faces are detected in. Larger images are detected in a
+    /// downscaled copy, which is quicker but may miss smaller faces.
+    pub max_image_size: Option<u32>,
+}
+
+impl Detector {
+    /// How much an image of the given size is scaled by before faces are detected in it.
+    fn scale(&self, width: u32, height: u32) -> f64 {
+        let longest_side = std::cmp::max(width, height);
+        let downscale = match self.max_image_size {
+            Some(max_image_size) if max_image_size > 0 && longest_side > max_image_size => {
+                max_image_size as f64 / longest_side as f64
+            }
+            _ => 1.0,
+        };
+
+        downscale * 2f64.powi(self.upsample as i32)
+    }
+
+    /// Whether a face found in an image of the given size is big enough to keep.
+    fn keeps(&self, face_box: &FaceBox, width: u32, height: u32) -> bool {
+        let face_side =
+            std::cmp::min(face_box.right - face_box.left, face_box.bottom - face_box.top);
+        let image_side = std::cmp::min(width, height);
+
+        self.min_face_pixels
+            .map_or(true, |min_face_pixels| face_side >= min_face_pixels as i32)
+            && self.min_face_fraction.map_or(true, |min_face_fraction| {
+                face_side as f64 >= min_face_fraction * image_side as f64
+            })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NewServiceError {
+    #[error("Failed to setup face landmark predictor: {0}")]
+    FaceLandmarkPredictorInitFailed(String),
+    #[error("Failed to setup face encoder: {0}")]
+    FaceEncoderInitFailed(String),
+    #[error("Failed to read face encoder model")]
+    FaceEncoderReadFailed(#[source] std::io::Error),
+    #[error("Failed to open fake face fixture")]
+    FakeFixtureOpenFailed(#[source] std::io::Error),
+    #[error("Failed to parse fake face fixture")]
+    FakeFixtureParseFailed(#[source] serde_json::Error),
+    #[error("At least one recognition thread is needed")]
+    NoThreads,
+    #[error("Failed to spawn recognition thread")]
+    ThreadSpawnFailed(#[source] std::io::Error),
+    #[error("Recognition thread exited during startup")]
+    ThreadExited,
+    #[error("Failed to fetch known faces")]
+    FetchKnownFacesFailed(#[source] crate::db::QueryError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("Face recognition service stopped")]
+    Stopped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceBox {
+    pub top: i32,
+    pub left: i32,
+    pub bottom: i32,
+    pub right: i32,
+}
+
+impl FaceBox {
+    pub fn area(&self) -> i64 {
+        std::cmp::max(self.right - self.left, 0) as i64
+            * std::cmp::max(self.bottom - self.top, 0) as i64
+    }
+
+    /// Whether two boxes overlap by at least half of the smaller one, and so likely frame the
+    /// same face.
+    pub fn coincides(&self, other: &FaceBox) -> bool {
+        let overlap = self.overlap(other);
+        overlap > 0 && overlap * 2 >= std::cmp::min(self.area(), other.area())
+    }
+
+    /// The area of the intersection of two boxes.
+    pub fn overlap(&self, other: &FaceBox) -> i64 {
+        let width = std::cmp::min(self.right, other.right) - std::cmp::max(self.left, other.left);
+        let height = std::cmp::min(self.bottom, other.bottom) - std::cmp::max(self.top, other.top);
+        if width > 0 && height > 0 {
+            width as i64 * height as i64
+        } else {
+            0
+        }
+    }
+
+    /// The box in an image resized by `scale`, for a box found in the resized image.
+    pub fn unscaled(&self, scale: f64) -> FaceBox {
+        let unscale = |coordinate: i32| (coordinate as f64 / scale).round() as i32;
+        FaceBox {
+            top: unscale(self.top),
+            left: unscale(self.left),
+            bottom: unscale(self.bottom),
+            right: unscale(self.right),
+        }
+    }
+
+    /// The area of the intersection of two boxes as a fraction of the area of their union.
+    pub fn similarity(&self, other: &FaceBox) -> f64 {
+        let overlap = self.overlap(other);
+        if overlap > 0 {
+            overlap as f64 / (self.area() + other.area() - overlap) as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct EncodedFace {
+    pub face_encoding: crate::db::model::FaceEncoding,
+    pub landmarks: crate::db::model::FaceLandmarks,
+}
+
+pub struct DetectedFace {
+    pub face_box: FaceBox,
+    pub face_encoding: crate::db::model::FaceEncoding,
+    pub landmarks: crate::db::model::FaceLandmarks,
+    pub quality: FaceQuality,
+}
+
+/// A face recognition backend. Each recognition thread owns its own instance.
+pub trait FaceRecognizer {
+    /// Locates the faces in an image, looking for them in a copy resized by `scale` but giving
+    /// their boxes in the original.
+    fn detect_faces(&mut self, image: &image::RgbImage, scale: f64) -> Vec<FaceBox>;
+
+    /// Encodes the faces within the given boxes, yielding `None` for any box in which a face
+    /// couldn't be made out.
+    fn encode_faces(
+        &mut self,
+        image: &image::RgbImage,
+        face_boxes: &[FaceBox],
+    ) -> Vec<Option<EncodedFace>>;
+}
+
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Dlib {
+        face_landmark_predictor_model_file_path: std::path::PathBuf,
+        face_encoder_model_file_path: std::path::PathBuf,
+    },
+    Fake {
+        fixture_file_path: std::path::PathBuf,
+    },
+}
+
+impl Backend {
+    fn load(&self) -> Result<Box<dyn FaceRecognizer>, NewServiceError> {
+        Ok(match self {
+            Self::Dlib {
+                face_landmark_predictor_model_file_path,
+                face_encoder_model_file_path,
+            } => Box::new(dlib::DlibFaceRecognizer::new(
+                face_landmark_predictor_model_file_path,
+                face_encoder_model_file_path,
+            )?),
+            Self::Fake { fixture_file_path } => {
+                Box::new(fake::FakeFaceRecognizer::load(fixture_file_path)?)
+            }
+        })
+    }
+
+    /// Identifies the model that encodes faces. Encodings from different models can't be
+    /// compared with each other, so each is recorded along with the model that produced it.
+    fn encoder(&self) -> Result<String, NewServiceError> {
+        Ok(match self {
+            Self::Dlib {
+                face_encoder_model_file_path,
+                ..
+            } => format!(
+                "dlib:{}",
+                crate::db::model::Digest::compute(
+                    std::fs::read(face_encoder_model_file_path)
+                        .map_err(NewServiceError::FaceEncoderReadFailed)?
+                )
+            ),
+            Self::Fake { fixture_file_path } => format!(
+                "fake:{}",
+                crate::db::model::Digest::compute(
+                    std::fs::read(fixture_file_path)
+                        .map_err(NewServiceError::FakeFixtureOpenFailed)?
+                )
+            ),
+        })
+    }
+}
+
+fn detect_faces(
+    recognizer: &mut dyn FaceRecognizer,
+    image: &image::RgbImage,
+    detector: &Detector,
+) -> Vec<DetectedFace> {
+    let (width, height) = image.dimensions();
+
+    let face_boxes = recognizer.detect_faces(image, detector.scale(width, height));
+
+    log::debug!("  FOUND FACE COUNT {}", face_boxes.len());
+
+    let face_boxes = face_boxes
+        .into_iter()
+        .filter(|face_box| detector.keeps(face_box, width, height))
+        .collect();
+
+    encode_faces(recognizer, image, face_boxes)
+        .into_iter()
+        .filter_map(|face| {
+            if let Some(face) = &face {
+                log::debug!(
+                    "  FOUND FACE @ {} {} {} {}",
+                    face.face_box.top,
+                    face.face_box.left,
+                    face.face_box.bottom,
+                    face.face_box.right
+                );
+            }
+
+            face
+        })
+        .collect()
+}
+
+/// Encodes the faces within the given boxes and assesses how well each is shown.
+fn encode_faces(
+    recognizer: &mut dyn FaceRecognizer,
+    image: &image::RgbImage,
+    face_boxes: Vec<FaceBox>,
+) -> Vec<Option<DetectedFace>> {
+    let encoded_faces = recognizer.encode_faces(image, &face_boxes);
+
+    face_boxes
+        .into_iter()
+        .zip(encoded_faces.into_iter())
+        .map(|(face_box, encoded_face)| {
+            encoded_face.map(|encoded_face| DetectedFace {
+                quality: FaceQuality::assess(image, &face_box, &encoded_face.landmarks),
+                face_box,
+                face_encoding: encoded_face.face_encoding,
+                landmarks: encoded_face.landmarks,
+            })
+        })
+        .collect()
+}
+
+type Task = Box<dyn FnOnce(&mut dyn FaceRecognizer) + Send>;
+
+struct Inner {
+    task_tx: std::sync::Mutex<std::sync::mpsc::Sender<Task>>,
+    known_faces: std::sync::RwLock<KnownFaces>,
+    matcher: Matcher,
+    detector: Detector,
+    encoder: String,
+}
+
+/// Runs face detection and encoding on a fixed pool of threads, each of which owns a recognizer
+/// loaded at startup, and keeps the encodings used for matching in memory. Only faces encoded by
+/// the model in use are kept, as any others can't be compared with new ones.
+#[derive(Clone)]
+pub struct Service(std::sync::Arc<Inner>);
+
+impl Service {
+    pub async fn new(
+        db: &crate::db::System,
+        backend: Backend,
+        thread_count: usize,
+        matcher: Matcher,
+        detector: Detector,
+    ) -> Result<Self, NewServiceError> {
+        // Tasks would wait forever for a thread to pick them up.
+        if thread_count == 0 {
+            return Err(NewServiceError::NoThreads);
+        }
+
+        let encoder = backend.encoder()?;
+
+        let (task_tx, task_rx) = std::sync::mpsc::channel::<Task>();
+        let task_rx = std::sync::Arc::new(std::sync::Mutex::new(task_rx));
+
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        for index in 0..thread_count {
+            let task_rx = task_rx.clone();
+            let ready_tx = ready_tx.clone();
+            let backend = backend.clone();
+
+            std::thread::Builder::new()
+                .name(format!("recognition-{}", index))
+                .spawn(move || {
+                    let mut recognizer = match backend.load() {
+                        Ok(recognizer) => {
+                            let _ = ready_tx.send(Ok(()));
+                            recognizer
+                        }
+                        Err(err) => {
+                            let _ = ready_tx.send(Err(err));
+                            return;
+                        }
+                    };
+
+                    drop(ready_tx);
+
+                    loop {
+                        // Only hold the lock while waiting so other threads can pick up tasks
+                        // while this one is busy.
+                        let task = match task_rx.lock().unwrap().recv() {
+                            Ok(task) => task,
+                            Err(_) => break,
+                        };
+
+                        task(recognizer.as_mut());
+                    }
+                })
+                .map_err(NewServiceError::ThreadSpawnFailed)?;
+        }
+
+        drop(ready_tx);
+
+        for _ in 0..thread_count {
+            ready_rx.recv().map_err(|_| NewServiceError::ThreadExited)??;
+        }
+
+        let known_faces = KnownFaces::fetch(db, encoder.clone())
+            .await
+            .map_err(NewServiceError::FetchKnownFacesFailed)?;
+
+        log::debug!("FACE COUNT {}", known_faces.len());
+
+        Ok(Self(std::sync::Arc::new(Inner {
+            task_tx: std::sync::Mutex::new(task_tx),
+            known_faces: std::sync::RwLock::new(known_faces),
+            matcher,
+            detector,
+            encoder,
+        })))
+    }
+
+    pub async fn detect_faces(
+        &self,
+        image: image::RgbImage,
+    ) -> Result<Vec<DetectedFace>, ServiceError> {
+        self.detect_faces_with(image, self.0.detector).await
+    }
+
+    /// As `detect_faces`, but looking for faces in some other way than the one the service was
+    /// started with.
+    pub async fn detect_faces_with(
+        &self,
+        image: image::RgbImage,
+        detector: Detector,
+    ) -> Result<Vec<DetectedFace>, ServiceError> {
+        self.run(move |recognizer| detect_faces(recognizer, &image, &detector))
+            .await
+    }
+
+    /// Encodes the face within `face_box`, yielding `None` if a face couldn't be made out there.
+    pub async fn encode_face(
+        &self,
+        image: image::RgbImage,
+        face_box: FaceBox,
+    ) -> Result<Option<DetectedFace>, ServiceError> {
+        self.run(move |recognizer| {
+            encode_faces(recognizer, &image, vec![face_box])
+                .pop()
+                .and_then(|face| face)
+        })
+        .await
+    }
+
+    /// As `encode_face`, for each of several boxes in the same image.
+    pub async fn encode_faces(
+        &self,
+        image: image::RgbImage,
+        face_boxes: Vec<FaceBox>,
+    ) -> Result<Vec<Option<DetectedFace>>, ServiceError> {
+        self.run(move |recognizer| encode_faces(recognizer, &image, face_boxes))
+            .await
+    }
+
+    /// Finds the known face that a new one belongs with, if any are close enough.
+    pub fn identify(&self, face_encoding: &crate::db::model::FaceEncoding) -> Option<Match> {
+        self.identify_with(face_encoding, &self.0.matcher)
+    }
+
+    /// As `identify`, but matching in some other way than the one the service was started with.
+    pub fn identify_with(
+        &self,
+        face_encoding: &crate::db::model::FaceEncoding,
+        matcher: &Matcher,
+    ) -> Option<Match> {
+        self.known_faces().closest(face_encoding, matcher)
+    }
+
+    pub fn matcher(&self) -> Matcher {
+        self.0.matcher
+    }
+
+    pub fn detector(&self) -> Detector {
+        self.0.detector
+    }
+
+    /// Identifies the model that encodes faces, as recorded against each appearance.
+    pub fn encoder(&self) -> &str {
+        &self.0.encoder
+    }
+
+    pub fn known_faces(&self) -> std::sync::RwLockReadGuard<KnownFaces> {
+        self.0.known_faces.read().unwrap()
+    }
+
+    pub fn known_faces_mut(&self) -> std::sync::RwLockWriteGuard<KnownFaces> {
+        self.0.known_faces.write().unwrap()
+    }
+
+    async fn run<F, T>(&self, f: F) -> Result<T, ServiceError>
+    where
+        F: FnOnce(&mut dyn FaceRecognizer) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = futures::channel::oneshot::channel();
+
+        self.0
+            .task_tx
+            .lock()
+            .unwrap()
+            .send(Box::new(move |recognizer| {
+                let _ = result_tx.send(f(recognizer));
+            }))
+            .map_err(|_| ServiceError::Stopped)?;
+
+        result_rx.await.map_err(|_| ServiceError::Stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[actix_rt::test]
+    async fn refuses_to_start_without_threads() {
+        let dir_path =
+            std::env::temp_dir().join(format!("photosd-recognition-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir_path);
+        std::fs::create_dir_all(&dir_path).unwrap();
+
+        let db = crate::db::System::new(&dir_path.join("photos.db")).unwrap();
+        let result = super::Service::new(
+            &db,
+            super::Backend::Fake {
+                fixture_file_path: dir_path.join("fixture.json"),
+            },
+            0,
+            super::Matcher {
+                tolerance: 0.6,
+                strategy: super::Strategy::Nearest,
+            },
+            super::Detector::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(super::NewServiceError::NoThreads)));
+
+        std::fs::remove_dir_all(&dir_path).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+//! Judges how well a face shows its person, so the best of their faces can be picked for their
+//! avatar. Sharpness and frontalness depend on the image and are measured when the face is
+//! detected, whereas size and recency are known from the appearance and its photo.
+
+/// The side of the square that a face is resampled to before its sharpness is measured, so that
+/// faces of different sizes are measured alike.
+const SHARPNESS_SAMPLE_SIZE: u32 = 128;
+
+/// The Laplacian variance at which a face counts as half sharp.
+const SHARPNESS_SCALE: f64 = 100.0;
+
+/// The side of a face, in pixels, beyond which a bigger face is no better.
+const FULL_SIZE: f64 = 256.0;
+
+/// How long it takes for a photo to count half as recent.
+const RECENCY_HALF_LIFE_YEARS: f64 = 5.0;
+
+const SIZE_WEIGHT: f64 = 0.3;
+const SHARPNESS_WEIGHT: f64 = 0.3;
+const FRONTALNESS_WEIGHT: f64 = 0.25;
+const RECENCY_WEIGHT: f64 = 0.15;
+
+/// Stands in for a measure that wasn't taken.
+const NEUTRAL: f64 = 0.5;
+
+/// The measures of a face that depend on the image it was found in, each between 0 and 1.
+#[derive(Clone, Copy, Debug)]
+pub struct FaceQuality {
+    pub sharpness: f64,
+    /// Only known where the recognizer locates the face's landmarks.
+    pub frontalness: Option<f64>,
+}
+
+impl FaceQuality {
+    pub fn assess(
+        image: &image::RgbImage,
+        face_box: &super::FaceBox,
+        landmarks: &crate::db::model::FaceLandmarks,
+    ) -> Self {
+        Self {
+            sharpness: sharpness(image, face_box),
+            frontalness: frontalness(landmarks),
+        }
+    }
+}
+
+/// Measures sharpness as the variance of the Laplacian of the face, which is high where there are
+/// crisp edges and low where the face is blurred.
+fn sharpness(image: &image::RgbImage, face_box: &super::FaceBox) -> f64 {
+    let left = std::cmp::max(face_box.left, 0) as u32;
+    let top = std::cmp::max(face_box.top, 0) as u32;
+    let right = std::cmp::min(face_box.right.max(0) as u32, image.width());
+    let bottom = std::cmp::min(face_box.bottom.max(0) as u32, image.height());
+    if right <= left || bottom <= top {
+        return 0.0;
+    }
+
+    let face = image::imageops::grayscale(&image::imageops::crop_imm(
+        image,
+        left,
+        top,
+        right - left,
+        bottom - top,
+    ));
+    let face = image::imageops::resize(
+        &face,
+        SHARPNESS_SAMPLE_SIZE,
+        SHARPNESS_SAMPLE_SIZE,
+        image::imageops::FilterType::Triangle,
+    );
+
+    let pixel = |x: u32, y: u32| face.get_pixel(x, y)[0] as f64;
+
+    let mut laplacians = Vec::with_capacity(
+        ((SHARPNESS_SAMPLE_SIZE - 2) * (SHARPNESS_SAMPLE_SIZE - 2)) as usize,
+    );
+    for y in 1..SHARPNESS_SAMPLE_SIZE - 1 {
+        for x in 1..SHARPNESS_SAMPLE_SIZE - 1 {
+            laplacians.push(
+                pixel(x - 1, y) + pixel(x + 1, y) + pixel(x, y - 1) + pixel(x, y + 1)
+                    - 4.0 * pixel(x, y),
+            );
+        }
+    }
+
+    let mean = laplacians.iter().sum::<f64>() / laplacians.len() as f64;
+    let variance = laplacians
+        .iter()
+        .map(|laplacian| (laplacian - mean) * (laplacian - mean))
+        .sum::<f64>()
+        / laplacians.len() as f64;
+
+    variance / (variance + SHARPNESS_SCALE)
+}
+
+/// Measures how squarely a face is turned towards the camera from where the tip of the nose
+/// falls between the outer corners of the eyes, which is halfway for a frontal face.
+fn frontalness(landmarks: &crate::db::model::FaceLandmarks) -> Option<f64> {
+    // Indexes in the 68 point scheme.
+    const RIGHT_EYE_OUTER: usize = 36;
+    const LEFT_EYE_OUTER: usize = 45;
+    const NOSE_TIP: usize = 30;
+
+    let point = |index: usize| {
+        landmarks
+            .get(index)
+            .map(|(x, y)| (*x as f64, *y as f64))
+    };
+
+    let (right_eye_x, right_eye_y) = point(RIGHT_EYE_OUTER)?;
+    let (left_eye_x, left_eye_y) = point(LEFT_EYE_OUTER)?;
+    let (nose_x, nose_y) = point(NOSE_TIP)?;
+
+    let (eyes_x, eyes_y) = (left_eye_x - right_eye_x, left_eye_y - right_eye_y);
+    let eyes_length_squared = eyes_x * eyes_x + eyes_y * eyes_y;
+    if eyes_length_squared == 0.0 {
+        return None;
+    }
+
+    // How far along the line between the eyes the nose lies, which is unaffected by the head
+    // being tilted to one side.
+    let along = ((nose_x - right_eye_x) * eyes_x + (nose_y - right_eye_y) * eyes_y)
+        / eyes_length_squared;
+
+    Some(1.0 - ((along - 0.5).abs() * 2.0).min(1.0))
+}
+
+/// Scores a face for use as an avatar, higher being better. Measures that weren't taken, as for
+/// faces recorded before they were, count as middling.
+pub fn score(
+    face_box: &super::FaceBox,
+    sharpness: Option<f64>,
+    frontalness: Option<f64>,
+    taken: chrono::NaiveDateTime,
+    now: chrono::NaiveDateTime,
+) -> f64 {
+    let size = ((face_box.area() as f64).sqrt() / FULL_SIZE).min(1.0);
+
+    let age_years = std::cmp::max(now - taken, chrono::Duration::zero()).num_days() as f64 / 365.25;
+    let recency = 0.5f64.powf(age_years / RECENCY_HALF_LIFE_YEARS);
+
+    SIZE_WEIGHT * size
+        + SHARPNESS_WEIGHT * sharpness.unwrap_or(NEUTRAL)
+        + FRONTALNESS_WEIGHT * frontalness.unwrap_or(NEUTRAL)
+        + RECENCY_WEIGHT * recency
+}
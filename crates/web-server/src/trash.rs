@@ -0,0 +1,155 @@
+//! Moves the files of photos in the trash out of the way, into `photos` and `thumbs` directories
+//! under the trash directory that are laid out as the photo and thumb directories are, and purges
+//! photos that have been in the trash for longer than they're kept.
+//!
+//! Files are looked for in both places when they're moved back or purged, in case they couldn't be
+//! moved before.
+
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Clone)]
+pub struct Context {
+    pub db: crate::db::System,
+    pub recognition: crate::recognition::Service,
+    pub photo_dir_path: std::path::PathBuf,
+    pub thumb_dir_path: std::path::PathBuf,
+    pub avatar_dir_path: std::path::PathBuf,
+    pub trash_dir_path: std::path::PathBuf,
+    /// How long photos are kept in the trash before they're purged.
+    pub retention: chrono::Duration,
+}
+
+/// The paths of a photo's files outside of the trash and within it.
+fn file_paths(
+    photo_dir_path: &std::path::Path,
+    thumb_dir_path: &std::path::Path,
+    trash_dir_path: &std::path::Path,
+    file_name: &str,
+) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+    vec![
+        (
+            photo_dir_path.join(file_name),
+            trash_dir_path.join("photos").join(file_name),
+        ),
+        (
+            thumb_dir_path.join(file_name),
+            trash_dir_path.join("thumbs").join(file_name),
+        ),
+    ]
+}
+
+/// Moves the files of a photo that has just been put in the trash into the trash directory.
+pub async fn move_in(
+    photo_dir_path: &std::path::Path,
+    thumb_dir_path: &std::path::Path,
+    trash_dir_path: &std::path::Path,
+    file_name: &str,
+) {
+    crate::files::move_all(file_paths(
+        photo_dir_path,
+        thumb_dir_path,
+        trash_dir_path,
+        file_name,
+    ))
+    .await
+}
+
+/// Moves the files of a photo that has just been taken out of the trash back where they were.
+pub async fn move_out(
+    photo_dir_path: &std::path::Path,
+    thumb_dir_path: &std::path::Path,
+    trash_dir_path: &std::path::Path,
+    file_name: &str,
+) {
+    crate::files::move_all(
+        file_paths(photo_dir_path, thumb_dir_path, trash_dir_path, file_name)
+            .into_iter()
+            .map(|(file_path, trashed_file_path)| (trashed_file_path, file_path))
+            .collect(),
+    )
+    .await
+}
+
+/// Deletes a photo in the trash for good, along with its files and the avatars rendered from its
+/// faces.
+pub async fn purge(
+    db: &crate::db::System,
+    recognition: &crate::recognition::Service,
+    photo_dir_path: &std::path::Path,
+    thumb_dir_path: &std::path::Path,
+    avatar_dir_path: &std::path::Path,
+    trash_dir_path: &std::path::Path,
+    photo_id: i32,
+    delete_empty_people: bool,
+) -> Result<crate::db::model::photo::Deleted, crate::db::UpdateQueryError> {
+    let deleted = crate::db::model::Photo::delete(db, photo_id, delete_empty_people).await?;
+
+    {
+        let mut known_faces = recognition.known_faces_mut();
+        for appearance_id in deleted.appearances.iter() {
+            known_faces.remove(*appearance_id);
+        }
+    }
+
+    crate::files::remove_all(
+        file_paths(
+            photo_dir_path,
+            thumb_dir_path,
+            trash_dir_path,
+            &deleted.file_name,
+        )
+        .into_iter()
+        .flat_map(|(file_path, trashed_file_path)| vec![trashed_file_path, file_path])
+        .collect(),
+        deleted
+            .appearances
+            .iter()
+            .map(|appearance_id| avatar_dir_path.join(appearance_id.to_string()))
+            .collect(),
+    )
+    .await;
+
+    Ok(deleted)
+}
+
+/// Starts purging photos that have been in the trash for longer than they're kept, checking
+/// every so often on the current arbiter.
+pub fn spawn_sweeper(context: Context) {
+    actix_web::rt::spawn(sweep(context));
+}
+
+async fn sweep(context: Context) {
+    use actix_web::rt::time::delay_for;
+
+    loop {
+        let before = chrono::Utc::now().naive_utc() - context.retention;
+
+        match crate::db::model::Photo::fetch_ids_trashed_before(&context.db, before).await {
+            Ok(photo_ids) => {
+                if !photo_ids.is_empty() {
+                    log::info!("Purging {} photos from the trash", photo_ids.len());
+                }
+
+                for photo_id in photo_ids.into_iter() {
+                    if let Err(err) = purge(
+                        &context.db,
+                        &context.recognition,
+                        &context.photo_dir_path,
+                        &context.thumb_dir_path,
+                        &context.avatar_dir_path,
+                        &context.trash_dir_path,
+                        photo_id,
+                        false,
+                    )
+                    .await
+                    {
+                        log::error!("Failed to purge photo {} from the trash: {}", photo_id, err);
+                    }
+                }
+            }
+            Err(err) => log::error!("Failed to fetch the photos to purge from the trash: {}", err),
+        }
+
+        delay_for(SWEEP_INTERVAL).await
+    }
+}